@@ -1,3 +1,40 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() {
+    embed_public_key();
+    embed_build_time();
     tauri_build::build()
 }
+
+// 记录构建时间（Unix秒），作为运行时可信时间的下限
+fn embed_build_time() {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    println!("cargo:rustc-env=DRILLING_BUILD_TIMESTAMP={}", secs);
+}
+
+// 启用 embedded-public-key 功能时，将 DRILLING_EMBEDDED_PUBLIC_KEY 指定的公钥PEM复制到OUT_DIR供编译时嵌入
+fn embed_public_key() {
+    println!("cargo:rerun-if-env-changed=DRILLING_EMBEDDED_PUBLIC_KEY");
+    if env::var_os("CARGO_FEATURE_EMBEDDED_PUBLIC_KEY").is_none() {
+        return;
+    }
+
+    let source = env::var("DRILLING_EMBEDDED_PUBLIC_KEY")
+        .expect("启用 embedded-public-key 时必须通过 DRILLING_EMBEDDED_PUBLIC_KEY 指定公钥PEM文件");
+    println!("cargo:rerun-if-changed={}", source);
+
+    let pem = fs::read_to_string(&source)
+        .unwrap_or_else(|e| panic!("读取嵌入公钥 {} 失败: {}", source, e));
+    if !pem.contains("BEGIN PUBLIC KEY") {
+        panic!("嵌入公钥 {} 不是SPKI格式的PEM公钥", source);
+    }
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR 未设置"));
+    fs::write(out_dir.join("embedded_public_key.pem"), pem).expect("写入嵌入公钥失败");
+}
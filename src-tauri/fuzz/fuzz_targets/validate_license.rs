@@ -0,0 +1,9 @@
+#![no_main]
+
+// 运行: cd src-tauri/fuzz && cargo +nightly fuzz run validate_license
+use drilling_system_lib::fuzzing;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    fuzzing::validate_license_input(data);
+});
@@ -0,0 +1,42 @@
+use crate::clock;
+use crate::license::{file_error, get_app_dir, LicenseError};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+// 审计日志中的一条记录
+#[derive(Debug, Serialize)]
+struct AuditEntry<'a> {
+    timestamp: DateTime<Utc>,
+    action: &'a str,
+    license_id: &'a str,
+    detail: &'a str,
+}
+
+// 审计日志文件路径，每行一条JSON记录
+fn get_audit_log_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("audit.log"))
+}
+
+// 追加一条审计记录
+pub fn record(action: &str, license_id: &str, detail: &str) -> Result<(), LicenseError> {
+    let entry = AuditEntry {
+        timestamp: clock::now(),
+        action,
+        license_id,
+        detail,
+    };
+    let line = serde_json::to_string(&entry)
+        .map_err(|e| LicenseError::SerializationError(format!("序列化审计记录失败: {}", e)))?;
+
+    let path = get_audit_log_path()?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| file_error(&e, &path, "写入", format!("打开审计日志失败: {}", e)))?;
+    writeln!(file, "{}", line)
+        .map_err(|e| file_error(&e, &path, "写入", format!("写入审计日志失败: {}", e)))
+}
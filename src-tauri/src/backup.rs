@@ -0,0 +1,165 @@
+use crate::audit;
+use crate::license::{
+    file_error, get_all_licenses, is_signed_by_known_key, LicenseDatabase, LicenseError,
+};
+use crate::store;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// 备份文件格式版本
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+// 加密备份文件：Argon2id 由口令派生密钥，AES-256-GCM 加密数据库JSON
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedBackup {
+    format_version: u32,
+    kdf: String,
+    cipher: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// 由口令和盐值派生AES密钥
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], LicenseError> {
+    if passphrase.is_empty() {
+        return Err(LicenseError::ValidationError(
+            "备份口令不能为空".to_string(),
+        ));
+    }
+
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| LicenseError::ValidationError(format!("派生备份密钥失败: {}", e)))?;
+    Ok(key)
+}
+
+fn decode_field(value: &str, label: &str) -> Result<Vec<u8>, LicenseError> {
+    general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| LicenseError::SerializationError(format!("备份文件{}无效: {}", label, e)))
+}
+
+// 将整个许可证数据库加密导出到指定文件，返回导出的许可证数量
+pub fn backup_database(path: &Path, passphrase: &str) -> Result<usize, LicenseError> {
+    // 读取期间持有数据库锁，避免备份到并发修改的中间状态
+    let db = {
+        let _lock = store::lock_license_db();
+        LicenseDatabase {
+            licenses: get_all_licenses()?,
+            ..Default::default()
+        }
+    };
+    let plaintext = serde_json::to_vec(&db)
+        .map_err(|e| LicenseError::SerializationError(format!("序列化数据库失败: {}", e)))?;
+
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key)
+        .map_err(|e| LicenseError::ValidationError(format!("初始化加密失败: {}", e)))?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| LicenseError::ValidationError("加密备份失败".to_string()))?;
+
+    let backup = EncryptedBackup {
+        format_version: BACKUP_FORMAT_VERSION,
+        kdf: "argon2id".to_string(),
+        cipher: "aes-256-gcm".to_string(),
+        salt: general_purpose::STANDARD.encode(salt),
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+    };
+    let json = serde_json::to_string_pretty(&backup)
+        .map_err(|e| LicenseError::SerializationError(format!("序列化备份失败: {}", e)))?;
+    fs::write(path, json)
+        .map_err(|e| file_error(&e, path, "写入", format!("写入备份文件失败: {}", e)))?;
+
+    Ok(db.licenses.len())
+}
+
+// 从加密备份恢复许可证数据库，返回恢复的许可证数量。
+// 所有许可证的签名都必须有效；当前数据库非空时必须显式确认才会覆盖
+pub fn restore_database(
+    path: &Path,
+    passphrase: &str,
+    confirm: bool,
+    profile: Option<&str>,
+) -> Result<usize, LicenseError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| file_error(&e, path, "读取", format!("读取备份文件失败: {}", e)))?;
+    let backup: EncryptedBackup = serde_json::from_str(&contents)
+        .map_err(|e| LicenseError::SerializationError(format!("解析备份文件失败: {}", e)))?;
+    if backup.format_version > BACKUP_FORMAT_VERSION {
+        return Err(LicenseError::ValidationError(format!(
+            "不支持的备份文件版本: {}",
+            backup.format_version
+        )));
+    }
+
+    let salt = decode_field(&backup.salt, "盐值")?;
+    let nonce = decode_field(&backup.nonce, "随机数")?;
+    let ciphertext = decode_field(&backup.ciphertext, "数据")?;
+    if nonce.len() != 12 {
+        return Err(LicenseError::SerializationError(
+            "备份文件随机数长度无效".to_string(),
+        ));
+    }
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key)
+        .map_err(|e| LicenseError::ValidationError(format!("初始化解密失败: {}", e)))?;
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| LicenseError::ValidationError("口令错误或备份文件已损坏".to_string()))?;
+    let value = serde_json::from_slice(&plaintext)
+        .map_err(|e| LicenseError::SerializationError(format!("解析备份数据失败: {}", e)))?;
+    // 旧版程序创建的备份与数据库文件一样按结构版本升级
+    let db = store::upgrade_database(value)?;
+
+    // 写入前校验每个许可证的签名
+    let mut invalid = Vec::new();
+    for license in &db.licenses {
+        if !is_signed_by_known_key(license, profile)? {
+            invalid.push(license.license_id.clone());
+        }
+    }
+    if !invalid.is_empty() {
+        return Err(LicenseError::ValidationError(format!(
+            "备份中有{}个许可证签名无效: {}",
+            invalid.len(),
+            invalid.join(", ")
+        )));
+    }
+
+    let restored = db.licenses.len();
+    let existing = store::update_license_db(|current| {
+        let existing = current.licenses.len();
+        if existing > 0 && !confirm {
+            return Err(LicenseError::ValidationError(format!(
+                "恢复将覆盖现有的{}个许可证，请确认后重试",
+                existing
+            )));
+        }
+        *current = db;
+        Ok(existing)
+    })?;
+    audit::record(
+        "restore",
+        "",
+        &format!("从备份恢复{}个许可证，覆盖{}个", restored, existing),
+    )?;
+
+    Ok(restored)
+}
@@ -0,0 +1,66 @@
+// 签名验证性能测试，仅在启用 benchmark 功能时编译
+use crate::clock;
+use crate::license::{
+    clear_validation_cache, has_signing_capability, sign_license, validate_license, LicenseError,
+    LicenseInfo,
+};
+use chrono::Duration;
+use serde::Serialize;
+use std::time::Instant;
+
+// 单次测试的最大迭代次数
+const MAX_ITERATIONS: u32 = 1_000_000;
+
+/// 验证性能测试结果
+#[derive(Debug, Serialize)]
+pub struct BenchmarkResult {
+    pub iterations: u32,
+    pub total_ms: f64,
+    pub verifications_per_sec: f64,
+    // 所有迭代的验证结果都有效，否则测试结果不具代表性
+    pub all_valid: bool,
+}
+
+/// 用当前密钥签发一个测试许可证（不写入数据库），计时执行 iterations 次完整验证。
+/// 每次验证前清空签名校验缓存，测得的是实际的签名验证速度
+pub fn benchmark_verification(
+    iterations: u32,
+    profile: Option<&str>,
+) -> Result<BenchmarkResult, LicenseError> {
+    // 不自动生成密钥，避免在没有签发能力的安装上留下新密钥
+    if !has_signing_capability(profile) {
+        return Err(LicenseError::ValidationError(
+            "性能测试需要私钥，请先生成或导入密钥对".to_string(),
+        ));
+    }
+
+    let iterations = iterations.clamp(1, MAX_ITERATIONS);
+    let now = clock::now();
+    let license_key = sign_license(
+        LicenseInfo {
+            license_id: "benchmark".to_string(),
+            customer_name: "benchmark".to_string(),
+            customer_email: "benchmark@localhost".to_string(),
+            issue_date: now,
+            expiry_date: now + Duration::days(1),
+            features: vec!["benchmark".to_string()],
+            ..Default::default()
+        },
+        profile,
+    )?;
+
+    let mut all_valid = true;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        clear_validation_cache();
+        all_valid &= validate_license(&license_key, profile)?.is_valid;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    Ok(BenchmarkResult {
+        iterations,
+        total_ms: elapsed * 1000.0,
+        verifications_per_sec: iterations as f64 / elapsed.max(f64::EPSILON),
+        all_valid,
+    })
+}
@@ -0,0 +1,104 @@
+use crate::license::{
+    encode_license_key, export_public_key, file_error, get_license_by_id, has_public_key,
+    install_public_key, public_key_fingerprint, public_key_pem_fingerprint, validate_license,
+    LicenseError, LicenseInfo, LicenseValidationResult,
+};
+use crate::{policy, remote_key, settings};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// 许可证包格式版本
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+// 许可证包：许可证密钥、签发公钥及其指纹，以及便于阅读的许可证信息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicenseBundle {
+    pub format_version: u32,
+    pub license_key: String,
+    pub public_key_pem: String,
+    pub public_key_fingerprint: String,
+    pub info: LicenseInfo,
+}
+
+// 将数据库中的许可证连同公钥导出为单个JSON文件
+pub fn export_license_bundle(
+    license_id: &str,
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<(), LicenseError> {
+    let info = get_license_by_id(license_id)?
+        .ok_or_else(|| LicenseError::NotFound(license_id.to_string()))?;
+
+    let bundle = LicenseBundle {
+        format_version: BUNDLE_FORMAT_VERSION,
+        license_key: encode_license_key(&info)?,
+        public_key_pem: export_public_key(profile)?,
+        public_key_fingerprint: public_key_fingerprint(profile)?,
+        info,
+    };
+
+    let json = serde_json::to_string_pretty(&bundle)
+        .map_err(|e| LicenseError::SerializationError(format!("序列化许可证包失败: {}", e)))?;
+    fs::write(path, json)
+        .map_err(|e| file_error(&e, path, "写入", format!("写入许可证包失败: {}", e)))
+}
+
+// 导入许可证包并验证其中的许可证。包内的指纹和公钥来自同一个文件，不能证明公钥可信：
+// 已安装公钥时只用已安装的公钥验证（包内公钥必须与其一致）；未安装时，包内公钥须与
+// 另行从签发方获得的 expected_fingerprint、已固定的远程公钥或受信任指纹列表一致才会安装
+pub fn import_license_bundle(
+    path: &Path,
+    expected_fingerprint: Option<&str>,
+    profile: Option<&str>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| file_error(&e, path, "读取", format!("读取许可证包失败: {}", e)))?;
+    let bundle: LicenseBundle = serde_json::from_str(&contents)
+        .map_err(|e| LicenseError::SerializationError(format!("解析许可证包失败: {}", e)))?;
+
+    if bundle.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(LicenseError::ValidationError(format!(
+            "不支持的许可证包版本: {}",
+            bundle.format_version
+        )));
+    }
+
+    let fingerprint = public_key_pem_fingerprint(&bundle.public_key_pem)?;
+    if !fingerprint.eq_ignore_ascii_case(bundle.public_key_fingerprint.trim()) {
+        return Err(LicenseError::ValidationError(
+            "许可证包中的公钥与其指纹不一致".to_string(),
+        ));
+    }
+    match expected_fingerprint {
+        Some(expected) => {
+            let expected = settings::normalize_fingerprints(vec![expected.to_string()])?;
+            if !expected.contains(&fingerprint) {
+                return Err(LicenseError::ValidationError(
+                    "许可证包中的公钥与提供的指纹不一致".to_string(),
+                ));
+            }
+        }
+        None if !has_public_key(profile) && !is_trusted_bundle_key(&fingerprint, profile)? => {
+            return Err(LicenseError::ValidationError(
+                "尚未安装公钥，导入许可证包需要提供从签发方另行获得的公钥指纹".to_string(),
+            ));
+        }
+        None => {}
+    }
+    // 已安装公钥时只检查一致性，不会替换已安装的公钥
+    install_public_key(&bundle.public_key_pem, None, profile)?;
+
+    validate_license(&bundle.license_key, profile)
+}
+
+// 未安装公钥时，包内公钥是否已由固定的远程公钥或受信任指纹列表认可
+fn is_trusted_bundle_key(fingerprint: &str, profile: Option<&str>) -> Result<bool, LicenseError> {
+    if remote_key::pinned_fingerprint(profile)?.is_some_and(|pinned| pinned == fingerprint) {
+        return Ok(true);
+    }
+    Ok(
+        policy::trusted_key_fingerprints(&policy::get_validation_policy()?)?
+            .is_some_and(|trusted| trusted.iter().any(|trusted| trusted == fingerprint)),
+    )
+}
@@ -0,0 +1,276 @@
+// 确定性CBOR（RFC 8949 4.2 节）编解码，只支持JSON能表示的数据：
+// 整数使用最短编码，映射的键按编码后的字节序排序，不使用不定长编码和标签。
+// 许可证载荷只需要这个子集，因此没有引入通用的CBOR库
+use serde_json::{Map, Number, Value};
+
+// 解码时允许的最大嵌套深度，许可证数据只有两三层
+const MAX_DEPTH: usize = 32;
+
+/// 将JSON值编码为确定性CBOR，相同的值总是得到相同的字节
+pub fn to_canonical_vec(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_value(value, &mut out);
+    out
+}
+
+// 写入数据项头部：主类型和最短编码的参数
+fn write_head(major: u8, arg: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match arg {
+        0..=23 => out.push(major | arg as u8),
+        24..=0xff => {
+            out.push(major | 24);
+            out.push(arg as u8);
+        }
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(arg as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(arg as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&arg.to_be_bytes());
+        }
+    }
+}
+
+fn encode_value(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(number) => encode_number(number, out),
+        Value::String(text) => {
+            write_head(3, text.len() as u64, out);
+            out.extend_from_slice(text.as_bytes());
+        }
+        Value::Array(items) => {
+            write_head(4, items.len() as u64, out);
+            for item in items {
+                encode_value(item, out);
+            }
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(Vec<u8>, &Value)> = map
+                .iter()
+                .map(|(key, value)| {
+                    let mut encoded_key = Vec::with_capacity(key.len() + 1);
+                    encode_value(&Value::String(key.clone()), &mut encoded_key);
+                    (encoded_key, value)
+                })
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+            write_head(5, entries.len() as u64, out);
+            for (key, value) in entries {
+                out.extend_from_slice(&key);
+                encode_value(value, out);
+            }
+        }
+    }
+}
+
+// 非负整数为主类型0，负整数为主类型1（编码 -1-n），其余数值为64位浮点数
+fn encode_number(number: &Number, out: &mut Vec<u8>) {
+    if let Some(n) = number.as_u64() {
+        write_head(0, n, out);
+    } else if let Some(n) = number.as_i64() {
+        write_head(1, !(n as u64), out);
+    } else {
+        out.push(0xfb);
+        out.extend_from_slice(&number.as_f64().unwrap_or(0.0).to_bits().to_be_bytes());
+    }
+}
+
+/// 将CBOR解码为JSON值，遇到不支持的类型、不完整的数据或多余的尾部字节时返回错误
+pub fn from_slice(data: &[u8]) -> Result<Value, String> {
+    let mut decoder = Decoder { data, pos: 0 };
+    let value = decoder.value(0)?;
+    if decoder.pos != data.len() {
+        return Err("CBOR数据末尾有多余的字节".to_string());
+    }
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| "CBOR数据不完整".to_string())?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    // 读取头部的参数
+    fn argument(&mut self, info: u8) -> Result<u64, String> {
+        let len = match info {
+            0..=23 => return Ok(info as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err("不支持不定长或保留的CBOR编码".to_string()),
+        };
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64))
+    }
+
+    // 读取长度，并确认剩余数据足够，避免按伪造的长度分配内存
+    fn length(&mut self, info: u8, min_item_size: usize) -> Result<usize, String> {
+        let len = self.argument(info)?;
+        let remaining = (self.data.len() - self.pos) as u64;
+        if len.saturating_mul(min_item_size as u64) > remaining {
+            return Err("CBOR数据不完整".to_string());
+        }
+        Ok(len as usize)
+    }
+
+    fn text(&mut self, info: u8) -> Result<String, String> {
+        let len = self.length(info, 1)?;
+        std::str::from_utf8(self.take(len)?)
+            .map(|text| text.to_string())
+            .map_err(|e| format!("CBOR文本不是有效的UTF-8: {}", e))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("CBOR数据嵌套过深".to_string());
+        }
+
+        let head = self.take(1)?[0];
+        let (major, info) = (head >> 5, head & 0x1f);
+        match major {
+            0 => Ok(Value::from(self.argument(info)?)),
+            1 => {
+                let n = self.argument(info)?;
+                i64::try_from(n)
+                    .map(|n| Value::from(-1 - n))
+                    .map_err(|_| "CBOR整数超出范围".to_string())
+            }
+            3 => self.text(info).map(Value::String),
+            4 => {
+                let len = self.length(info, 1)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            5 => {
+                let len = self.length(info, 2)?;
+                let mut map = Map::new();
+                for _ in 0..len {
+                    let key_head = self.take(1)?[0];
+                    if key_head >> 5 != 3 {
+                        return Err("CBOR映射的键必须是文本".to_string());
+                    }
+                    let key = self.text(key_head & 0x1f)?;
+                    let value = self.value(depth + 1)?;
+                    if map.insert(key, value).is_some() {
+                        return Err("CBOR映射中有重复的键".to_string());
+                    }
+                }
+                Ok(Value::Object(map))
+            }
+            7 => match info {
+                20 => Ok(Value::Bool(false)),
+                21 => Ok(Value::Bool(true)),
+                22 => Ok(Value::Null),
+                27 => {
+                    let bits = self.argument(info)?;
+                    Number::from_f64(f64::from_bits(bits))
+                        .map(Value::Number)
+                        .ok_or_else(|| "CBOR浮点数无效".to_string())
+                }
+                _ => Err("不支持的CBOR简单值".to_string()),
+            },
+            _ => Err("不支持的CBOR数据类型".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn map_keys_are_sorted_by_encoded_bytes() {
+        // 较短的键编码后的头部更小，排在前面；等长的键按字节序排列
+        let encoded = to_canonical_vec(&json!({ "bb": 1, "c": 3, "a": 2 }));
+        assert_eq!(
+            encoded,
+            [0xa3, 0x61, b'a', 0x02, 0x61, b'c', 0x03, 0x62, b'b', b'b', 0x01]
+        );
+    }
+
+    #[test]
+    fn integers_use_the_shortest_encoding() {
+        assert_eq!(to_canonical_vec(&json!(23)), [0x17]);
+        assert_eq!(to_canonical_vec(&json!(24)), [0x18, 0x18]);
+        assert_eq!(to_canonical_vec(&json!(500)), [0x19, 0x01, 0xf4]);
+        assert_eq!(to_canonical_vec(&json!(-1)), [0x20]);
+        assert_eq!(to_canonical_vec(&json!(-500)), [0x39, 0x01, 0xf3]);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let value = json!({
+            "license_id": "abc",
+            "features": ["basic", "seats=5"],
+            "expiry": -1,
+            "count": 4_294_967_296u64,
+            "ratio": 0.5,
+            "single_use": true,
+            "machine_code": null,
+            "metadata": { "region": "中国" },
+        });
+        assert_eq!(from_slice(&to_canonical_vec(&value)).unwrap(), value);
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut encoded = to_canonical_vec(&json!({ "a": 1 }));
+        encoded.push(0x00);
+        assert!(from_slice(&encoded).unwrap_err().contains("多余的字节"));
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        let encoded = [0xa2, 0x61, b'a', 0x01, 0x61, b'a', 0x02];
+        assert!(from_slice(&encoded).unwrap_err().contains("重复的键"));
+    }
+
+    #[test]
+    fn indefinite_lengths_are_rejected() {
+        // 不定长的数组、映射和文本
+        for encoded in [
+            &[0x9f, 0x01, 0xff][..],
+            &[0xbf, 0x61, b'a', 0x01, 0xff][..],
+            &[0x7f, 0x61, b'a', 0xff][..],
+        ] {
+            assert!(from_slice(encoded).unwrap_err().contains("不定长"));
+        }
+    }
+
+    #[test]
+    fn truncated_data_is_rejected() {
+        let encoded = to_canonical_vec(&json!({ "license_id": "abc" }));
+        assert!(from_slice(&encoded[..encoded.len() - 1]).is_err());
+        // 声明的长度超过剩余数据时不按该长度分配内存
+        assert!(from_slice(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+}
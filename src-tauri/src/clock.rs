@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::RwLock;
+use std::time::Duration as StdDuration;
+
+type NowProvider = Box<dyn Fn() -> DateTime<Utc> + Send + Sync>;
+
+// 可替换的时间来源，未设置时使用系统时钟
+static CLOCK: RwLock<Option<NowProvider>> = RwLock::new(None);
+
+/// 获取当前时间，所有与有效期相关的逻辑都应通过此函数取时间
+pub fn now() -> DateTime<Utc> {
+    let clock = CLOCK
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match clock.as_ref() {
+        Some(provider) => provider(),
+        None => Utc::now(),
+    }
+}
+
+/// 替换时间来源（用于测试过期等依赖时间的逻辑）
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn set_clock<F>(provider: F)
+where
+    F: Fn() -> DateTime<Utc> + Send + Sync + 'static,
+{
+    let mut clock = CLOCK
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *clock = Some(Box::new(provider));
+}
+
+/// 将时间固定在指定时刻
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn set_fixed_time(time: DateTime<Utc>) {
+    set_clock(move || time);
+}
+
+/// 恢复使用系统时钟
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn reset_clock() {
+    let mut clock = CLOCK
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *clock = None;
+}
+
+// 默认的NTP服务器
+pub const DEFAULT_NTP_SERVER: &str = "pool.ntp.org:123";
+
+// 默认允许的最大时钟偏差（秒）
+pub const DEFAULT_MAX_DRIFT_SECS: f64 = 300.0;
+
+// NTP时间戳（1900年起）与Unix时间戳的差值
+const NTP_UNIX_OFFSET_SECS: i64 = 2_208_988_800;
+
+// NTP秒数是32位的，2036年2月回绕到0（进入第1纪元）
+const NTP_ERA_SECS: i64 = 1 << 32;
+
+// 将NTP秒数转换为Unix时间戳。最高位为0的值视为第1纪元（2036年之后），
+// 在1968年到2104年之间都能得到正确结果
+fn ntp_secs_to_unix(secs: u32) -> i64 {
+    let secs = if secs < 0x8000_0000 {
+        secs as i64 + NTP_ERA_SECS
+    } else {
+        secs as i64
+    };
+    secs - NTP_UNIX_OFFSET_SECS
+}
+
+/// 本地时钟的可信状态
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockTrustState {
+    Trusted,
+    Untrusted,
+    // 无法连接时间服务器（离线）
+    Unknown,
+}
+
+/// 时钟校验结果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClockTrustStatus {
+    pub state: ClockTrustState,
+    // 本地时钟相对服务器的偏差，正数表示本地时钟偏慢
+    pub drift_seconds: Option<f64>,
+    pub server: String,
+    pub message: String,
+}
+
+// 发送一次SNTP请求，返回服务器时间与本地时间的偏差（秒）
+fn query_sntp_offset(server: &str) -> Result<f64, String> {
+    let addr = server
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| format!("无法解析时间服务器地址: {}", server))?;
+    // 本地地址需要与服务器地址属于同一协议族，否则无法连接仅支持IPv6的服务器
+    let local_addr: SocketAddr = if addr.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local_addr).map_err(|e| e.to_string())?;
+    socket
+        .set_read_timeout(Some(StdDuration::from_secs(3)))
+        .map_err(|e| e.to_string())?;
+    socket.connect(addr).map_err(|e| e.to_string())?;
+
+    // LI=0, VN=3, Mode=3（客户端）
+    let mut request = [0u8; 48];
+    request[0] = 0x1B;
+
+    let sent_at = now();
+    socket.send(&request).map_err(|e| e.to_string())?;
+    let mut response = [0u8; 48];
+    let received = socket.recv(&mut response).map_err(|e| e.to_string())?;
+    let received_at = now();
+
+    if received < 48 {
+        return Err("时间服务器响应不完整".to_string());
+    }
+
+    // 发送时间戳位于第40-47字节：秒和秒的小数部分
+    let secs = u32::from_be_bytes([response[40], response[41], response[42], response[43]]);
+    let fraction = u32::from_be_bytes([response[44], response[45], response[46], response[47]]);
+    if secs == 0 {
+        return Err("时间服务器返回了无效的时间".to_string());
+    }
+    let server_time = ntp_secs_to_unix(secs) as f64 + fraction as f64 / u32::MAX as f64;
+
+    // 以请求往返的中点作为本地时间
+    let local_time = (sent_at.timestamp_millis() + received_at.timestamp_millis()) as f64 / 2000.0;
+
+    Ok(server_time - local_time)
+}
+
+/// 通过SNTP检查本地时钟是否可信，离线时返回 Unknown
+pub fn check_clock_trust(server: Option<&str>, max_drift_secs: Option<f64>) -> ClockTrustStatus {
+    let server = server.unwrap_or(DEFAULT_NTP_SERVER).to_string();
+    let max_drift = max_drift_secs.unwrap_or(DEFAULT_MAX_DRIFT_SECS);
+
+    match query_sntp_offset(&server) {
+        Ok(drift) if drift.abs() <= max_drift => ClockTrustStatus {
+            state: ClockTrustState::Trusted,
+            drift_seconds: Some(drift),
+            server,
+            message: "本地时钟准确".to_string(),
+        },
+        Ok(drift) => ClockTrustStatus {
+            state: ClockTrustState::Untrusted,
+            drift_seconds: Some(drift),
+            server,
+            message: format!("本地时钟偏差 {:.0} 秒，许可证有效期判断可能不准确", drift),
+        },
+        Err(e) => ClockTrustStatus {
+            state: ClockTrustState::Unknown,
+            drift_seconds: None,
+            server,
+            message: format!("无法连接时间服务器: {}", e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntp_seconds_are_converted_across_the_2036_era_rollover() {
+        // 2024-01-01T00:00:00Z
+        assert_eq!(ntp_secs_to_unix(3_913_056_000), 1_704_067_200);
+        // 第0纪元的最后一秒：2036-02-07T06:28:15Z
+        assert_eq!(ntp_secs_to_unix(u32::MAX), 2_085_978_495);
+        // 回绕后的第一秒属于第1纪元
+        assert_eq!(ntp_secs_to_unix(0), 2_085_978_496);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::clock;
+use crate::license::{
+    check_private_key, decode_license_key, get_all_licenses, get_app_dir, is_issued_in_future,
+    public_key_fingerprint, validate_license, LicenseValidationResult,
+};
+use crate::machine_code::get_machine_id;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+// 诊断报告：每项检查独立执行，失败时只记录错误，不影响其他检查
+#[derive(Debug, Serialize, Default)]
+pub struct DiagnosticsReport {
+    pub app_dir: Option<String>,
+    pub public_key_ok: bool,
+    pub public_key_fingerprint: Option<String>,
+    pub private_key_present: bool,
+    pub private_key_ok: bool,
+    pub database_ok: bool,
+    pub license_count: Option<usize>,
+    pub machine_id: Option<String>,
+    pub errors: Vec<String>,
+}
+
+// 运行系统自检，汇总密钥、数据库、机器码和数据目录的状态
+pub fn run_diagnostics(profile: Option<&str>) -> DiagnosticsReport {
+    let mut report = DiagnosticsReport::default();
+
+    match get_app_dir() {
+        Ok(dir) => report.app_dir = Some(dir.display().to_string()),
+        Err(e) => report.errors.push(format!("数据目录: {}", e)),
+    }
+
+    match public_key_fingerprint(profile) {
+        Ok(fingerprint) => {
+            report.public_key_ok = true;
+            report.public_key_fingerprint = Some(fingerprint);
+        }
+        Err(e) => report.errors.push(format!("公钥: {}", e)),
+    }
+
+    match check_private_key(profile) {
+        Ok(present) => {
+            report.private_key_present = present;
+            report.private_key_ok = present;
+        }
+        Err(e) => {
+            report.private_key_present = true;
+            report.errors.push(format!("私钥: {}", e));
+        }
+    }
+
+    match get_all_licenses() {
+        Ok(licenses) => {
+            report.database_ok = true;
+            report.license_count = Some(licenses.len());
+        }
+        Err(e) => report.errors.push(format!("许可证数据库: {}", e)),
+    }
+
+    match get_machine_id() {
+        Ok(id) => report.machine_id = Some(id),
+        Err(e) => report.errors.push(format!("机器码: {}", e)),
+    }
+
+    report
+}
+
+// 单个许可证的诊断结果，供技术支持排查问题
+#[derive(Debug, Serialize, Default)]
+pub struct LicenseDiagnosis {
+    pub decoded: bool,
+    pub license_id: Option<String>,
+    pub issue_date: Option<DateTime<Utc>>,
+    pub expiry_date: Option<DateTime<Utc>>,
+    // 签发时间超前本机当前时间的秒数，负数表示签发于过去
+    pub issue_date_skew_secs: Option<i64>,
+    // 签发时间超出容差地晚于本机时间，通常是签发方时钟错误
+    pub issue_date_in_future: bool,
+    pub validation: Option<LicenseValidationResult>,
+    pub errors: Vec<String>,
+}
+
+// 诊断许可证：解析字段、检查签发时间并执行完整验证
+pub fn diagnose_license(license_key: &str, profile: Option<&str>) -> LicenseDiagnosis {
+    let mut diagnosis = LicenseDiagnosis::default();
+
+    match decode_license_key(license_key) {
+        Ok(info) => {
+            let now = clock::now();
+            diagnosis.decoded = true;
+            diagnosis.issue_date_skew_secs = Some((info.issue_date - now).num_seconds());
+            diagnosis.issue_date_in_future = is_issued_in_future(&info, now);
+            diagnosis.license_id = Some(info.license_id);
+            diagnosis.issue_date = Some(info.issue_date);
+            diagnosis.expiry_date = Some(info.expiry_date);
+        }
+        Err(e) => {
+            diagnosis.errors.push(format!("解析: {}", e));
+            return diagnosis;
+        }
+    }
+
+    match validate_license(license_key, profile) {
+        Ok(result) => diagnosis.validation = Some(result),
+        Err(e) => diagnosis.errors.push(format!("验证: {}", e)),
+    }
+
+    diagnosis
+}
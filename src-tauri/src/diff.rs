@@ -0,0 +1,67 @@
+use crate::license::{decode_license_key, LicenseInfo};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+// 某个字段在两个许可证中的取值，字段不存在时为 null
+#[derive(Debug, Serialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub a: Value,
+    pub b: Value,
+}
+
+// 两个许可证的比较结果，只比较解码后的内容，不验证签名
+#[derive(Debug, Serialize)]
+pub struct LicenseDiff {
+    pub identical: bool,
+    pub a_error: Option<String>,
+    pub b_error: Option<String>,
+    pub differences: Vec<FieldDiff>,
+}
+
+// 解码许可证并转换为JSON对象，便于逐字段比较
+fn decode_fields(license_key: &str) -> Result<Map<String, Value>, String> {
+    let info: LicenseInfo = decode_license_key(license_key).map_err(|e| e.to_string())?;
+    match serde_json::to_value(info).map_err(|e| e.to_string())? {
+        Value::Object(fields) => Ok(fields),
+        _ => Err("许可证内容不是对象".to_string()),
+    }
+}
+
+// 比较两个许可证密钥，返回取值不同的字段；任一密钥无法解码时在对应的错误字段中说明
+pub fn diff_licenses(key_a: &str, key_b: &str) -> LicenseDiff {
+    let (a, b) = (decode_fields(key_a), decode_fields(key_b));
+    let (a, b) = match (a, b) {
+        (Ok(a), Ok(b)) => (a, b),
+        (a, b) => {
+            return LicenseDiff {
+                identical: false,
+                a_error: a.err(),
+                b_error: b.err(),
+                differences: Vec::new(),
+            }
+        }
+    };
+
+    let fields: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    let differences: Vec<FieldDiff> = fields
+        .into_iter()
+        .filter_map(|field| {
+            let value_a = a.get(field).cloned().unwrap_or(Value::Null);
+            let value_b = b.get(field).cloned().unwrap_or(Value::Null);
+            (value_a != value_b).then(|| FieldDiff {
+                field: field.clone(),
+                a: value_a,
+                b: value_b,
+            })
+        })
+        .collect();
+
+    LicenseDiff {
+        identical: differences.is_empty(),
+        a_error: None,
+        b_error: None,
+        differences,
+    }
+}
@@ -0,0 +1,114 @@
+use crate::license::{get_all_licenses, write_file_atomic, LicenseError, LicenseInfo};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::path::Path;
+
+// CSV列，完整导出和按时间范围导出使用相同的列
+const CSV_HEADER: &str =
+    "license_id,customer_name,customer_email,issue_date,expiry_date,features,machine_code,tier,issuer";
+
+// 转义CSV字段：含逗号、引号或换行时加引号，引号写两次；
+// 以 = + - @ 或制表符、回车开头的值前加单引号，避免在电子表格中被当作公式执行
+fn escape_csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// 生成CSV内容，功能列表用分号分隔
+fn licenses_to_csv(licenses: &[LicenseInfo]) -> Result<String, LicenseError> {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+
+    for license in licenses {
+        let tier = serde_json::to_value(license.tier)
+            .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+        let fields = [
+            license.license_id.clone(),
+            license.customer_name.clone(),
+            license.customer_email.clone(),
+            format_date(&license.issue_date),
+            format_date(&license.expiry_date),
+            license.features.join(";"),
+            license.machine_code.clone().unwrap_or_default(),
+            tier.as_str().unwrap_or_default().to_string(),
+            license.issuer.clone(),
+        ];
+
+        let row: Vec<String> = fields.iter().map(|f| escape_csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    Ok(csv)
+}
+
+// 按文件扩展名选择格式：.json 导出JSON数组，其他导出CSV
+fn write_licenses(licenses: &[LicenseInfo], path: &Path) -> Result<usize, LicenseError> {
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    let contents = if is_json {
+        serde_json::to_string_pretty(licenses)
+            .map_err(|e| LicenseError::SerializationError(format!("序列化许可证失败: {}", e)))?
+    } else {
+        licenses_to_csv(licenses)?
+    };
+
+    write_file_atomic(path, contents.as_bytes(), "导出文件")?;
+    Ok(licenses.len())
+}
+
+/// 导出数据库中的所有许可证，返回导出的数量
+pub fn export_licenses(path: &Path) -> Result<usize, LicenseError> {
+    write_licenses(&get_all_licenses()?, path)
+}
+
+/// 导出签发时间在 [start, end) 范围内的许可证（UTC，包含开始时间，不包含结束时间），
+/// 返回导出的数量
+pub fn export_licenses_between(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    path: &Path,
+) -> Result<usize, LicenseError> {
+    if start >= end {
+        return Err(LicenseError::ValidationError(
+            "开始时间必须早于结束时间".to_string(),
+        ));
+    }
+
+    let licenses: Vec<LicenseInfo> = get_all_licenses()?
+        .into_iter()
+        .filter(|license| license.issue_date >= start && license.issue_date < end)
+        .collect();
+
+    write_licenses(&licenses, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_csv_field;
+
+    #[test]
+    fn escapes_formula_prefixes() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\t=1", "\r=1"] {
+            assert!(escape_csv_field(value)
+                .trim_start_matches('"')
+                .starts_with('\''));
+        }
+        assert_eq!(escape_csv_field("plain"), "plain");
+        assert_eq!(escape_csv_field("a,b"), "\"a,b\"");
+    }
+}
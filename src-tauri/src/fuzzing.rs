@@ -0,0 +1,34 @@
+// 模糊测试入口，仅在 cargo fuzz 构建时编译
+use crate::license::{self, check_license_terms, decode_license_key};
+use crate::policy;
+use base64::{engine::general_purpose, Engine as _};
+use std::sync::Once;
+
+static INIT: Once = Once::new();
+
+// 使用临时数据目录并生成密钥，使输入能走到签名验证之后的逻辑
+fn init() {
+    INIT.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("drilling-fuzz-{}", std::process::id()));
+        license::set_data_dir_override(Some(dir));
+        license::ensure_keys(None).expect("生成模糊测试密钥失败");
+    });
+}
+
+/// 将任意字节作为许可证密钥和JSON载荷输入解析与验证逻辑，任何输入都不应导致panic
+pub fn validate_license_input(data: &[u8]) {
+    init();
+
+    // 原始字节作为许可证密钥
+    if let Ok(key) = std::str::from_utf8(data) {
+        let _ = license::validate_license(key, None);
+        let _ = license::inspect_license_crypto(key, None);
+    }
+
+    // 原始字节作为JSON载荷：签名必然无效，另外跳过签名直接检查许可证条款
+    let key = general_purpose::STANDARD.encode(data);
+    let _ = license::validate_license(&key, None);
+    if let (Ok(info), Ok(policy)) = (decode_license_key(&key), policy::get_validation_policy()) {
+        let _ = check_license_terms(info, &policy, true);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::license::{is_signed_by_known_key, LicenseError, LicenseInfo};
+use crate::store;
+use serde::Serialize;
+
+// 无法解析的许可证记录
+#[derive(Debug, Serialize)]
+pub struct UnreadableRecord {
+    // 记录在数据库中的位置（从0开始）
+    pub index: usize,
+    pub license_id: Option<String>,
+    pub error: String,
+}
+
+// 许可证库完整性检查报告
+#[derive(Debug, Serialize, Default)]
+pub struct StoreIntegrityReport {
+    // 所有记录都能解析且签名有效
+    pub intact: bool,
+    pub total: usize,
+    pub valid: usize,
+    // 签名无法由当前或已停用的密钥验证的许可证ID
+    pub invalid_signatures: Vec<String>,
+    pub unreadable: Vec<UnreadableRecord>,
+}
+
+// 逐条检查数据库中的许可证：能否解析，签名能否由当前或已停用的受信任密钥验证
+pub fn verify_store_integrity(profile: Option<&str>) -> Result<StoreIntegrityReport, LicenseError> {
+    let records = store::current_store().load_raw()?;
+    let mut report = StoreIntegrityReport {
+        total: records.len(),
+        ..Default::default()
+    };
+
+    for (index, record) in records.into_iter().enumerate() {
+        let license_id = record
+            .get("license_id")
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string());
+
+        let license: LicenseInfo = match serde_json::from_value(record) {
+            Ok(license) => license,
+            Err(e) => {
+                report.unreadable.push(UnreadableRecord {
+                    index,
+                    license_id,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        if is_signed_by_known_key(&license, profile)? {
+            report.valid += 1;
+        } else {
+            report.invalid_signatures.push(license.license_id);
+        }
+    }
+
+    report.intact = report.invalid_signatures.is_empty() && report.unreadable.is_empty();
+    Ok(report)
+}
@@ -0,0 +1,89 @@
+use crate::license::{
+    check_overwrite_confirmed, generate_private_key, install_key_pair, KeyPairResult, LicenseError,
+};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+// 进度事件的发送间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// 当前密钥生成任务的取消标记
+static CANCEL_FLAG: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+// 密钥生成进度事件，RSA密钥生成无法预估剩余时间，只报告已用时间
+#[derive(Debug, Clone, Serialize)]
+struct KeygenProgressEvent {
+    bits: usize,
+    elapsed_secs: u64,
+}
+
+/// 在阻塞线程池中生成密钥对，生成期间每秒发送 `keygen://progress` 事件。
+/// 取消后生成结果会被丢弃，不会覆盖现有密钥
+pub async fn generate_key_pair_async(
+    app: AppHandle,
+    bits: usize,
+    profile: Option<String>,
+    confirm: bool,
+) -> Result<KeyPairResult, LicenseError> {
+    // 生成耗时较长，先检查是否需要确认覆盖
+    check_overwrite_confirmed(profile.as_deref(), confirm)?;
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    {
+        let mut flag = CANCEL_FLAG
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if flag.is_some() {
+            return Err(LicenseError::ValidationError(
+                "已有密钥生成任务正在进行".to_string(),
+            ));
+        }
+        *flag = Some(cancelled.clone());
+    }
+
+    let mut task = tauri::async_runtime::spawn_blocking(move || generate_private_key(bits));
+    let started = Instant::now();
+    let generated = loop {
+        match tokio::time::timeout(PROGRESS_INTERVAL, &mut task).await {
+            Ok(result) => break result,
+            Err(_) => {
+                let _ = app.emit(
+                    "keygen://progress",
+                    KeygenProgressEvent {
+                        bits,
+                        elapsed_secs: started.elapsed().as_secs(),
+                    },
+                );
+            }
+        }
+    };
+
+    CANCEL_FLAG
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+
+    let private_key = generated.map_err(|e| LicenseError::ValidationError(e.to_string()))??;
+    if cancelled.load(Ordering::SeqCst) {
+        return Err(LicenseError::ValidationError("密钥生成已取消".to_string()));
+    }
+
+    install_key_pair(&private_key, profile.as_deref(), confirm)
+}
+
+/// 取消正在进行的密钥生成，返回是否有任务在运行
+pub fn cancel_key_generation() -> bool {
+    let flag = CANCEL_FLAG
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match flag.as_ref() {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
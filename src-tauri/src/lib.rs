@@ -1,14 +1,68 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+mod audit;
+mod backup;
+#[cfg(feature = "benchmark")]
+mod benchmark;
+mod bundle;
+mod cbor;
+mod clock;
+mod diagnostics;
+mod diff;
+mod export;
+#[cfg(fuzzing)]
+pub mod fuzzing;
+mod integrity;
+mod keygen;
 mod license;
 mod machine_code;
+mod machine_token;
+mod merge;
+mod notification;
+mod offline;
+mod policy;
+mod qr;
+mod remote_key;
+mod selftest;
+mod settings;
+mod shortcode;
+#[cfg(all(debug_assertions, feature = "simulate-validation"))]
+mod simulate;
+mod store;
+mod template;
+#[cfg(test)]
+mod test_support;
+mod time_anchor;
+mod timezone;
+mod updater;
+mod watch;
+mod webhook;
 
+use chrono::{DateTime, NaiveDate, Utc};
+use clock::ClockTrustStatus;
+use diagnostics::{DiagnosticsReport, LicenseDiagnosis};
+use diff::LicenseDiff;
+use integrity::StoreIntegrityReport;
 use license::{
-    export_public_key, generate_license, generate_license_with_machine_code, generate_new_key_pair,
-    get_all_licenses, validate_license, validate_license_with_machine_code, LicenseInfo,
-    LicenseValidationResult,
+    export_public_key, generate_license, generate_license_with_machine_code, get_all_licenses,
+    validate_license, validate_license_with_machine_code, BestLicenseResult, BulkLicenseIssuance,
+    DeleteOutcome, EffectiveFeatures, ErrorPayload, KeyPairResult, LicenseCryptoInfo, LicenseError,
+    LicenseFileResult, LicenseInfo, LicenseIssuance, LicenseOptions, LicenseStats,
+    LicenseValidationResult, MachineBoundLicense, MachineVerificationResult, PublicKeyFormat,
+    PublicKeyRepair, SigningKeyInfo,
 };
-use machine_code::get_machine_id;
-use tauri_plugin_updater::UpdaterExt;
+use machine_code::{get_machine_id, MachineCodeInput, MachineIdError, MachineIdentifiers};
+use merge::MergeReport;
+use notification::ExpiryNotificationSchedule;
+use offline::OfflineVerificationBundle;
+use policy::ValidationPolicy;
+use qr::{LicenseSizeInfo, QrErrorCorrection};
+use remote_key::PinnedPublicKey;
+use settings::{FeatureCatalog, LicenseLimits};
+use shortcode::{IssuedLicense, ShortCodeRedemption};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use template::LicenseTemplate;
+use updater::{RetryConfig, UpdateDownloadResult, UpdateInstallResult, UpdaterConfigStatus};
 
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -21,119 +75,1119 @@ fn generate_license_key(
     customer_email: &str,
     expiry_days: u32,
     features: Vec<String>,
-) -> Result<String, String> {
-    generate_license(customer_name, customer_email, expiry_days, features)
-        .map_err(|e| e.to_string())
+    options: Option<LicenseOptions>,
+    profile: Option<String>,
+) -> Result<LicenseIssuance, ErrorPayload> {
+    // 返回带错误码的错误，前端可据此区分权限不足等情况
+    Ok(generate_license(
+        customer_name,
+        customer_email,
+        expiry_days,
+        features,
+        &options.unwrap_or_default(),
+        profile.as_deref(),
+    )?)
 }
 
 #[tauri::command]
-fn generate_license_key_with_machine_code(
+async fn generate_license_key_async(
+    customer_name: String,
+    customer_email: String,
+    expiry_days: u32,
+    features: Vec<String>,
+    options: Option<LicenseOptions>,
+    profile: Option<String>,
+) -> Result<LicenseIssuance, ErrorPayload> {
+    // 签名和写入数据库放到阻塞线程池中执行，避免大密钥签名时界面卡顿
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        generate_license(
+            &customer_name,
+            &customer_email,
+            expiry_days,
+            features,
+            &options.unwrap_or_default(),
+            profile.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| LicenseError::Internal(format!("签发任务异常中止: {}", e)))?;
+    Ok(result?)
+}
+
+#[tauri::command]
+fn generate_license_key_until(
+    customer_name: &str,
+    customer_email: &str,
+    expiry_date: NaiveDate,
+    timezone: Option<String>,
+    features: Vec<String>,
+    options: Option<LicenseOptions>,
+    profile: Option<String>,
+) -> Result<LicenseIssuance, ErrorPayload> {
+    Ok(license::generate_license_until(
+        customer_name,
+        customer_email,
+        expiry_date,
+        timezone.as_deref(),
+        features,
+        &options.unwrap_or_default(),
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn generate_license_key_with_short_code(
     customer_name: &str,
     customer_email: &str,
     expiry_days: u32,
     features: Vec<String>,
-    machine_code: &str,
-) -> Result<String, String> {
-    generate_license_with_machine_code(
+    options: Option<LicenseOptions>,
+    profile: Option<String>,
+) -> Result<IssuedLicense, ErrorPayload> {
+    Ok(shortcode::generate_license_with_short_code(
         customer_name,
         customer_email,
         expiry_days,
         features,
+        &options.unwrap_or_default(),
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn create_short_code(license_id: &str) -> Result<String, ErrorPayload> {
+    Ok(shortcode::create_short_code(license_id)?)
+}
+
+#[tauri::command]
+fn redeem_short_code(
+    code: &str,
+    machine_code: &str,
+    profile: Option<String>,
+) -> Result<ShortCodeRedemption, ErrorPayload> {
+    Ok(shortcode::redeem_short_code(
+        code,
         machine_code,
-    )
-    .map_err(|e| e.to_string())
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn generate_license_key_with_machine_code(
+    customer_name: &str,
+    customer_email: &str,
+    expiry_days: u32,
+    features: Vec<String>,
+    machine_code: MachineCodeInput,
+    options: Option<LicenseOptions>,
+    profile: Option<String>,
+) -> Result<LicenseIssuance, ErrorPayload> {
+    Ok(generate_license_with_machine_code(
+        customer_name,
+        customer_email,
+        expiry_days,
+        features,
+        &options.unwrap_or_default(),
+        &machine_code,
+        profile.as_deref(),
+    )?)
 }
 
 #[tauri::command]
-fn validate_license_key(license_key: &str) -> Result<LicenseValidationResult, String> {
-    validate_license(license_key).map_err(|e| e.to_string())
+fn sign_license(info: LicenseInfo, profile: Option<String>) -> Result<String, ErrorPayload> {
+    Ok(license::sign_license(info, profile.as_deref())?)
+}
+
+#[tauri::command]
+fn validate_license_key(
+    license_key: &str,
+    profile: Option<String>,
+) -> Result<LicenseValidationResult, ErrorPayload> {
+    Ok(validate_license(license_key, profile.as_deref())?)
 }
 
 #[tauri::command]
 fn validate_license_key_with_machine_code(
     license_key: &str,
     machine_code: &str,
-) -> Result<LicenseValidationResult, String> {
-    validate_license_with_machine_code(license_key, machine_code).map_err(|e| e.to_string())
+    profile: Option<String>,
+) -> Result<LicenseValidationResult, ErrorPayload> {
+    Ok(validate_license_with_machine_code(
+        license_key,
+        machine_code,
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn set_validation_policy(policy: ValidationPolicy) -> Result<(), ErrorPayload> {
+    Ok(policy::set_validation_policy(policy)?)
+}
+
+#[tauri::command]
+fn get_validation_policy() -> Result<ValidationPolicy, ErrorPayload> {
+    Ok(policy::get_validation_policy()?)
+}
+
+#[tauri::command]
+fn clear_validation_cache() {
+    license::clear_validation_cache();
+}
+
+#[tauri::command]
+fn record_heartbeat(license_id: &str) -> Result<String, ErrorPayload> {
+    Ok(license::record_heartbeat(license_id).map(|time| time.to_rfc3339())?)
+}
+
+#[tauri::command]
+fn get_license_stats() -> Result<LicenseStats, ErrorPayload> {
+    Ok(license::get_license_stats()?)
+}
+
+#[tauri::command]
+fn sign_data(data: &str, profile: Option<String>) -> Result<String, ErrorPayload> {
+    Ok(license::sign_data(data, profile.as_deref())?)
+}
+
+#[tauri::command]
+fn verify_data(data: &str, signature: &str, profile: Option<String>) -> Result<bool, ErrorPayload> {
+    Ok(license::verify_data(data, signature, profile.as_deref())?)
+}
+
+#[tauri::command]
+fn get_expiring_licenses(days: u32) -> Result<Vec<LicenseInfo>, ErrorPayload> {
+    Ok(license::get_expiring_licenses(days)?)
+}
+
+#[tauri::command]
+fn feature_usage_report() -> Result<BTreeMap<String, usize>, ErrorPayload> {
+    Ok(license::feature_usage_report()?)
+}
+
+#[tauri::command]
+fn set_data_dir(path: Option<String>) {
+    license::set_data_dir_override(path.map(PathBuf::from));
+}
+
+#[tauri::command]
+fn validate_license_file(
+    path: &str,
+    profile: Option<String>,
+) -> Result<LicenseValidationResult, ErrorPayload> {
+    Ok(license::validate_license_file(
+        Path::new(path),
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn license_size_info(
+    license_key: &str,
+    error_correction: Option<QrErrorCorrection>,
+) -> LicenseSizeInfo {
+    qr::license_size_info(license_key, error_correction.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn validate_best_license(
+    keys: Vec<String>,
+    machine_code: Option<String>,
+    profile: Option<String>,
+) -> Result<BestLicenseResult, ErrorPayload> {
+    // 逐个验证签名，密钥较多时耗时较长，放到阻塞线程池中执行
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        license::validate_best_license(&keys, machine_code.as_deref(), profile.as_deref())
+    })
+    .await
+    .map_err(|e| LicenseError::Internal(format!("验证任务异常中止: {}", e)))?;
+    Ok(result?)
+}
+
+#[tauri::command]
+async fn effective_features(
+    keys: Vec<String>,
+    machine_code: Option<String>,
+    profile: Option<String>,
+) -> Result<EffectiveFeatures, ErrorPayload> {
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        license::effective_features(&keys, machine_code.as_deref(), profile.as_deref())
+    })
+    .await
+    .map_err(|e| LicenseError::Internal(format!("验证任务异常中止: {}", e)))?;
+    Ok(result?)
+}
+
+#[tauri::command]
+async fn validate_license_dir(
+    path: String,
+    profile: Option<String>,
+) -> Result<Vec<LicenseFileResult>, ErrorPayload> {
+    // 批量验证耗时较长，放到阻塞线程池中执行
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        license::validate_license_dir(Path::new(&path), profile.as_deref())
+    })
+    .await
+    .map_err(|e| LicenseError::Internal(format!("验证任务异常中止: {}", e)))?;
+    Ok(result?)
+}
+
+#[tauri::command]
+fn save_license_to_file(license_key: &str, path: &str) -> Result<(), ErrorPayload> {
+    Ok(license::save_license_to_file(license_key, Path::new(path))?)
+}
+
+#[tauri::command]
+fn backup_database(path: &str, passphrase: &str) -> Result<usize, ErrorPayload> {
+    Ok(backup::backup_database(Path::new(path), passphrase)?)
+}
+
+#[tauri::command]
+fn restore_database(
+    path: &str,
+    passphrase: &str,
+    confirm: Option<bool>,
+    profile: Option<String>,
+) -> Result<usize, ErrorPayload> {
+    Ok(backup::restore_database(
+        Path::new(path),
+        passphrase,
+        confirm.unwrap_or(false),
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn export_license_bundle(
+    license_id: &str,
+    path: &str,
+    profile: Option<String>,
+) -> Result<(), ErrorPayload> {
+    Ok(bundle::export_license_bundle(
+        license_id,
+        Path::new(path),
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn generate_offline_verification_bundle(
+    path: Option<String>,
+    profile: Option<String>,
+) -> Result<OfflineVerificationBundle, ErrorPayload> {
+    Ok(match path {
+        Some(path) => {
+            offline::write_offline_verification_bundle(Path::new(&path), profile.as_deref())
+        }
+        None => offline::generate_offline_verification_bundle(profile.as_deref()),
+    }?)
+}
+
+#[tauri::command]
+fn export_licenses(path: &str) -> Result<usize, ErrorPayload> {
+    Ok(export::export_licenses(Path::new(path))?)
+}
+
+#[tauri::command]
+fn export_licenses_between(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    path: &str,
+) -> Result<usize, ErrorPayload> {
+    Ok(export::export_licenses_between(
+        start,
+        end,
+        Path::new(path),
+    )?)
+}
+
+#[tauri::command]
+fn merge_database(other_path: &str, profile: Option<String>) -> Result<MergeReport, ErrorPayload> {
+    Ok(merge::merge_database(
+        Path::new(other_path),
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn import_license_bundle(
+    path: &str,
+    expected_fingerprint: Option<String>,
+    profile: Option<String>,
+) -> Result<LicenseValidationResult, ErrorPayload> {
+    Ok(bundle::import_license_bundle(
+        Path::new(path),
+        expected_fingerprint.as_deref(),
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn redeem_license(
+    license_key: &str,
+    machine_code: &str,
+    profile: Option<String>,
+) -> Result<LicenseValidationResult, ErrorPayload> {
+    Ok(license::redeem_license(
+        license_key,
+        machine_code,
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn revoke_license(license_id: &str, reason: Option<String>) -> Result<bool, ErrorPayload> {
+    Ok(license::revoke_license(
+        license_id,
+        reason.as_deref().unwrap_or_default(),
+    )?)
+}
+
+#[tauri::command]
+fn verify_current_machine(
+    license_key: &str,
+    profile: Option<String>,
+) -> Result<MachineVerificationResult, ErrorPayload> {
+    Ok(license::verify_current_machine(
+        license_key,
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn diff_licenses(key_a: &str, key_b: &str) -> LicenseDiff {
+    diff::diff_licenses(key_a, key_b)
+}
+
+#[tauri::command]
+fn inspect_license_crypto(
+    license_key: &str,
+    min_rsa_bits: Option<usize>,
+) -> Result<LicenseCryptoInfo, ErrorPayload> {
+    Ok(license::inspect_license_crypto(license_key, min_rsa_bits)?)
+}
+
+#[tauri::command]
+fn canonicalize_license(
+    license_key: &str,
+    profile: Option<String>,
+) -> Result<String, ErrorPayload> {
+    Ok(license::canonicalize_license(
+        license_key,
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn add_signature(license_key: &str, profile: Option<String>) -> Result<String, ErrorPayload> {
+    Ok(license::add_signature(license_key, profile.as_deref())?)
+}
+
+#[tauri::command]
+fn install_cosigner_key(public_key: &str, profile: Option<String>) -> Result<String, ErrorPayload> {
+    Ok(license::install_cosigner_key(
+        public_key,
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn validate_license_for_feature(
+    license_key: &str,
+    feature: &str,
+    profile: Option<String>,
+) -> Result<LicenseValidationResult, ErrorPayload> {
+    Ok(license::validate_license_for_feature(
+        license_key,
+        feature,
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+fn set_disabled_features(features: Vec<String>) {
+    license::set_disabled_features(features)
+}
+
+#[tauri::command]
+fn get_disabled_features() -> Vec<String> {
+    license::get_disabled_features()
+}
+
+#[tauri::command]
+fn get_license_by_id(license_id: &str) -> Result<Option<LicenseInfo>, ErrorPayload> {
+    Ok(license::get_license_by_id(license_id)?)
+}
+
+#[tauri::command]
+fn get_licenses_for_machine(machine_code: &str) -> Result<Vec<MachineBoundLicense>, ErrorPayload> {
+    Ok(license::get_licenses_for_machine(machine_code)?)
+}
+
+#[tauri::command]
+fn get_licenses_redacted(redact_name: Option<bool>) -> Result<Vec<LicenseInfo>, ErrorPayload> {
+    Ok(license::get_licenses_redacted(
+        redact_name.unwrap_or(false),
+    )?)
+}
+
+#[tauri::command]
+fn get_licenses() -> Result<Vec<LicenseInfo>, ErrorPayload> {
+    Ok(get_all_licenses()?)
+}
+
+#[tauri::command]
+fn export_license_public_key(profile: Option<String>) -> Result<String, ErrorPayload> {
+    Ok(export_public_key(profile.as_deref())?)
+}
+
+#[tauri::command]
+fn export_public_key_as(
+    format: PublicKeyFormat,
+    profile: Option<String>,
+) -> Result<String, ErrorPayload> {
+    Ok(license::export_public_key_as(format, profile.as_deref())?)
+}
+
+#[tauri::command]
+fn ensure_keys(profile: Option<String>) -> Result<String, ErrorPayload> {
+    Ok(license::ensure_keys(profile.as_deref())?)
+}
+
+#[tauri::command]
+fn has_signing_capability(profile: Option<String>) -> bool {
+    license::has_signing_capability(profile.as_deref())
+}
+
+#[tauri::command]
+async fn generate_rsa_key_pair(
+    app: tauri::AppHandle,
+    bits: usize,
+    profile: Option<String>,
+    confirm: Option<bool>,
+) -> Result<KeyPairResult, ErrorPayload> {
+    // 大尺寸密钥生成耗时数秒，放到后台执行以免界面卡顿
+    Ok(keygen::generate_key_pair_async(app, bits, profile, confirm.unwrap_or(false)).await?)
+}
+
+#[tauri::command]
+async fn fetch_and_pin_public_key(
+    url: String,
+    profile: Option<String>,
+) -> Result<PinnedPublicKey, ErrorPayload> {
+    Ok(remote_key::fetch_and_pin_public_key(&url, profile.as_deref()).await?)
+}
+
+#[tauri::command]
+fn repair_public_key(profile: Option<String>) -> Result<PublicKeyRepair, ErrorPayload> {
+    Ok(license::repair_public_key(profile.as_deref())?)
+}
+
+#[tauri::command]
+async fn export_private_key(
+    passphrase: String,
+    profile: Option<String>,
+    confirm: Option<bool>,
+) -> Result<String, ErrorPayload> {
+    // 口令派生（scrypt）耗时较长，放到阻塞线程池中执行
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        license::export_private_key(&passphrase, profile.as_deref(), confirm.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| LicenseError::Internal(format!("导出任务异常中止: {}", e)))?;
+    Ok(result?)
+}
+
+#[tauri::command]
+async fn import_private_key(
+    encrypted_pem: String,
+    passphrase: String,
+    profile: Option<String>,
+    confirm: Option<bool>,
+) -> Result<KeyPairResult, ErrorPayload> {
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        license::import_private_key(
+            &encrypted_pem,
+            &passphrase,
+            profile.as_deref(),
+            confirm.unwrap_or(false),
+        )
+    })
+    .await
+    .map_err(|e| LicenseError::Internal(format!("导入任务异常中止: {}", e)))?;
+    Ok(result?)
+}
+
+#[tauri::command]
+fn restore_key_pair(
+    backup_path: &str,
+    profile: Option<String>,
+    confirm: Option<bool>,
+) -> Result<KeyPairResult, ErrorPayload> {
+    Ok(license::restore_key_pair(
+        Path::new(backup_path),
+        profile.as_deref(),
+        confirm.unwrap_or(false),
+    )?)
+}
+
+#[tauri::command]
+fn cancel_key_generation() -> bool {
+    keygen::cancel_key_generation()
+}
+
+#[tauri::command]
+fn list_signing_keys(profile: Option<String>) -> Result<Vec<SigningKeyInfo>, ErrorPayload> {
+    Ok(license::list_signing_keys(profile.as_deref())?)
+}
+
+#[tauri::command]
+fn list_profiles() -> Result<Vec<String>, ErrorPayload> {
+    Ok(license::list_profiles()?)
+}
+
+#[tauri::command]
+fn create_profile(name: &str, bits: Option<usize>) -> Result<String, ErrorPayload> {
+    Ok(license::create_profile(name, bits.unwrap_or(2048))?)
 }
 
 #[tauri::command]
-fn get_licenses() -> Result<Vec<LicenseInfo>, String> {
-    get_all_licenses().map_err(|e| e.to_string())
+fn get_current_machine_id() -> Result<String, ErrorPayload> {
+    Ok(get_machine_id()?)
 }
 
 #[tauri::command]
-fn export_license_public_key() -> String {
-    export_public_key()
+fn get_machine_id_formatted() -> Result<String, ErrorPayload> {
+    Ok(machine_code::get_machine_id_formatted()?)
 }
 
 #[tauri::command]
-fn generate_rsa_key_pair(bits: usize) -> Result<(String, String), String> {
-    generate_new_key_pair(bits).map_err(|e| e.to_string())
+fn get_machine_identifiers() -> MachineIdentifiers {
+    machine_code::get_machine_identifiers()
 }
 
 #[tauri::command]
-fn get_current_machine_id() -> Result<String, String> {
-    get_machine_id().map_err(|e| e.to_string())
+async fn generate_machine_token() -> Result<String, ErrorPayload> {
+    // 首次生成时需要创建安装密钥，放到阻塞线程池中执行
+    let result = tauri::async_runtime::spawn_blocking(machine_token::generate_machine_token)
+        .await
+        .map_err(|e| LicenseError::Internal(format!("生成任务异常中止: {}", e)))?;
+    Ok(result?)
 }
 
 #[tauri::command]
-fn delete_license_by_id(license_id: &str) -> Result<(), String> {
-    license::delete_license(license_id).map_err(|e| e.to_string())
+fn verify_machine_token(token: &str, max_age_days: Option<u32>) -> Result<String, ErrorPayload> {
+    Ok(machine_token::verify_machine_token(
+        token,
+        max_age_days.unwrap_or(machine_token::DEFAULT_TOKEN_MAX_AGE_DAYS),
+    )?)
 }
 
 #[tauri::command]
-async fn check_update(app: tauri::AppHandle) -> Result<String, String> {
-    let updater = app.updater().map_err(|e| e.to_string())?;
+async fn refresh_machine_id() -> Result<String, ErrorPayload> {
+    // 重新收集系统信息较慢，放到阻塞线程池中执行
+    let result = tauri::async_runtime::spawn_blocking(machine_code::refresh_machine_id)
+        .await
+        .map_err(|e| LicenseError::Internal(format!("刷新任务异常中止: {}", e)))?;
+    Ok(result?)
+}
 
-    let update_response = updater.check().await.map_err(|e| e.to_string())?;
+#[tauri::command]
+fn reset_machine_salt() -> Result<String, ErrorPayload> {
+    Ok(machine_code::reset_machine_salt()?)
+}
+
+#[tauri::command]
+fn get_machine_code_input() -> Result<MachineCodeInput, ErrorPayload> {
+    Ok(machine_code::get_machine_code_input()?)
+}
 
-    if update_response.is_some() {
-        Ok("有更新可用".to_string())
-    } else {
-        Ok("当前已是最新版本".to_string())
+#[tauri::command]
+fn get_machine_id_versioned(version: u32) -> Result<String, ErrorPayload> {
+    Ok(machine_code::get_machine_id_versioned(version)?)
+}
+
+#[tauri::command]
+fn delete_license_by_id(license_id: &str) -> Result<(), ErrorPayload> {
+    // 许可证不存在时返回 not_found 错误码，前端可提示“已删除”而非报错
+    match license::delete_license(license_id)? {
+        DeleteOutcome::Deleted => Ok(()),
+        DeleteOutcome::NotFound => Err(LicenseError::NotFound(license_id.to_string()).into()),
     }
 }
 
 #[tauri::command]
-async fn install_update(app: tauri::AppHandle) -> Result<(), String> {
-    let updater = app.updater().map_err(|e| e.to_string())?;
+fn save_template(template: LicenseTemplate) -> Result<(), ErrorPayload> {
+    Ok(template::save_template(template)?)
+}
+
+#[tauri::command]
+fn get_templates() -> Result<Vec<LicenseTemplate>, ErrorPayload> {
+    Ok(template::get_templates()?)
+}
+
+#[tauri::command]
+fn delete_template(name: &str) -> Result<(), ErrorPayload> {
+    Ok(template::delete_template(name)?)
+}
+
+#[tauri::command]
+fn generate_license_from_template(
+    template_name: &str,
+    customer_name: &str,
+    customer_email: &str,
+    profile: Option<String>,
+) -> Result<LicenseIssuance, ErrorPayload> {
+    Ok(template::generate_license_from_template(
+        template_name,
+        customer_name,
+        customer_email,
+        profile.as_deref(),
+    )?)
+}
+
+#[tauri::command]
+async fn generate_licenses_for_machines(
+    template_name: String,
+    customer_name: String,
+    customer_email: String,
+    machine_codes: Vec<MachineCodeInput>,
+    profile: Option<String>,
+) -> Result<BulkLicenseIssuance, ErrorPayload> {
+    // 批量签名较慢，放到阻塞线程池中执行
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        template::generate_licenses_for_machines(
+            &template_name,
+            &customer_name,
+            &customer_email,
+            &machine_codes,
+            profile.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| LicenseError::Internal(format!("签发任务异常中止: {}", e)))?;
+    Ok(result?)
+}
+
+#[tauri::command]
+fn set_license_limits(limits: LicenseLimits) -> Result<(), ErrorPayload> {
+    Ok(settings::set_license_limits(limits)?)
+}
+
+#[tauri::command]
+fn get_license_limits() -> Result<LicenseLimits, ErrorPayload> {
+    Ok(settings::get_license_limits()?)
+}
+
+#[tauri::command]
+fn set_feature_catalog(catalog: FeatureCatalog) -> Result<(), ErrorPayload> {
+    Ok(settings::set_feature_catalog(catalog)?)
+}
+
+#[tauri::command]
+fn get_feature_catalog() -> Result<FeatureCatalog, ErrorPayload> {
+    Ok(settings::get_feature_catalog()?)
+}
+
+#[tauri::command]
+fn check_feature_catalog(features: Vec<String>) -> Result<Vec<String>, ErrorPayload> {
+    Ok(license::unknown_catalog_features(&features)?)
+}
+
+#[tauri::command]
+fn set_default_issuer(issuer: Option<String>) -> Result<(), ErrorPayload> {
+    Ok(settings::set_default_issuer(issuer.as_deref())?)
+}
+
+#[tauri::command]
+fn set_webhook_url(url: &str) -> Result<(), ErrorPayload> {
+    Ok(settings::set_webhook_url(url)?)
+}
+
+#[tauri::command]
+fn clear_webhook_url() -> Result<(), ErrorPayload> {
+    Ok(settings::clear_webhook_url()?)
+}
+
+#[tauri::command]
+fn set_feature_registry(registry: BTreeMap<String, u8>) -> Result<(), ErrorPayload> {
+    Ok(settings::set_feature_registry(registry)?)
+}
+
+#[tauri::command]
+fn get_feature_registry() -> Result<BTreeMap<String, u8>, ErrorPayload> {
+    Ok(settings::get_feature_registry()?)
+}
+
+#[cfg(all(debug_assertions, feature = "simulate-validation"))]
+#[tauri::command]
+fn simulate_validation_state(state: simulate::SimulatedValidationState) -> LicenseValidationResult {
+    simulate::simulate_validation_state(state)
+}
+
+#[cfg(feature = "benchmark")]
+#[tauri::command]
+async fn benchmark_verification(
+    iterations: Option<u32>,
+    profile: Option<String>,
+) -> Result<benchmark::BenchmarkResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        benchmark::benchmark_verification(iterations.unwrap_or(1000), profile.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn create_time_anchor(profile: Option<String>) -> Result<String, ErrorPayload> {
+    Ok(time_anchor::create_time_anchor(profile.as_deref())?)
+}
+
+#[tauri::command]
+fn install_time_anchor(
+    token: &str,
+    profile: Option<String>,
+) -> Result<DateTime<Utc>, ErrorPayload> {
+    Ok(time_anchor::install_time_anchor(token, profile.as_deref())?)
+}
+
+#[tauri::command]
+fn get_time_anchor() -> Option<DateTime<Utc>> {
+    time_anchor::get_time_anchor()
+}
+
+#[tauri::command]
+async fn check_clock_trust(
+    server: Option<String>,
+    max_drift_secs: Option<f64>,
+) -> Result<ClockTrustStatus, String> {
+    // SNTP查询会阻塞等待网络，放到阻塞线程池中执行
+    tauri::async_runtime::spawn_blocking(move || {
+        clock::check_clock_trust(server.as_deref(), max_drift_secs)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn start_license_watch(
+    app: tauri::AppHandle,
+    license_key: String,
+    interval_secs: u64,
+    profile: Option<String>,
+) {
+    watch::start_license_watch(app, license_key, interval_secs, profile)
+}
+
+#[tauri::command]
+fn stop_license_watch() -> bool {
+    watch::stop_license_watch()
+}
 
-    // 检查是否有可用更新
-    let update = updater.check().await.map_err(|e| e.to_string())?;
+#[tauri::command]
+fn schedule_expiry_notification(
+    app: tauri::AppHandle,
+    license_key: &str,
+    warn_days: Option<u32>,
+) -> Result<ExpiryNotificationSchedule, ErrorPayload> {
+    Ok(notification::schedule_expiry_notification(
+        app,
+        license_key,
+        warn_days.unwrap_or(notification::DEFAULT_WARN_DAYS),
+    )?)
+}
 
-    if let Some(update) = update {
-        // 下载并安装更新
-        // 第一个参数是进度回调函数，第二个参数是退出前的回调函数
-        update
-            .download_and_install(|_, _| {}, || {})
-            .await
-            .map_err(|e| e.to_string())?;
-        Ok(())
-    } else {
-        Err("没有可用的更新".to_string())
+#[tauri::command]
+fn cancel_expiry_notification(license_id: &str) -> bool {
+    notification::cancel_expiry_notification(license_id)
+}
+
+#[tauri::command]
+fn run_diagnostics(profile: Option<String>) -> DiagnosticsReport {
+    diagnostics::run_diagnostics(profile.as_deref())
+}
+
+#[tauri::command]
+fn verify_store_integrity(profile: Option<String>) -> Result<StoreIntegrityReport, ErrorPayload> {
+    Ok(integrity::verify_store_integrity(profile.as_deref())?)
+}
+
+#[tauri::command]
+fn diagnose_license(license_key: &str, profile: Option<String>) -> LicenseDiagnosis {
+    diagnostics::diagnose_license(license_key, profile.as_deref())
+}
+
+#[tauri::command]
+fn set_trusted_key_fingerprints(fingerprints: Vec<String>) -> Result<(), ErrorPayload> {
+    Ok(settings::set_trusted_key_fingerprints(fingerprints)?)
+}
+
+#[tauri::command]
+fn get_trusted_key_fingerprints() -> Result<Vec<String>, ErrorPayload> {
+    Ok(settings::get_trusted_key_fingerprints()?)
+}
+
+#[tauri::command]
+fn set_update_endpoint(
+    url: Option<String>,
+    allow_insecure: Option<bool>,
+) -> Result<(), ErrorPayload> {
+    Ok(settings::set_update_endpoint(
+        url.as_deref(),
+        allow_insecure.unwrap_or(false),
+    )?)
+}
+
+#[tauri::command]
+fn get_update_endpoint(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    updater::get_update_endpoint(&app)
+}
+
+#[tauri::command]
+fn get_updater_config_status(app: tauri::AppHandle) -> Result<UpdaterConfigStatus, String> {
+    updater::get_updater_config_status(&app)
+}
+
+#[tauri::command]
+async fn check_update(app: tauri::AppHandle, retry: Option<RetryConfig>) -> Result<String, String> {
+    updater::check_update(&app, retry.unwrap_or_default()).await
+}
+
+#[tauri::command]
+async fn download_update_only(
+    app: tauri::AppHandle,
+    retry: Option<RetryConfig>,
+) -> Result<UpdateDownloadResult, String> {
+    updater::download_update_only(&app, retry.unwrap_or_default()).await
+}
+
+#[tauri::command]
+async fn install_update(
+    app: tauri::AppHandle,
+    retry: Option<RetryConfig>,
+) -> Result<UpdateInstallResult, String> {
+    updater::install_update(&app, retry.unwrap_or_default()).await
+}
+
+// 命令行参数：以JSON输出本机机器码及其算法版本（如 {"code": "...", "version": 3}）后退出，
+// 不启动界面（用于通过SSH等方式在无界面的服务器上采集机器码）。签发时须将版本号连同机器码一起交给签发方。
+// 当前版本的算法需要在数据目录中保存盐值，没有HOME/APPDATA的环境可通过DRILLING_DATA_DIR指定目录；
+// 无法读写盐值时退回不需要盐值的版本1
+const PRINT_MACHINE_ID_ARG: &str = "--print-machine-id";
+
+// 命令行参数：与 --print-machine-id 一起使用，指定机器码算法版本，如 --machine-id-version 1
+const MACHINE_ID_VERSION_ARG: &str = "--machine-id-version";
+
+// 输出本机机器码，返回进程退出码
+fn print_machine_id(args: &[String]) -> i32 {
+    let requested_version = match args.iter().position(|arg| arg == MACHINE_ID_VERSION_ARG) {
+        Some(index) => match args.get(index + 1).and_then(|v| v.parse::<u32>().ok()) {
+            Some(version) => Some(version),
+            None => {
+                eprintln!("{} 需要指定算法版本号", MACHINE_ID_VERSION_ARG);
+                return 2;
+            }
+        },
+        None => None,
+    };
+
+    let version = requested_version.unwrap_or(machine_code::CURRENT_MACHINE_CODE_VERSION);
+    let result = match machine_code::get_machine_id_versioned(version) {
+        // 未指定版本且无法读写盐值时退回版本1，并提示如何得到当前版本的机器码
+        Err(MachineIdError::SaltError(e)) if requested_version.is_none() => {
+            eprintln!("{}", e);
+            eprintln!(
+                "无法读写机器码盐值，改为输出不需要盐值的版本1机器码。\
+                 如需版本{}的机器码，请设置DRILLING_DATA_DIR指定可写的数据目录后重试",
+                version
+            );
+            machine_code::get_machine_id_versioned(1).map(|code| (code, 1))
+        }
+        result => result.map(|code| (code, version)),
+    };
+
+    let (code, version) = match result {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    match serde_json::to_string(&MachineCodeInput { code, version }) {
+        Ok(json) => {
+            println!("{}", json);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
     }
 }
 
+// 运行自检并以JSON输出报告，供安装脚本和CI使用。
+// 自检会临时切换进程级的数据目录，只在独立的命令行进程中运行，不提供给界面调用
+const SELF_TEST_ARG: &str = "--self-test";
+
+// 处理不需要界面的命令行参数，返回进程退出码；没有此类参数时返回 None
+fn run_cli() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == SELF_TEST_ARG) {
+        let report = selftest::self_test();
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("{}", e),
+        }
+        return Some(if report.passed { 0 } else { 1 });
+    }
+
+    if !args.iter().any(|arg| arg == PRINT_MACHINE_ID_ARG) {
+        return None;
+    }
+
+    Some(print_machine_id(&args))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // 命令行模式在启动Tauri事件循环之前处理
+    if let Some(code) = run_cli() {
+        std::process::exit(code);
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_notification::init())
+        .setup(|_app| {
+            // 启动时在后台预先计算机器码，之后的调用直接读取缓存
+            std::thread::spawn(|| {
+                let _ = get_machine_id();
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             generate_license_key,
+            generate_license_key_async,
+            sign_license,
             validate_license_key,
+            validate_license_for_feature,
+            set_disabled_features,
+            get_disabled_features,
             get_licenses,
+            get_licenses_redacted,
+            get_license_by_id,
+            get_licenses_for_machine,
+            inspect_license_crypto,
+            canonicalize_license,
+            add_signature,
+            install_cosigner_key,
+            diff_licenses,
+            get_license_stats,
+            get_expiring_licenses,
+            feature_usage_report,
+            set_data_dir,
+            sign_data,
+            verify_data,
+            record_heartbeat,
             export_license_public_key,
+            export_public_key_as,
+            ensure_keys,
+            has_signing_capability,
             generate_rsa_key_pair,
+            cancel_key_generation,
+            restore_key_pair,
+            repair_public_key,
+            fetch_and_pin_public_key,
+            export_private_key,
+            import_private_key,
             generate_license_key_with_machine_code,
+            generate_license_key_until,
+            generate_license_key_with_short_code,
+            create_short_code,
+            redeem_short_code,
             validate_license_key_with_machine_code,
+            clear_validation_cache,
+            set_validation_policy,
+            get_validation_policy,
+            validate_license_file,
+            validate_license_dir,
+            validate_best_license,
+            effective_features,
+            license_size_info,
+            save_license_to_file,
+            backup_database,
+            restore_database,
+            export_license_bundle,
+            generate_offline_verification_bundle,
+            export_licenses,
+            export_licenses_between,
+            merge_database,
+            import_license_bundle,
+            redeem_license,
+            revoke_license,
+            verify_current_machine,
             get_current_machine_id,
+            get_machine_code_input,
+            get_machine_id_versioned,
+            get_machine_id_formatted,
+            get_machine_identifiers,
+            refresh_machine_id,
+            generate_machine_token,
+            verify_machine_token,
+            reset_machine_salt,
             delete_license_by_id,
+            list_signing_keys,
+            list_profiles,
+            create_profile,
+            save_template,
+            get_templates,
+            delete_template,
+            generate_license_from_template,
+            generate_licenses_for_machines,
+            set_license_limits,
+            get_license_limits,
+            set_feature_catalog,
+            get_feature_catalog,
+            check_feature_catalog,
+            set_default_issuer,
+            set_webhook_url,
+            clear_webhook_url,
+            set_feature_registry,
+            get_feature_registry,
+            check_clock_trust,
+            create_time_anchor,
+            install_time_anchor,
+            get_time_anchor,
+            #[cfg(feature = "benchmark")]
+            benchmark_verification,
+            #[cfg(all(debug_assertions, feature = "simulate-validation"))]
+            simulate_validation_state,
+            start_license_watch,
+            stop_license_watch,
+            schedule_expiry_notification,
+            cancel_expiry_notification,
+            run_diagnostics,
+            diagnose_license,
+            verify_store_integrity,
+            set_trusted_key_fingerprints,
+            get_trusted_key_fingerprints,
+            set_update_endpoint,
+            get_update_endpoint,
+            get_updater_config_status,
             check_update,
+            download_update_only,
             install_update
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|_app, event| {
+            // 退出时停止后台监控任务
+            if let tauri::RunEvent::Exit = event {
+                watch::stop_license_watch();
+                notification::cancel_all_expiry_notifications();
+            }
+        });
 }
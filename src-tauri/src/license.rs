@@ -1,18 +1,55 @@
+use crate::audit;
+use crate::cbor;
+use crate::clock;
+use crate::machine_code::{
+    self, get_machine_id_versioned, MachineCodeInput, CURRENT_MACHINE_CODE_VERSION,
+    MACHINE_CODE_LEN, MACHINE_IDENTIFIER_COMPONENTS,
+};
+use crate::policy::{self, ValidationPolicy};
+use crate::remote_key;
+use crate::settings;
+use crate::store;
+use crate::time_anchor;
+use crate::timezone::TimeZone;
+use crate::webhook;
 use base64::{engine::general_purpose, Engine as _};
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use pkcs8::der::oid::AssociatedOid;
 use pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey};
 use rand::rngs::OsRng;
+use rsa::pkcs1::EncodeRsaPublicKey;
+use rsa::traits::PublicKeyParts;
 use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// 许可证等级
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LicenseTier {
+    #[default]
+    Standard,
+    Pro,
+    Enterprise,
+}
+
+impl LicenseTier {
+    fn is_standard(&self) -> bool {
+        *self == LicenseTier::Standard
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct LicenseInfo {
     pub license_id: String,
     pub customer_name: String,
@@ -22,6 +59,164 @@ pub struct LicenseInfo {
     pub features: Vec<String>,
     pub signature: String,
     pub machine_code: Option<String>,
+    // 旧版许可证没有该字段，反序列化为标准版；标准版不写入签名数据，保证旧签名仍然有效
+    #[serde(default, skip_serializing_if = "LicenseTier::is_standard")]
+    pub tier: LicenseTier,
+    // 需要定期联网的订阅许可证：两次心跳之间允许的最长天数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_interval_days: Option<u32>,
+    // 一次性许可证：只能在一台机器上兑换一次
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub single_use: bool,
+    // 绑定机器时使用的机器码算法版本，旧版绑定许可证没有该字段，按版本1处理
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine_code_version: Option<u32>,
+    // 生效时间，早于该时间的验证均失败
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<DateTime<Utc>>,
+    // 按功能注册表压缩的功能位掩码（十六进制），未注册的功能仍保留在features中
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature_bits: Option<String>,
+    // 附加的签名元数据（如经销商ID、区域代码），按键排序以保证签名数据确定
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    // 签名密钥标识（公钥指纹前16位），密钥轮换后用于区分签名所用的密钥
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    // 签发方（公司或签发人名称），为空表示未指定
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub issuer: String,
+    // 首次使用时绑定：签发时不绑定机器，首次带机器码验证时绑定到该机器
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub bind_on_first_use: bool,
+    // 签名使用的摘要算法，旧版许可证没有该字段，按SHA-256处理
+    #[serde(default, skip_serializing_if = "HashAlgorithm::is_sha256")]
+    pub hash_algorithm: HashAlgorithm,
+    // 单个功能的到期时间（按功能基础名称），未列出的功能随许可证整体有效期
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub feature_expiry: BTreeMap<String, DateTime<Utc>>,
+    // 部分硬件绑定：标识项名称 -> 签发时目标机器的标识（见 MachineIdentifiers），
+    // 验证时只比较列出的标识项
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hardware_binding: BTreeMap<String, String>,
+    // 需要通过验证的签名总数（含主签名），随主签名一起签名；0和1表示只需主签名。
+    // 联合签名本身不在签名数据中，只能按这里签名的数量要求，不能要求“附加的都要有效”
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub required_signatures: u32,
+    // 联合签名，不参与任何签名的计算，后续签名不会使已有签名失效
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub co_signatures: Vec<SignatureEntry>,
+    // 签名数据的编码方式，JSON签名的旧许可证没有该字段
+    #[serde(default, skip_serializing_if = "PayloadFormat::is_json")]
+    pub payload_format: PayloadFormat,
+    // 验证该许可证应使用的公钥的完整SHA-256指纹，多签发方时用于区分用错了公钥和签名无效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_fingerprint: Option<String>,
+}
+
+// 许可证载荷和签名数据的编码方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    // 对 serde_json 序列化结果签名，密钥前缀 v1/v2
+    #[default]
+    Json,
+    // 对确定性CBOR编码签名，不受字段顺序和转义方式影响，密钥前缀 v3
+    Cbor,
+}
+
+impl PayloadFormat {
+    fn is_json(&self) -> bool {
+        *self == PayloadFormat::Json
+    }
+}
+
+// 联合签名：签名所用密钥的标识和Base64编码的签名
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SignatureEntry {
+    pub kid: String,
+    pub signature: String,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+// 许可证签名的摘要算法
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn is_sha256(&self) -> bool {
+        *self == HashAlgorithm::Sha256
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Sha384 => "SHA-384",
+            HashAlgorithm::Sha512 => "SHA-512",
+        }
+    }
+}
+
+// 签发许可证时的可选参数
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LicenseOptions {
+    pub tier: LicenseTier,
+    pub heartbeat_interval_days: Option<u32>,
+    pub single_use: bool,
+    // 从签发起推迟生效的天数，有效期从生效时间开始计算
+    pub start_offset_days: Option<u32>,
+    // 按功能注册表将已注册的功能压缩为位掩码
+    pub pack_features: bool,
+    // 按全部签发输入和签发日期生成确定性的许可证ID（UUIDv5），相同输入得到相同ID；
+    // 同一天内用相同输入重复签发时返回已签发的许可证，不新增记录
+    pub deterministic_id: bool,
+    // 写入许可证并参与签名的元数据
+    pub metadata: BTreeMap<String, String>,
+    // 签发方名称，未指定时使用设置中的默认签发方
+    pub issuer: Option<String>,
+    // 不预先收集机器码，首次验证时绑定到验证所在的机器
+    pub bind_on_first_use: bool,
+    // 签名使用的摘要算法，默认SHA-256
+    pub hash_algorithm: HashAlgorithm,
+    // 单独到期的功能及其到期时间，功能必须包含在签发的功能列表中
+    pub feature_expiry: BTreeMap<String, DateTime<Utc>>,
+    // 部分硬件绑定的标识项及其取值，取值来自目标机器的 get_machine_identifiers
+    pub hardware_binding: BTreeMap<String, String>,
+    // 需要的签名总数（含主签名），大于1时需通过 add_signature 添加联合签名
+    pub required_signatures: u32,
+    // 载荷编码方式，CBOR编码的许可证（v3）需要支持该格式的验证端
+    pub payload_format: PayloadFormat,
+    // 在许可证中嵌入签名公钥的指纹
+    pub embed_key_fingerprint: bool,
+}
+
+// 一次性许可证的兑换记录
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Redemption {
+    pub machine_code: String,
+    pub redeemed_at: DateTime<Utc>,
+}
+
+// 许可证的吊销记录
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Revocation {
+    pub revoked_at: DateTime<Utc>,
+    #[serde(default)]
+    pub reason: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,13 +224,105 @@ pub struct LicenseValidationResult {
     pub is_valid: bool,
     pub info: Option<LicenseInfo>,
     pub message: String,
+    pub tier: Option<LicenseTier>,
+    // 验证签名时匹配到的密钥标识，无法匹配任何已知密钥时为 "unknown"
+    pub kid: Option<String>,
+    pub issuer: Option<String>,
+    // 首次使用绑定后重新签名的许可证密钥（仅在具备签发能力时生成），应替换原密钥
+    pub bound_license_key: Option<String>,
+    // 已单独到期的功能，许可证整体仍然有效
+    pub expired_features: Vec<String>,
+    // 按功能验证时的功能状态，其他验证为 None
+    pub feature_status: Option<FeatureStatus>,
+    // 许可证已过期，但仍在验证策略允许的宽限期内
+    pub in_grace_period: bool,
+}
+
+impl LicenseValidationResult {
+    fn new(is_valid: bool, info: Option<LicenseInfo>, message: &str) -> Self {
+        let tier = info.as_ref().map(|info| info.tier);
+        let issuer = info
+            .as_ref()
+            .map(|info| info.issuer.clone())
+            .filter(|issuer| !issuer.is_empty());
+        LicenseValidationResult {
+            is_valid,
+            info,
+            message: message.to_string(),
+            tier,
+            kid: None,
+            issuer,
+            bound_license_key: None,
+            expired_features: Vec::new(),
+            feature_status: None,
+            in_grace_period: false,
+        }
+    }
+
+    // 沿用原验证结果匹配到的密钥标识
+    fn with_kid(mut self, kid: Option<String>) -> Self {
+        self.kid = kid;
+        self
+    }
 }
 
+// 当前机器的许可证校验结果
 #[derive(Debug, Serialize, Deserialize)]
+pub struct MachineVerificationResult {
+    pub machine_id: String,
+    // 许可证未绑定机器时视为匹配
+    pub machine_matched: bool,
+    pub result: LicenseValidationResult,
+}
+
+// 公钥导出格式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublicKeyFormat {
+    // SubjectPublicKeyInfo PEM（默认格式）
+    PemSpki,
+    // SubjectPublicKeyInfo DER，以Base64返回
+    DerSpki,
+    // PKCS#1 RSAPublicKey PEM
+    PemPkcs1,
+}
+
+// 许可证统计信息
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct LicenseStats {
+    pub total: usize,
+    pub active: usize,
+    pub expired: usize,
+    // 30天内到期的有效许可证数量
+    pub expiring_soon: usize,
+    // 已吊销的许可证数量，不计入有效和已过期
+    pub revoked: usize,
+}
+
+// 许可证数据库文件的结构版本，修改结构时递增，并在 store::migrate_database 中添加升级步骤
+pub const CURRENT_DB_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LicenseDatabase {
+    // 版本1的数据库文件没有该字段
+    #[serde(default = "legacy_db_schema_version")]
+    pub schema_version: u32,
     pub licenses: Vec<LicenseInfo>,
 }
 
+fn legacy_db_schema_version() -> u32 {
+    1
+}
+
+impl Default for LicenseDatabase {
+    fn default() -> Self {
+        LicenseDatabase {
+            schema_version: CURRENT_DB_SCHEMA_VERSION,
+            licenses: Vec::new(),
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum LicenseError {
@@ -44,6 +331,56 @@ pub enum LicenseError {
     ExpiredLicense,
     InvalidSignature,
     FileError(String),
+    CorruptedKeyFile {
+        path: String,
+        backup_available: bool,
+    },
+    PublicKeyNotFound,
+    NotFound(String),
+    // 密钥文件不存在，内容为给用户的提示
+    KeyNotFound(String),
+    // 文件或目录权限不足，action 为被拒绝的操作（如“写入”）
+    PermissionError {
+        path: String,
+        action: String,
+    },
+    // 后台任务异常中止等内部错误
+    Internal(String),
+}
+
+impl LicenseError {
+    // 供前端区分错误类型的错误码
+    pub fn code(&self) -> &'static str {
+        match self {
+            LicenseError::SerializationError(_) => "serialization_error",
+            LicenseError::ValidationError(_) => "validation_error",
+            LicenseError::ExpiredLicense => "expired_license",
+            LicenseError::InvalidSignature => "invalid_signature",
+            LicenseError::FileError(_) => "file_error",
+            LicenseError::CorruptedKeyFile { .. } => "corrupted_key_file",
+            LicenseError::PublicKeyNotFound => "public_key_not_found",
+            LicenseError::NotFound(_) => "not_found",
+            LicenseError::KeyNotFound(_) => "key_not_found",
+            LicenseError::PermissionError { .. } => "permission_error",
+            LicenseError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+// 返回给前端的结构化错误，包含错误码和错误信息
+#[derive(Debug, Serialize)]
+pub struct ErrorPayload {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<LicenseError> for ErrorPayload {
+    fn from(error: LicenseError) -> Self {
+        ErrorPayload {
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
 }
 
 impl fmt::Display for LicenseError {
@@ -54,67 +391,321 @@ impl fmt::Display for LicenseError {
             LicenseError::ExpiredLicense => write!(f, "许可证已过期"),
             LicenseError::InvalidSignature => write!(f, "无效的许可证签名"),
             LicenseError::FileError(e) => write!(f, "文件操作错误: {}", e),
+            LicenseError::CorruptedKeyFile {
+                path,
+                backup_available,
+            } => {
+                if *backup_available {
+                    write!(
+                        f,
+                        "密钥文件已损坏: {}，可使用备份文件 {}.bak 恢复",
+                        path, path
+                    )
+                } else {
+                    write!(f, "密钥文件已损坏: {}，且没有可用的备份", path)
+                }
+            }
+            LicenseError::PublicKeyNotFound => {
+                write!(f, "未找到公钥文件，请先导入公钥或生成密钥对")
+            }
+            LicenseError::NotFound(id) => write!(f, "许可证不存在: {}", id),
+            LicenseError::KeyNotFound(message) => write!(f, "{}", message),
+            LicenseError::PermissionError { path, action } => write!(
+                f,
+                "没有{}权限: {}，请确认当前用户对该目录有读写权限，或通过 DRILLING_DATA_DIR 环境变量改用可写的数据目录",
+                action, path
+            ),
+            LicenseError::Internal(e) => write!(f, "内部错误: {}", e),
         }
     }
 }
 
 impl Error for LicenseError {}
 
-// 获取密钥存储目录
-fn get_keys_dir() -> PathBuf {
+// 文件操作失败时区分权限不足和其他错误：权限不足返回 PermissionError，其他错误返回带 message 的 FileError
+pub(crate) fn file_error(
+    error: &io::Error,
+    path: &Path,
+    action: &str,
+    message: String,
+) -> LicenseError {
+    if error.kind() == io::ErrorKind::PermissionDenied {
+        LicenseError::PermissionError {
+            path: path.display().to_string(),
+            action: action.to_string(),
+        }
+    } else {
+        LicenseError::FileError(message)
+    }
+}
+
+// 默认配置文件名称，对应密钥目录本身
+const DEFAULT_PROFILE: &str = "default";
+
+// 运行时设置的数据目录，优先级高于环境变量
+static DATA_DIR_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// 设置或清除运行时数据目录
+pub fn set_data_dir_override(dir: Option<PathBuf>) {
+    let mut data_dir = DATA_DIR_OVERRIDE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *data_dir = dir;
+    drop(data_dir);
+
+    // 版本2的机器码依赖数据目录中的盐值
+    machine_code::clear_machine_id_cache();
+    clear_validation_cache();
+}
+
+// 当前的运行时数据目录，未设置时为 None
+pub(crate) fn data_dir_override() -> Option<PathBuf> {
+    DATA_DIR_OVERRIDE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+// 平台默认的应用数据目录
+fn default_app_dir() -> Result<PathBuf, LicenseError> {
+    let env_var = |name: &str| {
+        std::env::var(name).map_err(|_| {
+            LicenseError::FileError(format!(
+                "无法获取{}环境变量，请通过DRILLING_DATA_DIR指定数据目录",
+                name
+            ))
+        })
+    };
+
     let app_dir = if cfg!(target_os = "windows") {
-        let app_data = std::env::var("APPDATA").expect("无法获取APPDATA环境变量");
-        PathBuf::from(app_data).join("drilling-system")
+        PathBuf::from(env_var("APPDATA")?).join("drilling-system")
     } else if cfg!(target_os = "macos") {
-        let home = std::env::var("HOME").expect("无法获取HOME环境变量");
-        PathBuf::from(home)
+        PathBuf::from(env_var("HOME")?)
             .join("Library")
             .join("Application Support")
             .join("drilling-system")
     } else {
         // Linux
-        let home = std::env::var("HOME").expect("无法获取HOME环境变量");
-        PathBuf::from(home).join(".config").join("drilling-system")
+        PathBuf::from(env_var("HOME")?)
+            .join(".config")
+            .join("drilling-system")
+    };
+
+    Ok(app_dir)
+}
+
+// 应用数据目录：运行时设置 > DRILLING_DATA_DIR环境变量 > 平台默认目录
+pub(crate) fn get_app_dir() -> Result<PathBuf, LicenseError> {
+    let override_dir = DATA_DIR_OVERRIDE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+
+    let app_dir = match override_dir {
+        Some(dir) => dir,
+        None => match std::env::var("DRILLING_DATA_DIR") {
+            Ok(dir) if !dir.trim().is_empty() => PathBuf::from(dir),
+            _ => default_app_dir()?,
+        },
     };
 
-    let keys_dir = app_dir.join("keys");
     // 确保目录存在
-    fs::create_dir_all(&keys_dir).expect("无法创建密钥目录");
+    fs::create_dir_all(&app_dir).map_err(|e| {
+        file_error(
+            &e,
+            &app_dir,
+            "创建目录",
+            format!("无法创建应用数据目录 {}: {}", app_dir.display(), e),
+        )
+    })?;
+
+    Ok(app_dir)
+}
+
+// 获取密钥存储根目录
+fn get_keys_root_dir() -> Result<PathBuf, LicenseError> {
+    let keys_dir = get_app_dir()?.join("keys");
+    // 确保目录存在
+    fs::create_dir_all(&keys_dir).map_err(|e| {
+        file_error(
+            &e,
+            &keys_dir,
+            "创建目录",
+            format!("无法创建密钥目录 {}: {}", keys_dir.display(), e),
+        )
+    })?;
+
+    Ok(keys_dir)
+}
+
+// 校验配置文件名称，只允许字母、数字、下划线和短横线
+fn validate_profile_name(name: &str) -> Result<(), LicenseError> {
+    // retired、cosigners 和 backups 为默认配置下存放已停用公钥、联合签名公钥和密钥备份的目录
+    let is_valid = !name.is_empty()
+        && name != RETIRED_KEYS_DIR
+        && name != COSIGNER_KEYS_DIR
+        && name != KEY_BACKUPS_DIR
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(LicenseError::ValidationError(format!(
+            "无效的配置文件名称: {}",
+            name
+        )))
+    }
+}
+
+// 获取指定配置文件的密钥目录，未指定或为默认配置时使用密钥根目录
+fn get_keys_dir(profile: Option<&str>) -> Result<PathBuf, LicenseError> {
+    let root = get_keys_root_dir()?;
+    match profile {
+        None | Some(DEFAULT_PROFILE) => Ok(root),
+        Some(name) => {
+            validate_profile_name(name)?;
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).map_err(|e| {
+                file_error(
+                    &e,
+                    &dir,
+                    "创建目录",
+                    format!("创建配置文件目录 {} 失败: {}", dir.display(), e),
+                )
+            })?;
+            Ok(dir)
+        }
+    }
+}
+
+// 已停用公钥的目录名称
+const RETIRED_KEYS_DIR: &str = "retired";
+
+// 联合签名公钥的目录名称
+const COSIGNER_KEYS_DIR: &str = "cosigners";
 
-    keys_dir
+// 联合签名公钥目录，受信任的联合签名方公钥以 <kid>.pem 保存在此
+fn get_cosigner_keys_dir(profile: Option<&str>) -> Result<PathBuf, LicenseError> {
+    Ok(get_keys_dir(profile)?.join(COSIGNER_KEYS_DIR))
+}
+
+// 已停用公钥目录，密钥轮换时旧公钥以 <kid>.pem 保存在此，用于识别旧密钥签发的许可证
+fn get_retired_keys_dir(profile: Option<&str>) -> Result<PathBuf, LicenseError> {
+    Ok(get_keys_dir(profile)?.join(RETIRED_KEYS_DIR))
 }
 
 // 获取私钥路径
-fn get_private_key_path() -> PathBuf {
-    get_keys_dir().join("private_key.pem")
+fn get_private_key_path(profile: Option<&str>) -> Result<PathBuf, LicenseError> {
+    Ok(get_keys_dir(profile)?.join("private_key.pem"))
 }
 
 // 获取公钥路径
-fn get_public_key_path() -> PathBuf {
-    get_keys_dir().join("public_key.pem")
+fn get_public_key_path(profile: Option<&str>) -> Result<PathBuf, LicenseError> {
+    Ok(get_keys_dir(profile)?.join("public_key.pem"))
+}
+
+// 密钥文件的校验和文件路径
+fn key_checksum_path(path: &Path) -> PathBuf {
+    let mut file_name = path.as_os_str().to_owned();
+    file_name.push(".sha256");
+    PathBuf::from(file_name)
+}
+
+// 密钥文件的备份路径（最后一次校验通过的内容）
+fn key_backup_path(path: &Path) -> PathBuf {
+    let mut file_name = path.as_os_str().to_owned();
+    file_name.push(".bak");
+    PathBuf::from(file_name)
+}
+
+// 计算密钥内容的SHA-256校验和
+fn key_checksum(pem: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(pem.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+// 读取密钥文件，存在校验和文件时校验完整性
+fn read_key_file(path: &Path, label: &str) -> Result<String, LicenseError> {
+    let mut file = File::open(path).map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            LicenseError::KeyNotFound(format!("{}文件不存在: {}", label, path.display()))
+        } else {
+            file_error(&e, path, "读取", format!("无法打开{}文件: {}", label, e))
+        }
+    })?;
+    let mut pem = String::new();
+    file.read_to_string(&mut pem)
+        .map_err(|e| file_error(&e, path, "读取", format!("无法读取{}文件: {}", label, e)))?;
+
+    let checksum_path = key_checksum_path(path);
+    let backup_path = key_backup_path(path);
+
+    // 只读取不写入：没有校验和文件（旧版本或只分发了公钥的验证端）时不校验，
+    // 校验和与备份只在写入密钥时生成
+    if let Ok(expected) = fs::read_to_string(&checksum_path) {
+        let expected = expected.trim();
+        if key_checksum(&pem) != expected {
+            // 备份文件内容与校验和一致时才认为备份可用
+            let backup_available = fs::read_to_string(&backup_path)
+                .map(|backup| key_checksum(&backup) == expected)
+                .unwrap_or(false);
+            return Err(LicenseError::CorruptedKeyFile {
+                path: path.display().to_string(),
+                backup_available,
+            });
+        }
+    }
+
+    Ok(pem)
+}
+
+// 写入密钥文件，同时更新校验和与备份
+fn write_key_file(path: &Path, pem: &str, label: &str) -> Result<(), LicenseError> {
+    let mut file = File::create(path)
+        .map_err(|e| file_error(&e, path, "写入", format!("创建{}文件失败: {}", label, e)))?;
+    file.write_all(pem.as_bytes())
+        .map_err(|e| file_error(&e, path, "写入", format!("写入{}文件失败: {}", label, e)))?;
+
+    let checksum_path = key_checksum_path(path);
+    fs::write(&checksum_path, key_checksum(pem)).map_err(|e| {
+        file_error(
+            &e,
+            &checksum_path,
+            "写入",
+            format!("写入{}校验和文件失败: {}", label, e),
+        )
+    })?;
+    let backup_path = key_backup_path(path);
+    fs::write(&backup_path, pem).map_err(|e| {
+        file_error(
+            &e,
+            &backup_path,
+            "写入",
+            format!("写入{}备份文件失败: {}", label, e),
+        )
+    })?;
+
+    // 密钥变化后缓存的签名校验结果不再可靠
+    clear_validation_cache();
+    Ok(())
 }
 
 // 加载或生成密钥对
-fn load_or_generate_keys() -> Result<(RsaPrivateKey, RsaPublicKey), LicenseError> {
-    let private_key_path = get_private_key_path();
-    let public_key_path = get_public_key_path();
+fn load_or_generate_keys(
+    profile: Option<&str>,
+) -> Result<(RsaPrivateKey, RsaPublicKey), LicenseError> {
+    let private_key_path = get_private_key_path(profile)?;
+    let public_key_path = get_public_key_path(profile)?;
 
     // 检查密钥文件是否存在
     if private_key_path.exists() && public_key_path.exists() {
-        // 从文件加载密钥
-        let mut private_key_file = File::open(&private_key_path)
-            .map_err(|e| LicenseError::FileError(format!("无法打开私钥文件: {}", e)))?;
-        let mut private_key_pem = String::new();
-        private_key_file
-            .read_to_string(&mut private_key_pem)
-            .map_err(|e| LicenseError::FileError(format!("无法读取私钥文件: {}", e)))?;
-
-        let mut public_key_file = File::open(&public_key_path)
-            .map_err(|e| LicenseError::FileError(format!("无法打开公钥文件: {}", e)))?;
-        let mut public_key_pem = String::new();
-        public_key_file
-            .read_to_string(&mut public_key_pem)
-            .map_err(|e| LicenseError::FileError(format!("无法读取公钥文件: {}", e)))?;
+        // 从文件加载密钥，并校验完整性
+        let private_key_pem = read_key_file(&private_key_path, "私钥")?;
+        let public_key_pem = read_key_file(&public_key_path, "公钥")?;
 
         // 解析密钥
         let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem)
@@ -123,6 +714,11 @@ fn load_or_generate_keys() -> Result<(RsaPrivateKey, RsaPublicKey), LicenseError
             .map_err(|e| LicenseError::ValidationError(format!("无法解析公钥: {}", e)))?;
 
         Ok((private_key, public_key))
+    } else if private_key_path.exists() {
+        // 只剩私钥时不能生成新密钥，否则会覆盖私钥
+        Err(LicenseError::ValidationError(
+            "公钥文件缺失，请先使用 repair_public_key 从私钥恢复公钥".to_string(),
+        ))
     } else {
         // 生成新的密钥对
         println!("密钥文件不存在，正在生成新的密钥对...");
@@ -139,408 +735,3695 @@ fn load_or_generate_keys() -> Result<(RsaPrivateKey, RsaPublicKey), LicenseError
             .to_public_key_pem(pkcs8::LineEnding::LF)
             .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
 
-        let mut private_key_file = File::create(&private_key_path)
-            .map_err(|e| LicenseError::FileError(format!("创建私钥文件失败: {}", e)))?;
-        private_key_file
-            .write_all(private_key_pem.as_bytes())
-            .map_err(|e| LicenseError::FileError(format!("写入私钥文件失败: {}", e)))?;
-
-        let mut public_key_file = File::create(&public_key_path)
-            .map_err(|e| LicenseError::FileError(format!("创建公钥文件失败: {}", e)))?;
-        public_key_file
-            .write_all(public_key_pem.as_bytes())
-            .map_err(|e| LicenseError::FileError(format!("写入公钥文件失败: {}", e)))?;
+        write_key_file(&private_key_path, &private_key_pem, "私钥")?;
+        write_key_file(&public_key_path, &public_key_pem, "公钥")?;
+        write_active_key_metadata(profile)?;
 
         Ok((private_key, public_key))
     }
 }
 
-// 生成RSA签名
-fn generate_signature(data: &str) -> Result<String, LicenseError> {
-    // 加载或生成密钥
-    let (private_key, _) = load_or_generate_keys()?;
+// 编译时嵌入的公钥
+#[cfg(feature = "embedded-public-key")]
+const EMBEDDED_PUBLIC_KEY_PEM: &str =
+    include_str!(concat!(env!("OUT_DIR"), "/embedded_public_key.pem"));
 
-    // 计算数据的SHA-256哈希值
-    let mut hasher = Sha256::new();
-    hasher.update(data.as_bytes());
-    let hashed = hasher.finalize();
+// 仅加载公钥，公钥不存在时返回错误而不是生成新的密钥对（验证端不应产生私钥）
+// 启用 embedded-public-key 时始终使用嵌入的公钥，忽略磁盘上的公钥文件
+#[cfg(feature = "embedded-public-key")]
+fn load_public_key(_profile: Option<&str>) -> Result<RsaPublicKey, LicenseError> {
+    RsaPublicKey::from_public_key_pem(EMBEDDED_PUBLIC_KEY_PEM)
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析嵌入的公钥: {}", e)))
+}
 
-    // 使用私钥对哈希值进行签名
-    let signature = private_key
-        .sign_with_rng(&mut OsRng, Pkcs1v15Sign::new::<Sha256>(), &hashed)
-        .map_err(|e| LicenseError::ValidationError(format!("签名失败: {}", e)))?;
+// 仅加载公钥，公钥不存在时返回错误而不是生成新的密钥对（验证端不应产生私钥）
+#[cfg(not(feature = "embedded-public-key"))]
+fn load_public_key(profile: Option<&str>) -> Result<RsaPublicKey, LicenseError> {
+    let public_key_path = get_public_key_path(profile)?;
+    if !public_key_path.exists() {
+        return Err(LicenseError::PublicKeyNotFound);
+    }
 
-    // 返回Base64编码的签名
-    Ok(general_purpose::STANDARD.encode(&signature))
+    let public_key_pem = read_key_file(&public_key_path, "公钥")?;
+    RsaPublicKey::from_public_key_pem(&public_key_pem)
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析公钥: {}", e)))
 }
 
-// 验证RSA签名
-fn verify_signature(data: &str, signature_base64: &str) -> Result<bool, LicenseError> {
-    // 加载密钥
-    let (_, public_key) = load_or_generate_keys()?;
+// 密钥标识：公钥指纹的前16位
+fn key_id(public_key: &RsaPublicKey) -> Result<String, LicenseError> {
+    Ok(key_fingerprint(public_key)?[..16].to_string())
+}
 
-    // 计算数据的SHA-256哈希值
-    let mut hasher = Sha256::new();
-    hasher.update(data.as_bytes());
-    let hashed = hasher.finalize();
+// 将当前公钥移入已停用目录，轮换密钥前调用
+fn retire_current_public_key(profile: Option<&str>) -> Result<(), LicenseError> {
+    let public_key = match load_public_key(profile) {
+        Ok(key) => key,
+        Err(LicenseError::PublicKeyNotFound) => return Ok(()),
+        Err(e) => return Err(e),
+    };
 
-    // 解码Base64签名
-    let signature = general_purpose::STANDARD
-        .decode(signature_base64)
-        .map_err(|e| LicenseError::ValidationError(format!("解码签名失败: {}", e)))?;
+    let retired_dir = get_retired_keys_dir(profile)?;
+    fs::create_dir_all(&retired_dir).map_err(|e| {
+        file_error(
+            &e,
+            &retired_dir,
+            "创建目录",
+            format!("创建已停用密钥目录失败: {}", e),
+        )
+    })?;
+    let public_key_pem = public_key
+        .to_public_key_pem(pkcs8::LineEnding::LF)
+        .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
 
-    // 验证签名
-    let result = public_key.verify(Pkcs1v15Sign::new::<Sha256>(), &hashed, &signature);
+    let kid = key_id(&public_key)?;
+    write_key_file(
+        &retired_dir.join(format!("{}.pem", kid)),
+        &public_key_pem,
+        "已停用公钥",
+    )?;
 
-    // 返回验证结果
-    match result {
-        Ok(_) => Ok(true),
-        Err(_) => Ok(false),
-    }
+    // 保留创建时间并记录停用时间
+    let mut metadata: KeyMetadata = read_json_file(&get_key_metadata_path(profile)?, "密钥信息")?;
+    metadata.retired_at = Some(clock::now());
+    write_json_file(
+        &retired_dir.join(format!("{}.json", kid)),
+        &metadata,
+        "密钥信息",
+    )
 }
 
-// 许可证数据库文件路径
-fn get_license_db_path() -> PathBuf {
-    let app_dir = if cfg!(target_os = "windows") {
-        let app_data = std::env::var("APPDATA").expect("无法获取APPDATA环境变量");
-        PathBuf::from(app_data).join("drilling-system")
-    } else if cfg!(target_os = "macos") {
-        let home = std::env::var("HOME").expect("无法获取HOME环境变量");
-        PathBuf::from(home)
-            .join("Library")
-            .join("Application Support")
-            .join("drilling-system")
-    } else {
-        // Linux
-        let home = std::env::var("HOME").expect("无法获取HOME环境变量");
-        PathBuf::from(home).join(".config").join("drilling-system")
+// 密钥元数据，与PEM文件保存在同一目录；旧版本生成的密钥没有元数据
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct KeyMetadata {
+    created_at: Option<DateTime<Utc>>,
+    retired_at: Option<DateTime<Utc>>,
+}
+
+// 当前密钥的元数据文件路径
+fn get_key_metadata_path(profile: Option<&str>) -> Result<PathBuf, LicenseError> {
+    Ok(get_keys_dir(profile)?.join("key_info.json"))
+}
+
+// 生成新密钥后记录创建时间
+fn write_active_key_metadata(profile: Option<&str>) -> Result<(), LicenseError> {
+    let metadata = KeyMetadata {
+        created_at: Some(clock::now()),
+        retired_at: None,
     };
-    println!("目录{}", app_dir.display());
-    // 确保目录存在
-    fs::create_dir_all(&app_dir).expect("无法创建应用数据目录");
+    write_json_file(&get_key_metadata_path(profile)?, &metadata, "密钥信息")
+}
 
-    app_dir.join("licenses.json")
+// 签名密钥信息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SigningKeyInfo {
+    pub kid: String,
+    // 摘要算法由每个许可证单独指定，这里只给出签名方案
+    pub algorithm: String,
+    pub key_bits: usize,
+    pub created_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+    // 当前用于签发的密钥
+    pub active: bool,
+    pub retired: bool,
 }
 
-// 加载许可证数据库
-fn load_license_db() -> Result<LicenseDatabase, LicenseError> {
-    let db_path = get_license_db_path();
+// 列出配置文件下的当前密钥和所有已停用的密钥，当前密钥排在第一位
+pub fn list_signing_keys(profile: Option<&str>) -> Result<Vec<SigningKeyInfo>, LicenseError> {
+    let mut keys = Vec::new();
 
-    if !db_path.exists() {
-        return Ok(LicenseDatabase { licenses: vec![] });
+    match load_public_key(profile) {
+        Ok(public_key) => {
+            let metadata: KeyMetadata =
+                read_json_file(&get_key_metadata_path(profile)?, "密钥信息")?;
+            keys.push(SigningKeyInfo {
+                kid: key_id(&public_key)?,
+                algorithm: "RSA-PKCS1-v1_5".to_string(),
+                key_bits: public_key.size() * 8,
+                created_at: metadata.created_at,
+                retired_at: None,
+                active: has_signing_capability(profile),
+                retired: false,
+            });
+        }
+        Err(LicenseError::PublicKeyNotFound) => {}
+        Err(e) => return Err(e),
     }
 
-    let mut file = File::open(&db_path)
-        .map_err(|e| LicenseError::FileError(format!("打开数据库文件失败: {}", e)))?;
-
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .map_err(|e| LicenseError::FileError(format!("读取数据库文件失败: {}", e)))?;
+    let retired_dir = get_retired_keys_dir(profile)?;
+    let mut retired = Vec::new();
+    for (kid, public_key) in load_retired_public_keys(profile)? {
+        let metadata: KeyMetadata =
+            read_json_file(&retired_dir.join(format!("{}.json", kid)), "密钥信息")?;
+        retired.push(SigningKeyInfo {
+            kid,
+            algorithm: "RSA-PKCS1-v1_5".to_string(),
+            key_bits: public_key.size() * 8,
+            created_at: metadata.created_at,
+            retired_at: metadata.retired_at,
+            active: false,
+            retired: true,
+        });
+    }
+    // 最近停用的排在前面
+    retired.sort_by_key(|key| std::cmp::Reverse(key.retired_at));
+    keys.extend(retired);
 
-    serde_json::from_str(&contents)
-        .map_err(|e| LicenseError::SerializationError(format!("解析数据库失败: {}", e)))
+    Ok(keys)
 }
 
-// 保存许可证数据库
-fn save_license_db(db: &LicenseDatabase) -> Result<(), LicenseError> {
-    let db_path = get_license_db_path();
+// 加载所有已停用的公钥及其标识，无法读取的文件会被跳过
+fn load_retired_public_keys(
+    profile: Option<&str>,
+) -> Result<Vec<(String, RsaPublicKey)>, LicenseError> {
+    load_public_keys_in_dir(&get_retired_keys_dir(profile)?, "已停用公钥")
+}
 
-    let json = serde_json::to_string_pretty(db)
-        .map_err(|e| LicenseError::SerializationError(format!("序列化数据库失败: {}", e)))?;
+// 加载所有联合签名公钥及其标识，无法读取的文件会被跳过
+fn load_cosigner_public_keys(
+    profile: Option<&str>,
+) -> Result<Vec<(String, RsaPublicKey)>, LicenseError> {
+    load_public_keys_in_dir(&get_cosigner_keys_dir(profile)?, "联合签名公钥")
+}
 
-    let mut file = File::create(&db_path)
-        .map_err(|e| LicenseError::FileError(format!("创建数据库文件失败: {}", e)))?;
+// 加载目录中所有 .pem 公钥及其标识
+fn load_public_keys_in_dir(
+    dir: &Path,
+    label: &str,
+) -> Result<Vec<(String, RsaPublicKey)>, LicenseError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
 
-    file.write_all(json.as_bytes())
-        .map_err(|e| LicenseError::FileError(format!("写入数据库失败: {}", e)))?;
+    let entries = fs::read_dir(dir)
+        .map_err(|e| file_error(&e, dir, "读取", format!("读取{}目录失败: {}", label, e)))?;
+    let mut keys = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+            continue;
+        }
+        let Ok(pem) = read_key_file(&path, label) else {
+            continue;
+        };
+        if let Ok(key) = RsaPublicKey::from_public_key_pem(&pem) {
+            keys.push((key_id(&key)?, key));
+        }
+    }
 
-    Ok(())
+    Ok(keys)
 }
 
-pub fn generate_license(
-    customer_name: &str,
-    customer_email: &str,
-    expiry_days: u32,
-    features: Vec<String>,
-) -> Result<String, LicenseError> {
-    let now = Utc::now();
-    // 处理特殊的过期时间：0表示永不过期
-    let expiry = if expiry_days == 0 {
-        // 设置一个非常远的未来日期，比如100年后
-        now + Duration::days(36500) // 约100年
-    } else {
-        now + Duration::days(expiry_days as i64)
-    };
-
-    let license_id = Uuid::new_v4().to_string();
-
-    // 创建不包含签名的许可证信息
-    let license_data = LicenseInfo {
-        license_id,
-        customer_name: customer_name.to_string(),
-        customer_email: customer_email.to_string(),
-        issue_date: now,
-        expiry_date: expiry,
-        features,
-        signature: String::new(), // 暂时为空
-        machine_code: None,       // 无机器码限制
-    };
+// 签名数据：签名字段和联合签名置空后的许可证，按载荷格式编码为JSON或确定性CBOR
+fn signing_payload(info: &LicenseInfo) -> Result<Vec<u8>, LicenseError> {
+    let mut license_for_verification = info.clone();
+    license_for_verification.signature = String::new();
+    license_for_verification.co_signatures = Vec::new();
 
-    // 序列化为JSON
-    let json_data = serde_json::to_string(&license_data)
-        .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+    match info.payload_format {
+        PayloadFormat::Json => serde_json::to_vec(&license_for_verification)
+            .map_err(|e| LicenseError::SerializationError(e.to_string())),
+        PayloadFormat::Cbor => serde_json::to_value(&license_for_verification)
+            .map(|value| cbor::to_canonical_vec(&value))
+            .map_err(|e| LicenseError::SerializationError(e.to_string())),
+    }
+}
 
-    // 生成签名
-    let signature = generate_signature(&json_data)?;
+// 用指定的公钥（PEM）验证许可证签名，不读取磁盘上的公钥
+#[cfg_attr(not(feature = "embedded-public-key"), allow(dead_code))]
+pub(crate) fn verify_license_with_public_key(
+    info: &LicenseInfo,
+    public_key_pem: &str,
+) -> Result<bool, LicenseError> {
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem)
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析公钥: {}", e)))?;
+    verify_with_key(
+        &public_key,
+        &signing_payload(info)?,
+        &info.signature,
+        info.hash_algorithm,
+    )
+}
 
-    // 更新许可证信息，包含签名
-    let license_with_signature = LicenseInfo {
-        signature,
-        ..license_data
-    };
+// 确定许可证签名所用的密钥
+fn match_license_signing_key(
+    info: &LicenseInfo,
+    policy: &ValidationPolicy,
+    profile: Option<&str>,
+) -> Result<SigningKeyMatch, LicenseError> {
+    // 嵌入了公钥指纹的许可证先确认本地有对应的公钥（当前或已停用），给出比签名无效更明确的结果
+    if let Some(expected) = info.key_fingerprint.as_deref() {
+        let current = key_fingerprint(&load_public_key(profile)?)?;
+        let known = current.eq_ignore_ascii_case(expected)
+            || load_retired_public_keys(profile)?
+                .iter()
+                .map(|(_, key)| key_fingerprint(key))
+                .collect::<Result<Vec<_>, _>>()?
+                .iter()
+                .any(|fingerprint| fingerprint.eq_ignore_ascii_case(expected));
+        if !known {
+            return Ok(SigningKeyMatch::WrongKey {
+                expected: expected.to_ascii_lowercase(),
+                current,
+            });
+        }
+    }
 
-    // 保存到数据库
-    let mut db = load_license_db()?;
-    db.licenses.push(license_with_signature.clone());
-    save_license_db(&db)?;
+    match_signing_key(
+        &signing_payload(info)?,
+        &info.signature,
+        info.hash_algorithm,
+        policy,
+        profile,
+    )
+}
 
-    // 序列化并编码为Base64
-    let final_json = serde_json::to_string(&license_with_signature)
-        .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+// 检查许可证记录的签名是否由受信任的当前或已停用密钥生成（用于导入历史记录）
+pub(crate) fn is_signed_by_known_key(
+    info: &LicenseInfo,
+    profile: Option<&str>,
+) -> Result<bool, LicenseError> {
+    Ok(matches!(
+        match_license_signing_key(info, &policy::get_validation_policy()?, profile)?,
+        SigningKeyMatch::Current(_) | SigningKeyMatch::Retired(_)
+    ))
+}
 
-    Ok(general_purpose::STANDARD.encode(final_json))
+// 许可证签名匹配到的密钥
+#[derive(Clone)]
+enum SigningKeyMatch {
+    Current(String),
+    Retired(String),
+    // 签名由当前或已停用的公钥验证通过，但该公钥不受信任（不在受信任指纹列表中或不是固定的公钥）
+    Untrusted(String),
+    // 许可证嵌入的公钥指纹与本地的公钥都不一致
+    WrongKey { expected: String, current: String },
+    Unknown,
 }
 
-pub fn validate_license(license_key: &str) -> Result<LicenseValidationResult, LicenseError> {
-    // 解码Base64
-    let decoded = general_purpose::STANDARD
-        .decode(license_key)
-        .map_err(|e| LicenseError::ValidationError(format!("Base64解码失败: {}", e)))?;
+// 用错公钥时的提示
+fn wrong_key_message(expected: &str, current: &str) -> String {
+    format!(
+        "许可证应由指纹为 {} 的公钥验证，当前公钥指纹为 {}，请确认使用了对应签发方的公钥",
+        expected, current
+    )
+}
 
-    // 解析JSON
-    let license_data: LicenseInfo = serde_json::from_slice(&decoded)
-        .map_err(|e| LicenseError::ValidationError(format!("JSON解析失败: {}", e)))?;
+// 签名公钥是否受信任：除受信任指纹列表外，已固定远程公钥的配置文件只信任固定的公钥
+fn is_trusted_signing_key(
+    public_key: &RsaPublicKey,
+    policy: &ValidationPolicy,
+    profile: Option<&str>,
+) -> Result<bool, LicenseError> {
+    Ok(is_trusted_key(public_key, policy)? && matches_pinned_key(public_key, profile)?)
+}
 
-    // 验证签名
-    let signature = license_data.signature.clone();
-    let mut license_for_verification = license_data.clone();
-    license_for_verification.signature = String::new();
+// 已固定远程公钥的配置文件只接受固定的公钥（包括停用后的该公钥），未固定时不限制
+fn matches_pinned_key(
+    public_key: &RsaPublicKey,
+    profile: Option<&str>,
+) -> Result<bool, LicenseError> {
+    match remote_key::pinned_fingerprint(profile)? {
+        Some(pinned) => Ok(key_fingerprint(public_key)? == pinned),
+        None => Ok(true),
+    }
+}
 
-    let json_data = serde_json::to_string(&license_for_verification)
-        .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+// 公钥是否受信任：未配置受信任指纹时信任磁盘上的公钥，否则指纹必须在生效的列表中
+fn is_trusted_key(
+    public_key: &RsaPublicKey,
+    policy: &ValidationPolicy,
+) -> Result<bool, LicenseError> {
+    let Some(trusted) = policy::trusted_key_fingerprints(policy)? else {
+        return Ok(true);
+    };
 
-    let is_signature_valid = verify_signature(&json_data, &signature)?;
+    let fingerprint = key_fingerprint(public_key)?;
+    Ok(trusted.contains(&fingerprint))
+}
 
-    if !is_signature_valid {
-        return Ok(LicenseValidationResult {
-            is_valid: false,
-            info: None,
-            message: "许可证签名无效".to_string(),
-        });
+// 依次用当前公钥和已停用的公钥验证签名，确定签名所用的密钥
+fn match_signing_key(
+    data: &[u8],
+    signature_base64: &str,
+    hash: HashAlgorithm,
+    policy: &ValidationPolicy,
+    profile: Option<&str>,
+) -> Result<SigningKeyMatch, LicenseError> {
+    let public_key = load_public_key(profile)?;
+    if verify_with_key(&public_key, data, signature_base64, hash)? {
+        let kid = key_id(&public_key)?;
+        if !is_trusted_signing_key(&public_key, policy, profile)? {
+            return Ok(SigningKeyMatch::Untrusted(kid));
+        }
+        return Ok(SigningKeyMatch::Current(kid));
     }
 
-    // 检查过期时间
-    let now = Utc::now();
-
-    // 检查是否设置了特殊的过期时间（0表示永不过期）
-    let is_expired = match license_data.expiry_date.timestamp() {
-        // 如果时间戳为0或负数，表示永不过期
-        t if t <= 0 => false,
-        // 否则正常检查是否过期
-        _ => license_data.expiry_date < now,
-    };
-
-    if is_expired {
-        return Ok(LicenseValidationResult {
-            is_valid: false,
-            info: Some(license_data),
-            message: "许可证已过期".to_string(),
-        });
+    for (kid, key) in load_retired_public_keys(profile)? {
+        if verify_with_key(&key, data, signature_base64, hash)? {
+            if !is_trusted_signing_key(&key, policy, profile)? {
+                return Ok(SigningKeyMatch::Untrusted(kid));
+            }
+            return Ok(SigningKeyMatch::Retired(kid));
+        }
     }
 
-    // 有效许可证
-    Ok(LicenseValidationResult {
-        is_valid: true,
-        info: Some(license_data),
-        message: "许可证有效".to_string(),
-    })
+    Ok(SigningKeyMatch::Unknown)
 }
 
-// 获取所有许可证
-pub fn get_all_licenses() -> Result<Vec<LicenseInfo>, LicenseError> {
-    let db = load_license_db()?;
-    Ok(db.licenses)
+// 计算数据摘要，并返回对应摘要算法的PKCS#1 v1.5签名方案
+fn pkcs1v15_digest<D: Digest + AssociatedOid>(data: &[u8]) -> (Pkcs1v15Sign, Vec<u8>) {
+    (Pkcs1v15Sign::new::<D>(), D::digest(data).to_vec())
 }
 
-// 导出公钥
-pub fn export_public_key() -> String {
-    match File::open(get_public_key_path()) {
-        Ok(mut file) => {
-            let mut public_key_pem = String::new();
-            if file.read_to_string(&mut public_key_pem).is_ok() {
-                public_key_pem
-            } else {
-                "无法读取公钥文件".to_string()
-            }
-        }
-        Err(_) => match load_or_generate_keys() {
-            Ok(_) => match File::open(get_public_key_path()) {
-                Ok(mut file) => {
-                    let mut public_key_pem = String::new();
-                    if file.read_to_string(&mut public_key_pem).is_ok() {
-                        public_key_pem
-                    } else {
-                        "无法读取新生成的公钥文件".to_string()
-                    }
-                }
-                Err(_) => "无法打开新生成的公钥文件".to_string(),
-            },
-            Err(e) => format!("生成密钥对失败: {}", e),
-        },
+fn hash_for_signing(data: &[u8], hash: HashAlgorithm) -> (Pkcs1v15Sign, Vec<u8>) {
+    match hash {
+        HashAlgorithm::Sha256 => pkcs1v15_digest::<Sha256>(data),
+        HashAlgorithm::Sha384 => pkcs1v15_digest::<Sha384>(data),
+        HashAlgorithm::Sha512 => pkcs1v15_digest::<Sha512>(data),
     }
 }
 
-// 生成新的RSA密钥对
-pub fn generate_new_key_pair(bits: usize) -> Result<(String, String), LicenseError> {
-    // 生成随机的RSA私钥
-    let private_key = RsaPrivateKey::new(&mut OsRng, bits)
-        .map_err(|e| LicenseError::ValidationError(format!("生成RSA密钥失败: {}", e)))?;
+// 生成RSA签名；签名期间持有数据库锁，自检替换数据目录时不会用临时密钥签名
+fn generate_signature(
+    data: &[u8],
+    hash: HashAlgorithm,
+    profile: Option<&str>,
+) -> Result<String, LicenseError> {
+    let _lock = store::lock_license_db();
 
-    // 从私钥导出公钥
-    let public_key = RsaPublicKey::from(&private_key);
+    // 加载或生成密钥
+    let (private_key, _) = load_or_generate_keys(profile)?;
 
-    // 转换为PEM格式
-    let private_key_pem = private_key
-        .to_pkcs8_pem(pkcs8::LineEnding::LF)
-        .map_err(|e| LicenseError::ValidationError(format!("转换私钥格式失败: {}", e)))?
-        .to_string();
+    // 计算数据的哈希值
+    let (scheme, hashed) = hash_for_signing(data, hash);
 
-    let public_key_pem = public_key
-        .to_public_key_pem(pkcs8::LineEnding::LF)
-        .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
+    // 使用私钥对哈希值进行签名
+    let signature = private_key
+        .sign_with_rng(&mut OsRng, scheme, &hashed)
+        .map_err(|e| LicenseError::ValidationError(format!("签名失败: {}", e)))?;
 
-    // 保存到文件
-    let mut private_key_file = File::create(get_private_key_path())
-        .map_err(|e| LicenseError::FileError(format!("创建私钥文件失败: {}", e)))?;
-    private_key_file
-        .write_all(private_key_pem.as_bytes())
-        .map_err(|e| LicenseError::FileError(format!("写入私钥文件失败: {}", e)))?;
+    // 返回Base64编码的签名
+    Ok(general_purpose::STANDARD.encode(&signature))
+}
 
-    let mut public_key_file = File::create(get_public_key_path())
-        .map_err(|e| LicenseError::FileError(format!("创建公钥文件失败: {}", e)))?;
-    public_key_file
-        .write_all(public_key_pem.as_bytes())
-        .map_err(|e| LicenseError::FileError(format!("写入公钥文件失败: {}", e)))?;
+// 常量时间比较两个字符串，避免通过比较耗时推断内容（长度不同时直接返回false）
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
 
-    Ok((private_key_pem, public_key_pem))
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
 }
 
-pub fn generate_license_with_machine_code(
+const UNTRUSTED_KEY_MESSAGE: &str = "许可证的签名公钥不在受信任的公钥指纹列表中";
+
+// 验证RSA签名，公钥不受信任时视为验证失败
+// 签名本身不做字符串比较：解码后交给rsa库的verify校验，时序安全性由rsa库保证
+fn verify_signature(
+    data: &[u8],
+    signature_base64: &str,
+    profile: Option<&str>,
+) -> Result<bool, LicenseError> {
+    let public_key = load_public_key(profile)?;
+    if !is_trusted_signing_key(&public_key, &policy::get_validation_policy()?, profile)? {
+        return Ok(false);
+    }
+
+    verify_with_key(&public_key, data, signature_base64, HashAlgorithm::Sha256)
+}
+
+// 使用指定公钥验证签名
+fn verify_with_key(
+    public_key: &RsaPublicKey,
+    data: &[u8],
+    signature_base64: &str,
+    hash: HashAlgorithm,
+) -> Result<bool, LicenseError> {
+    // 计算数据的哈希值
+    let (scheme, hashed) = hash_for_signing(data, hash);
+
+    // 解码Base64签名
+    let signature = general_purpose::STANDARD
+        .decode(signature_base64)
+        .map_err(|e| LicenseError::ValidationError(format!("解码签名失败: {}", e)))?;
+
+    // 验证签名
+    let result = public_key.verify(scheme, &hashed, &signature);
+
+    // 返回验证结果
+    match result {
+        Ok(_) => Ok(true),
+        Err(_) => Ok(false),
+    }
+}
+
+// 使用许可证密钥对任意数据签名（如配置文件），返回Base64编码的签名
+pub fn sign_data(data: &str, profile: Option<&str>) -> Result<String, LicenseError> {
+    generate_signature(data.as_bytes(), HashAlgorithm::Sha256, profile)
+}
+
+// 验证任意数据的分离签名
+pub fn verify_data(
+    data: &str,
+    signature_base64: &str,
+    profile: Option<&str>,
+) -> Result<bool, LicenseError> {
+    verify_signature(data.as_bytes(), signature_base64, profile)
+}
+
+// 许可证数据库文件路径
+pub(crate) fn get_license_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("licenses.json"))
+}
+
+// 心跳记录文件路径
+fn get_heartbeat_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("heartbeats.json"))
+}
+
+// 读取JSON文件，文件不存在时返回默认值
+pub(crate) fn read_json_file<T: DeserializeOwned + Default>(
+    path: &Path,
+    label: &str,
+) -> Result<T, LicenseError> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| file_error(&e, path, "读取", format!("读取{}失败: {}", label, e)))?;
+
+    serde_json::from_str(&contents)
+        .map_err(|e| LicenseError::SerializationError(format!("解析{}失败: {}", label, e)))
+}
+
+// 将数据以格式化JSON原子写入文件
+pub(crate) fn write_json_file<T: Serialize>(
+    path: &Path,
+    value: &T,
+    label: &str,
+) -> Result<(), LicenseError> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| LicenseError::SerializationError(format!("序列化{}失败: {}", label, e)))?;
+
+    write_file_atomic(path, json.as_bytes(), label)
+}
+
+// 先写入临时文件再重命名，避免写入中断导致文件损坏
+pub(crate) fn write_file_atomic(
+    path: &Path,
+    contents: &[u8],
+    label: &str,
+) -> Result<(), LicenseError> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut file = File::create(&tmp_path).map_err(|e| {
+        file_error(
+            &e,
+            &tmp_path,
+            "写入",
+            format!("创建{}临时文件失败: {}", label, e),
+        )
+    })?;
+    file.write_all(contents)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| file_error(&e, &tmp_path, "写入", format!("写入{}失败: {}", label, e)))?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)
+        .map_err(|e| file_error(&e, path, "写入", format!("替换{}文件失败: {}", label, e)))
+}
+
+// 加载心跳记录（许可证ID -> 最后一次联网时间）
+fn load_heartbeats() -> Result<BTreeMap<String, DateTime<Utc>>, LicenseError> {
+    read_json_file(&get_heartbeat_db_path()?, "心跳记录")
+}
+
+// 记录许可证的一次联网心跳，返回记录的时间
+pub fn record_heartbeat(license_id: &str) -> Result<DateTime<Utc>, LicenseError> {
+    // 读取和写入心跳记录期间持有数据库锁，避免并发心跳互相覆盖
+    let _lock = store::lock_license_db();
+    let mut heartbeats = load_heartbeats()?;
+    let now = clock::now();
+    heartbeats.insert(license_id.to_string(), now);
+    write_json_file(&get_heartbeat_db_path()?, &heartbeats, "心跳记录")?;
+
+    Ok(now)
+}
+
+// 兑换记录文件路径
+fn get_redemption_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("redemptions.json"))
+}
+
+// 加载兑换记录（许可证ID -> 兑换信息）
+fn load_redemptions() -> Result<BTreeMap<String, Redemption>, LicenseError> {
+    read_json_file(&get_redemption_db_path()?, "兑换记录")
+}
+
+// 吊销记录文件路径
+fn get_revocation_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("revocations.json"))
+}
+
+// 加载吊销记录（许可证ID -> 吊销信息）
+fn load_revocations() -> Result<BTreeMap<String, Revocation>, LicenseError> {
+    read_json_file(&get_revocation_db_path()?, "吊销记录")
+}
+
+// 首次使用绑定记录。记录是未签名的本地文件，只是辅助手段：删除或在另一台没有记录的机器上
+// 验证都能重新绑定，也只有带机器码的验证才会读取。需要可靠绑定时应使用重新签发的密钥
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FirstUseBinding {
+    pub machine_code: String,
+    pub bound_at: DateTime<Utc>,
+    // 已重新签发绑定了机器码的许可证密钥，此后本机只接受重新签发的密钥
+    #[serde(default)]
+    pub resigned: bool,
+}
+
+// 首次使用绑定记录文件路径
+fn get_binding_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("first_use_bindings.json"))
+}
+
+// 加载首次使用绑定记录（许可证ID -> 绑定信息）
+fn load_bindings() -> Result<BTreeMap<String, FirstUseBinding>, LicenseError> {
+    read_json_file(&get_binding_db_path()?, "绑定记录")
+}
+
+// 加载许可证数据库
+// 旧版结构的数据库文件在读取时自动升级
+fn load_license_db() -> Result<LicenseDatabase, LicenseError> {
+    store::current_store().load()
+}
+
+// 对许可证信息签名，返回包含签名的许可证信息
+fn sign_license_info(
+    info: LicenseInfo,
+    profile: Option<&str>,
+) -> Result<LicenseInfo, LicenseError> {
+    // 签名字段不参与签名计算，密钥标识参与签名
+    let (_, public_key) = load_or_generate_keys(profile)?;
+    // 嵌入了公钥指纹的许可证更新为签名公钥的指纹
+    let embedded_fingerprint = match info.key_fingerprint {
+        Some(_) => Some(key_fingerprint(&public_key)?),
+        None => None,
+    };
+    // 重新签名后原有的联合签名失效
+    let license_data = LicenseInfo {
+        signature: String::new(),
+        kid: Some(key_id(&public_key)?),
+        co_signatures: Vec::new(),
+        key_fingerprint: embedded_fingerprint,
+        ..info
+    };
+
+    // 生成签名
+    let signature = generate_signature(
+        &signing_payload(&license_data)?,
+        license_data.hash_algorithm,
+        profile,
+    )?;
+
+    // 更新许可证信息，包含签名
+    Ok(LicenseInfo {
+        signature,
+        ..license_data
+    })
+}
+
+// 将已签名的许可证信息序列化并编码为Base64许可证密钥
+// 许可证密钥以格式版本前缀开头，如 v2:<Base64>，没有前缀的旧密钥按版本1处理；
+// CBOR格式的许可证使用 v3 前缀
+pub(crate) fn encode_license_key(info: &LicenseInfo) -> Result<String, LicenseError> {
+    let (version, payload) = match info.payload_format {
+        PayloadFormat::Json => (
+            CURRENT_LICENSE_KEY_VERSION,
+            serde_json::to_vec(info)
+                .map_err(|e| LicenseError::SerializationError(e.to_string()))?,
+        ),
+        PayloadFormat::Cbor => (
+            CBOR_LICENSE_KEY_VERSION,
+            serde_json::to_value(info)
+                .map(|value| cbor::to_canonical_vec(&value))
+                .map_err(|e| LicenseError::SerializationError(e.to_string()))?,
+        ),
+    };
+
+    Ok(format!(
+        "v{}:{}",
+        version,
+        general_purpose::STANDARD.encode(payload)
+    ))
+}
+
+// 仅签名许可证并返回许可证密钥，不写入本地数据库（用于无状态签发）
+pub fn sign_license(info: LicenseInfo, profile: Option<&str>) -> Result<String, LicenseError> {
+    let license_with_signature = sign_license_info(info, profile)?;
+    encode_license_key(&license_with_signature)
+}
+
+// 将已注册的功能压缩为位掩码，返回剩余的未注册功能和掩码
+fn pack_features(
+    features: Vec<String>,
+    registry: &BTreeMap<String, u8>,
+) -> (Vec<String>, Option<String>) {
+    let mut mask = 0u64;
+    let mut unregistered = Vec::new();
+    for feature in features {
+        match registry.get(&feature) {
+            Some(bit) => mask |= 1 << bit,
+            None => unregistered.push(feature),
+        }
+    }
+
+    let bits = (mask != 0).then(|| format!("{:x}", mask));
+    (unregistered, bits)
+}
+
+// 将位掩码展开为功能名称，注册表中没有的位显示为 feature_bit_N
+fn expand_feature_bits(
+    info: &mut LicenseInfo,
+    registry: &BTreeMap<String, u8>,
+) -> Result<(), LicenseError> {
+    let Some(ref bits) = info.feature_bits else {
+        return Ok(());
+    };
+    let mask = u64::from_str_radix(bits, 16)
+        .map_err(|_| LicenseError::ValidationError(format!("无效的功能位掩码: {}", bits)))?;
+
+    for bit in 0..64u8 {
+        if mask & (1 << bit) == 0 {
+            continue;
+        }
+        let name = registry
+            .iter()
+            .find(|(_, b)| **b == bit)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("feature_bit_{}", bit));
+        if !info.features.contains(&name) {
+            info.features.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+// 客户字段的最大长度（字符数），避免签名数据过大
+const MAX_CUSTOMER_FIELD_LEN: usize = 256;
+
+// 去除客户字段首尾空白，并拒绝空值和超长值
+fn normalize_customer_field(value: &str, label: &str) -> Result<String, LicenseError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(LicenseError::ValidationError(format!("{}不能为空", label)));
+    }
+    if value.chars().count() > MAX_CUSTOMER_FIELD_LEN {
+        return Err(LicenseError::ValidationError(format!(
+            "{}长度不能超过{}个字符",
+            label, MAX_CUSTOMER_FIELD_LEN
+        )));
+    }
+
+    Ok(value.to_string())
+}
+
+// 单个功能名称的最大长度（字符数）
+const MAX_FEATURE_LEN: usize = 128;
+
+// 去除功能名称首尾空白，丢弃空值并按首次出现的顺序去重，拒绝超长的功能名称
+fn normalize_features(features: Vec<String>) -> Result<Vec<String>, LicenseError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(features.len());
+    for feature in features {
+        let feature = feature.trim();
+        if feature.is_empty() {
+            continue;
+        }
+        if feature.chars().count() > MAX_FEATURE_LEN {
+            return Err(LicenseError::ValidationError(format!(
+                "功能名称长度不能超过{}个字符",
+                MAX_FEATURE_LEN
+            )));
+        }
+        if !normalized.iter().any(|f| f == feature) {
+            normalized.push(feature.to_string());
+        }
+    }
+
+    Ok(normalized)
+}
+
+// 不在功能目录中的功能，配额和通配功能按基础名称比较；目录为空时不检查
+fn features_outside_catalog(
+    catalog: &settings::FeatureCatalog,
+    features: &[String],
+) -> Vec<String> {
+    if catalog.features.is_empty() {
+        return Vec::new();
+    }
+
+    features
+        .iter()
+        .filter(|feature| {
+            let base = feature_base_name(feature);
+            !catalog
+                .features
+                .iter()
+                .any(|known| feature_base_name(known) == base)
+        })
+        .cloned()
+        .collect()
+}
+
+/// 列出不在功能目录中的功能，供界面在签发前提示可能的拼写错误
+pub fn unknown_catalog_features(features: &[String]) -> Result<Vec<String>, LicenseError> {
+    Ok(features_outside_catalog(
+        &settings::get_feature_catalog()?,
+        features,
+    ))
+}
+
+// 按功能目录检查签发的功能：严格模式拒绝签发，宽松模式返回警告，由签发结果带给调用方
+fn check_feature_catalog(features: &[String]) -> Result<Vec<String>, LicenseError> {
+    let catalog = settings::get_feature_catalog()?;
+    let unknown = features_outside_catalog(&catalog, features);
+    if unknown.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let message = format!("以下功能不在功能目录中: {}", unknown.join(", "));
+    match catalog.mode {
+        settings::FeatureCatalogMode::Strict => Err(LicenseError::ValidationError(message)),
+        settings::FeatureCatalogMode::Lenient => Ok(vec![message]),
+    }
+}
+
+// 整理部分硬件绑定：标识项名称必须有效，取值为机器标识格式（32位十六进制）
+fn normalize_hardware_binding(
+    binding: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, LicenseError> {
+    let mut normalized = BTreeMap::new();
+    for (component, value) in binding {
+        let component = component.trim();
+        if !MACHINE_IDENTIFIER_COMPONENTS.contains(&component) {
+            return Err(LicenseError::ValidationError(format!(
+                "无效的硬件标识项: {}，可选: {}",
+                component,
+                MACHINE_IDENTIFIER_COMPONENTS.join(", ")
+            )));
+        }
+        let value = value.trim().to_ascii_lowercase();
+        if value.len() != MACHINE_CODE_LEN || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(LicenseError::ValidationError(format!(
+                "硬件标识项 {} 的取值格式无效",
+                component
+            )));
+        }
+        normalized.insert(component.to_string(), value);
+    }
+
+    Ok(normalized)
+}
+
+// 整理功能到期时间：按功能基础名称存储，功能必须包含在签发的功能列表中
+fn normalize_feature_expiry(
+    feature_expiry: &BTreeMap<String, DateTime<Utc>>,
+    features: &[String],
+) -> Result<BTreeMap<String, DateTime<Utc>>, LicenseError> {
+    let mut normalized = BTreeMap::new();
+    for (feature, expires) in feature_expiry {
+        let base = feature_base_name(feature);
+        if !features.iter().any(|f| feature_base_name(f) == base) {
+            return Err(LicenseError::ValidationError(format!(
+                "功能 {} 不在许可证的功能列表中，无法设置到期时间",
+                base
+            )));
+        }
+        normalized.insert(base.to_string(), *expires);
+    }
+
+    Ok(normalized)
+}
+
+// 元数据键值总长度上限（字节），避免许可证密钥过长
+const MAX_METADATA_BYTES: usize = 1024;
+
+// 校验元数据：键不能为空，总长度不能超过上限
+fn validate_metadata(metadata: &BTreeMap<String, String>) -> Result<(), LicenseError> {
+    if metadata.keys().any(|k| k.trim().is_empty()) {
+        return Err(LicenseError::ValidationError(
+            "元数据的键不能为空".to_string(),
+        ));
+    }
+    let total: usize = metadata.iter().map(|(k, v)| k.len() + v.len()).sum();
+    if total > MAX_METADATA_BYTES {
+        return Err(LicenseError::ValidationError(format!(
+            "元数据总长度不能超过{}字节",
+            MAX_METADATA_BYTES
+        )));
+    }
+
+    Ok(())
+}
+
+// 确定性许可证ID使用的命名空间
+fn license_id_namespace() -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_DNS, b"license.drilling-system")
+}
+
+// 由签发输入（客户、签发日期（按天）、到期方式、排序后的功能、机器码、签发方和全部签发选项）
+// 生成UUIDv5，同一天内以完全相同的输入签发时得到相同ID，任一参与签名的输入不同时ID不同
+fn deterministic_license_id(inputs: &serde_json::Value) -> String {
+    Uuid::new_v5(&license_id_namespace(), inputs.to_string().as_bytes()).to_string()
+}
+
+// 许可证的到期方式
+enum LicenseExpiry {
+    // 从生效时间起的天数，0表示永不过期
+    Days(u32),
+    // 指定的到期时刻
+    At(DateTime<Utc>),
+}
+
+/// 签发结果：许可证密钥，以及签发成功但需要提示的警告（如宽松模式下不在功能目录中的功能）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LicenseIssuance {
+    pub license_key: String,
+    pub warnings: Vec<String>,
+}
+
+/// 批量签发结果：规范化后的机器码到许可证密钥的映射，以及签发时的警告
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BulkLicenseIssuance {
+    pub license_keys: BTreeMap<String, String>,
+    pub warnings: Vec<String>,
+}
+
+// 构建、签名并保存许可证，返回许可证密钥和警告
+fn issue_license(
     customer_name: &str,
     customer_email: &str,
-    expiry_days: u32,
+    expiry: LicenseExpiry,
     features: Vec<String>,
-    machine_code: &str,
-) -> Result<String, LicenseError> {
-    let now = Utc::now();
-    // 处理特殊的过期时间：0表示永不过期
-    let expiry = if expiry_days == 0 {
-        // 设置一个非常远的未来日期，比如100年后
-        now + Duration::days(36500) // 约100年
+    options: &LicenseOptions,
+    machine_code: Option<&MachineCodeInput>,
+    profile: Option<&str>,
+) -> Result<LicenseIssuance, LicenseError> {
+    // 从构建到保存都持有数据库锁，签名使用的密钥和写入的数据库属于同一个数据目录
+    let _lock = store::lock_license_db();
+    let (license_with_signature, issuance) = build_license(
+        customer_name,
+        customer_email,
+        expiry,
+        features,
+        options,
+        machine_code,
+        profile,
+    )?;
+
+    // 保存到数据库
+    let existing = store::update_license_db(|db| {
+        let existing = find_issued_license(db, &license_with_signature.license_id);
+        if existing.is_none() {
+            db.licenses.push(license_with_signature.clone());
+        }
+        Ok(existing)
+    })?;
+
+    // 以相同输入重复签发（如超时后重试）时返回已有的许可证，不再通知Webhook
+    if let Some(existing) = existing {
+        return Ok(LicenseIssuance {
+            license_key: encode_license_key(&existing)?,
+            ..issuance
+        });
+    }
+
+    // 保存成功后通知Webhook（后台执行，不影响签发）
+    webhook::notify_license_issued(&license_with_signature);
+
+    Ok(issuance)
+}
+
+// 查找ID相同的已签发许可证。确定性ID相同说明同一天内以相同输入重复签发，
+// 调用方应返回已有的许可证，而不是替换记录或新增一条相同的记录
+fn find_issued_license(db: &LicenseDatabase, license_id: &str) -> Option<LicenseInfo> {
+    db.licenses
+        .iter()
+        .find(|l| l.license_id == license_id)
+        .cloned()
+}
+
+// 构建并签名许可证，返回签名后的许可证信息和签发结果，不保存到数据库
+fn build_license(
+    customer_name: &str,
+    customer_email: &str,
+    expiry: LicenseExpiry,
+    features: Vec<String>,
+    options: &LicenseOptions,
+    machine_code: Option<&MachineCodeInput>,
+    profile: Option<&str>,
+) -> Result<(LicenseInfo, LicenseIssuance), LicenseError> {
+    let customer_name = normalize_customer_field(customer_name, "客户名称")?;
+    let customer_email = normalize_customer_field(customer_email, "客户邮箱")?;
+    let features = normalize_features(features)?;
+    let feature_expiry = normalize_feature_expiry(&options.feature_expiry, &features)?;
+    let hardware_binding = normalize_hardware_binding(&options.hardware_binding)?;
+    if !hardware_binding.is_empty() && options.bind_on_first_use {
+        return Err(LicenseError::ValidationError(
+            "部分硬件绑定的许可证不能同时使用首次使用绑定".to_string(),
+        ));
+    }
+    validate_metadata(&options.metadata)?;
+    let issuer = match options.issuer.as_deref() {
+        Some(issuer) => issuer.to_string(),
+        None => settings::load_settings()?
+            .default_issuer
+            .unwrap_or_default(),
+    };
+    let issuer = match issuer.trim() {
+        "" => String::new(),
+        issuer => normalize_customer_field(issuer, "签发方")?,
+    };
+    // 已指定机器码的许可证无需首次使用绑定
+    let bind_on_first_use = options.bind_on_first_use && machine_code.is_none();
+    let warnings = check_feature_catalog(&features)?;
+    let limits = settings::get_license_limits()?;
+    if features.len() > limits.max_features {
+        return Err(LicenseError::ValidationError(format!(
+            "功能数量不能超过{}个",
+            limits.max_features
+        )));
+    }
+
+    let now = clock::now();
+    // 推迟生效的许可证从生效时间开始计算有效期
+    let not_before = match options.start_offset_days {
+        Some(days) if days > 0 => Some(
+            now.checked_add_signed(Duration::days(days as i64))
+                .ok_or_else(|| LicenseError::ValidationError("生效时间超出范围".to_string()))?,
+        ),
+        _ => None,
+    };
+    let start = not_before.unwrap_or(now);
+    // 确定性ID使用到期方式本身，而不是按签发时刻换算后的到期时间
+    let expiry_input = match &expiry {
+        LicenseExpiry::Days(days) => serde_json::json!({ "days": days }),
+        LicenseExpiry::At(expiry) => serde_json::json!({ "at": expiry }),
+    };
+    let expiry = match expiry {
+        LicenseExpiry::Days(expiry_days) => {
+            // 处理特殊的过期时间：0表示永不过期
+            let expiry_duration = if expiry_days == 0 {
+                // 设置一个非常远的未来日期，比如100年后
+                Duration::days(PERPETUAL_DAYS)
+            } else {
+                Duration::days(expiry_days as i64)
+            };
+            // 超出日期范围时返回错误，避免溢出导致程序崩溃
+            start
+                .checked_add_signed(expiry_duration)
+                .ok_or_else(|| LicenseError::ValidationError("有效期天数超出范围".to_string()))?
+        }
+        LicenseExpiry::At(expiry) if expiry <= start => {
+            return Err(LicenseError::ValidationError(
+                "到期时间必须晚于生效时间".to_string(),
+            ))
+        }
+        LicenseExpiry::At(expiry) => expiry,
+    };
+
+    let license_id = if options.deterministic_id {
+        let mut sorted_features = features.clone();
+        sorted_features.sort_unstable();
+        let options_input = serde_json::to_value(options)
+            .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+        deterministic_license_id(&serde_json::json!({
+            "customer_name": customer_name,
+            "customer_email": customer_email.to_lowercase(),
+            "issue_date": now.date_naive(),
+            "expiry": expiry_input,
+            "features": sorted_features,
+            "machine_code": machine_code,
+            "issuer": issuer,
+            "options": options_input,
+        }))
     } else {
-        now + Duration::days(expiry_days as i64)
+        Uuid::new_v4().to_string()
     };
 
-    let license_id = Uuid::new_v4().to_string();
+    let (features, feature_bits) = if options.pack_features {
+        pack_features(features, &settings::get_feature_registry()?)
+    } else {
+        (features, None)
+    };
 
-    // 创建不包含签名的许可证信息，包含机器码
+    // 创建不包含签名的许可证信息
     let license_data = LicenseInfo {
         license_id,
-        customer_name: customer_name.to_string(),
-        customer_email: customer_email.to_string(),
+        customer_name,
+        customer_email,
         issue_date: now,
         expiry_date: expiry,
         features,
-        signature: String::new(),                     // 暂时为空
-        machine_code: Some(machine_code.to_string()), // 添加机器码
+        signature: String::new(), // 暂时为空
+        machine_code: machine_code.map(|input| input.code.clone()), // 为空表示无机器码限制
+        tier: options.tier,
+        heartbeat_interval_days: options.heartbeat_interval_days,
+        single_use: options.single_use,
+        // 记录客户计算机器码时使用的算法版本；首次使用绑定在本机按当前版本计算
+        machine_code_version: match machine_code {
+            Some(input) => Some(input.version),
+            None => bind_on_first_use.then_some(CURRENT_MACHINE_CODE_VERSION),
+        },
+        not_before,
+        feature_bits,
+        metadata: options.metadata.clone(),
+        kid: None,
+        issuer,
+        bind_on_first_use,
+        hash_algorithm: options.hash_algorithm,
+        feature_expiry,
+        hardware_binding,
+        required_signatures: options.required_signatures,
+        co_signatures: Vec::new(),
+        payload_format: options.payload_format,
+        // 签名时填入签名公钥的指纹
+        key_fingerprint: options.embed_key_fingerprint.then(String::new),
     };
 
-    // 序列化为JSON
-    let json_data = serde_json::to_string(&license_data)
-        .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+    // 签名许可证信息
+    let license_with_signature = sign_license_info(license_data, profile)?;
+    let license_key = encode_license_key(&license_with_signature)?;
+    if license_key.len() > limits.max_key_bytes {
+        return Err(LicenseError::ValidationError(format!(
+            "许可证密钥长度 {} 字节超过上限 {} 字节",
+            license_key.len(),
+            limits.max_key_bytes
+        )));
+    }
 
-    // 生成签名
-    let signature = generate_signature(&json_data)?;
+    Ok((
+        license_with_signature,
+        LicenseIssuance {
+            license_key,
+            warnings,
+        },
+    ))
+}
 
-    // 更新许可证信息，包含签名
-    let license_with_signature = LicenseInfo {
-        signature,
-        ..license_data
-    };
+pub fn generate_license(
+    customer_name: &str,
+    customer_email: &str,
+    expiry_days: u32,
+    features: Vec<String>,
+    options: &LicenseOptions,
+    profile: Option<&str>,
+) -> Result<LicenseIssuance, LicenseError> {
+    issue_license(
+        customer_name,
+        customer_email,
+        LicenseExpiry::Days(expiry_days),
+        features,
+        options,
+        None,
+        profile,
+    )
+}
 
-    // 保存到数据库
-    let mut db = load_license_db()?;
-    db.licenses.push(license_with_signature.clone());
-    save_license_db(&db)?;
+/// 签发到指定日期结束时到期的许可证：到期时间为该日期在指定IANA时区（未指定时为UTC）的
+/// 23:59:59，换算为UTC保存。夏令时在午夜切换的时区按 TimeZone::end_of_day 的规则处理
+pub fn generate_license_until(
+    customer_name: &str,
+    customer_email: &str,
+    expiry_date: NaiveDate,
+    timezone: Option<&str>,
+    features: Vec<String>,
+    options: &LicenseOptions,
+    profile: Option<&str>,
+) -> Result<LicenseIssuance, LicenseError> {
+    let zone = TimeZone::load(timezone.unwrap_or("UTC"))?;
+    issue_license(
+        customer_name,
+        customer_email,
+        LicenseExpiry::At(zone.end_of_day(expiry_date)?),
+        features,
+        options,
+        None,
+        profile,
+    )
+}
 
-    // 序列化并编码为Base64
-    let final_json = serde_json::to_string(&license_with_signature)
-        .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+// 永久许可证的有效期天数（约100年）
+const PERPETUAL_DAYS: i64 = 36500;
 
-    Ok(general_purpose::STANDARD.encode(final_json))
+// 永久许可证：过期时间戳不大于0，或有效期达到永久许可证的天数
+pub(crate) fn is_perpetual(info: &LicenseInfo) -> bool {
+    let start = info.not_before.unwrap_or(info.issue_date);
+    info.expiry_date.timestamp() <= 0 || info.expiry_date - start >= Duration::days(PERPETUAL_DAYS)
 }
 
-// 添加验证许可证并检查机器码的函数
-pub fn validate_license_with_machine_code(
-    license_key: &str,
-    machine_code: &str,
-) -> Result<LicenseValidationResult, LicenseError> {
-    let result = validate_license(license_key)?;
+// 当前签发的许可证密钥格式版本
+pub(crate) const CURRENT_LICENSE_KEY_VERSION: u32 = 2;
 
-    // 如果许可证本身无效，直接返回结果
-    if !result.is_valid || result.info.is_none() {
-        return Ok(result);
-    }
+// CBOR载荷的许可证密钥格式版本
+pub(crate) const CBOR_LICENSE_KEY_VERSION: u32 = 3;
 
-    let license_info = result.info.unwrap();
+// 解码许可证密钥。从邮件中复制的密钥常带有首尾空白或按固定列宽换行，解码前去除所有空白字符
+pub(crate) fn decode_license_key(license_key: &str) -> Result<LicenseInfo, LicenseError> {
+    let compact: String = license_key.chars().filter(|c| !c.is_whitespace()).collect();
 
-    // 检查机器码是否匹配
-    if let Some(ref license_machine_code) = license_info.machine_code {
-        if license_machine_code != machine_code {
-            return Ok(LicenseValidationResult {
-                is_valid: false,
-                info: Some(license_info),
-                message: "许可证与当前机器不匹配".to_string(),
-            });
+    // Base64字符集不含冒号，有冒号即表示带版本前缀
+    let (version, payload) = match compact.split_once(':') {
+        Some((prefix, payload)) => {
+            let version = prefix
+                .strip_prefix('v')
+                .and_then(|v| v.parse::<u32>().ok())
+                .ok_or_else(|| {
+                    LicenseError::ValidationError(format!(
+                        "许可证格式无效: 无法识别的版本前缀 {}",
+                        prefix
+                    ))
+                })?;
+            (version, payload)
         }
+        None => (1, compact.as_str()),
+    };
+
+    match version {
+        // 版本1和版本2的载荷相同，都是Base64编码的JSON
+        1 | 2 => decode_json_payload(payload),
+        // 版本3为Base64编码的确定性CBOR
+        CBOR_LICENSE_KEY_VERSION => decode_cbor_payload(payload),
+        _ => Err(LicenseError::ValidationError(format!(
+            "不支持的许可证格式版本: v{}",
+            version
+        ))),
     }
+}
 
-    // 所有检查都通过
-    Ok(LicenseValidationResult {
-        is_valid: true,
-        info: Some(license_info),
-        message: "许可证有效且与当前机器匹配".to_string(),
-    })
+// 解码Base64编码的JSON许可证载荷
+fn decode_json_payload(payload: &str) -> Result<LicenseInfo, LicenseError> {
+    // 解析JSON
+    serde_json::from_slice(&decode_payload_base64(payload)?)
+        .map_err(|e| LicenseError::ValidationError(format!("许可证格式无效: {}", e)))
 }
 
-// 删除许可证
-pub fn delete_license(license_id: &str) -> Result<(), LicenseError> {
-    let mut db = load_license_db()?;
+// 解码Base64编码的CBOR许可证载荷，载荷必须声明为CBOR格式
+fn decode_cbor_payload(payload: &str) -> Result<LicenseInfo, LicenseError> {
+    let value = cbor::from_slice(&decode_payload_base64(payload)?)
+        .map_err(|e| LicenseError::ValidationError(format!("许可证格式无效: {}", e)))?;
+    let info: LicenseInfo = serde_json::from_value(value)
+        .map_err(|e| LicenseError::ValidationError(format!("许可证格式无效: {}", e)))?;
 
-    // 查找要删除的许可证索引
-    let index = db
-        .licenses
-        .iter()
-        .position(|license| license.license_id == license_id);
-
-    match index {
-        Some(idx) => {
-            // 删除许可证
-            db.licenses.remove(idx);
-            // 保存更新后的数据库
-            save_license_db(&db)?;
-            Ok(())
-        }
-        None => Err(LicenseError::ValidationError("许可证不存在".to_string())),
+    if info.payload_format != PayloadFormat::Cbor {
+        return Err(LicenseError::ValidationError(
+            "许可证格式无效: v3 许可证的载荷格式不是CBOR".to_string(),
+        ));
+    }
+    Ok(info)
+}
+
+// 解码许可证载荷的Base64
+// 同时接受URL安全字符集和省略填充的写法，它们与标准编码表示同一许可证
+fn decode_payload_base64(payload: &str) -> Result<Vec<u8>, LicenseError> {
+    let normalized: String = payload
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+
+    // 解码Base64
+    general_purpose::STANDARD_NO_PAD
+        .decode(normalized.as_bytes())
+        .map_err(|e| LicenseError::ValidationError(format!("许可证格式无效: {}", e)))
+}
+
+// 验证许可证签名并以规范形式（当前版本前缀 + 标准Base64）重新编码
+// 同一许可证的不同写法得到相同结果，便于存储和比较；只检查签名，不检查有效期
+pub fn canonicalize_license(
+    license_key: &str,
+    profile: Option<&str>,
+) -> Result<String, LicenseError> {
+    let info = decode_license_key(license_key)?;
+
+    match match_license_signing_key(&info, &policy::get_validation_policy()?, profile)? {
+        SigningKeyMatch::Current(_) => encode_license_key(&info),
+        SigningKeyMatch::Retired(_) => Err(LicenseError::ValidationError(
+            "许可证由已停用的密钥签名，不再受信任".to_string(),
+        )),
+        SigningKeyMatch::Untrusted(_) => Err(LicenseError::ValidationError(
+            UNTRUSTED_KEY_MESSAGE.to_string(),
+        )),
+        SigningKeyMatch::WrongKey { expected, current } => Err(LicenseError::ValidationError(
+            wrong_key_message(&expected, &current),
+        )),
+        SigningKeyMatch::Unknown => {
+            Err(LicenseError::ValidationError("许可证签名无效".to_string()))
+        }
+    }
+}
+
+// 许可证密码学参数的默认最低要求（RSA密钥位数）
+const DEFAULT_MIN_RSA_BITS: usize = 2048;
+
+// 许可证的签名参数，仅解析不验证签名，不需要受信任的公钥
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicenseCryptoInfo {
+    pub algorithm: String,
+    pub hash: String,
+    // RSA签名长度与模数长度相同，据此推断密钥位数
+    pub key_bits: Option<usize>,
+    pub kid: Option<String>,
+    pub meets_minimum: bool,
+    pub message: String,
+}
+
+// 检查许可证使用的签名算法、哈希算法和密钥位数，并判断是否满足最低要求
+pub fn inspect_license_crypto(
+    license_key: &str,
+    min_rsa_bits: Option<usize>,
+) -> Result<LicenseCryptoInfo, LicenseError> {
+    let info = decode_license_key(license_key)?;
+    let min_bits = min_rsa_bits.unwrap_or(DEFAULT_MIN_RSA_BITS);
+
+    let key_bits = general_purpose::STANDARD
+        .decode(info.signature.trim())
+        .ok()
+        .filter(|signature| !signature.is_empty())
+        .map(|signature| signature.len() * 8);
+
+    let (meets_minimum, message) = match key_bits {
+        Some(bits) if bits >= min_bits => (true, "签名参数符合要求".to_string()),
+        Some(bits) => (
+            false,
+            format!("RSA密钥位数 {} 低于最低要求 {}", bits, min_bits),
+        ),
+        None => (false, "无法解析签名".to_string()),
+    };
+
+    Ok(LicenseCryptoInfo {
+        algorithm: "RSA-PKCS1-v1_5".to_string(),
+        hash: info.hash_algorithm.name().to_string(),
+        key_bits,
+        kid: info.kid,
+        meets_minimum,
+        message,
+    })
+}
+
+// 检查许可证在指定时间是否已过期
+fn is_license_expired(info: &LicenseInfo, now: DateTime<Utc>) -> bool {
+    // 检查是否设置了特殊的过期时间（0表示永不过期）
+    match info.expiry_date.timestamp() {
+        // 如果时间戳为0或负数，表示永不过期
+        t if t <= 0 => false,
+        // 否则正常检查是否过期
+        _ => info.expiry_date < now,
+    }
+}
+
+pub fn validate_license(
+    license_key: &str,
+    profile: Option<&str>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    validate_license_inner(license_key, profile, true)
+}
+
+// 验证许可证；兑换流程本身不检查兑换状态
+fn validate_license_inner(
+    license_key: &str,
+    profile: Option<&str>,
+    check_redemption: bool,
+) -> Result<LicenseValidationResult, LicenseError> {
+    let license_data = decode_license_key(license_key)?;
+    // 每次验证只读取一次验证策略
+    let policy = policy::get_validation_policy()?;
+
+    // 验证签名
+    let (key_match, co_signature_error) = check_signatures_cached(&license_data, &policy, profile)?;
+    let kid = match key_match {
+        SigningKeyMatch::Current(kid) => kid,
+        SigningKeyMatch::Retired(kid) => {
+            return Ok(LicenseValidationResult::new(
+                false,
+                None,
+                "许可证由已停用的密钥签名，不再受信任",
+            )
+            .with_kid(Some(kid)));
+        }
+        SigningKeyMatch::Untrusted(kid) => {
+            return Ok(
+                LicenseValidationResult::new(false, None, UNTRUSTED_KEY_MESSAGE)
+                    .with_kid(Some(kid)),
+            );
+        }
+        SigningKeyMatch::WrongKey { expected, current } => {
+            let message = wrong_key_message(&expected, &current);
+            return Ok(LicenseValidationResult::new(false, None, &message)
+                .with_kid(Some(expected.chars().take(16).collect())));
+        }
+        SigningKeyMatch::Unknown => {
+            return Ok(LicenseValidationResult::new(false, None, "许可证签名无效")
+                .with_kid(Some("unknown".to_string())));
+        }
+    };
+
+    // 签名已验证通过，签名长度即为公钥模数的长度
+    let min_key_bits = policy.min_key_bits;
+    let key_bits = general_purpose::STANDARD
+        .decode(license_data.signature.trim())
+        .map_or(0, |signature| signature.len() * 8);
+    if key_bits < min_key_bits {
+        let message = format!(
+            "许可证的签名密钥为{}位，低于验证策略要求的{}位",
+            key_bits, min_key_bits
+        );
+        return Ok(
+            LicenseValidationResult::new(false, Some(license_data), &message).with_kid(Some(kid)),
+        );
+    }
+
+    if let Some(message) = co_signature_error {
+        return Ok(
+            LicenseValidationResult::new(false, Some(license_data), &message).with_kid(Some(kid)),
+        );
+    }
+
+    Ok(check_license_terms(license_data, &policy, check_redemption)?.with_kid(Some(kid)))
+}
+
+// 签名校验结果的缓存有效期（秒）
+const VALIDATION_CACHE_TTL_SECS: i64 = 60;
+
+// 缓存的最大条目数，超出时清空重来
+const VALIDATION_CACHE_MAX_ENTRIES: usize = 256;
+
+// 缓存的签名校验结果，有效期、生效时间等条款每次都重新检查
+struct CachedSignatureCheck {
+    key_match: SigningKeyMatch,
+    co_signature_error: Option<String>,
+    checked_at: DateTime<Utc>,
+}
+
+// 签名校验结果缓存，键为配置文件名称和解码后许可证内容的SHA-256哈希，
+// 换行或空白不同的同一密钥命中同一条目；只保存在内存中
+static VALIDATION_CACHE: Mutex<BTreeMap<(String, String), CachedSignatureCheck>> =
+    Mutex::new(BTreeMap::new());
+
+/// 清空签名校验结果缓存，密钥、受信任指纹、数据目录变化或吊销许可证时自动调用
+pub fn clear_validation_cache() {
+    VALIDATION_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clear();
+}
+
+// 校验主签名和联合签名，有效期内复用上次的结果；时钟回拨到缓存时间之前时重新校验
+fn check_signatures_cached(
+    info: &LicenseInfo,
+    policy: &ValidationPolicy,
+    profile: Option<&str>,
+) -> Result<(SigningKeyMatch, Option<String>), LicenseError> {
+    let payload =
+        serde_json::to_vec(info).map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+    let cache_key = (
+        profile.unwrap_or(DEFAULT_PROFILE).to_string(),
+        hex::encode(Sha256::digest(&payload)),
+    );
+    let now = clock::now();
+
+    let mut cache = VALIDATION_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(cached) = cache.get(&cache_key) {
+        let age = now - cached.checked_at;
+        if age >= Duration::zero() && age < Duration::seconds(VALIDATION_CACHE_TTL_SECS) {
+            return Ok((cached.key_match.clone(), cached.co_signature_error.clone()));
+        }
+        cache.remove(&cache_key);
+    }
+    drop(cache);
+
+    let key_match = match_license_signing_key(info, policy, profile)?;
+    let co_signature_error = match &key_match {
+        SigningKeyMatch::Current(kid) => check_co_signatures(info, kid, policy, profile)?,
+        _ => None,
+    };
+
+    let mut cache = VALIDATION_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if cache.len() >= VALIDATION_CACHE_MAX_ENTRIES {
+        cache.clear();
+    }
+    cache.insert(
+        cache_key,
+        CachedSignatureCheck {
+            key_match: key_match.clone(),
+            co_signature_error: co_signature_error.clone(),
+            checked_at: now,
+        },
+    );
+    Ok((key_match, co_signature_error))
+}
+
+// 检查联合签名：有效签名数（含主签名）须达到许可证要求的数量，要求不超过1时不检查联合签名。
+// 联合签名须由已安装的联合签名公钥生成，每个密钥只计一次，返回不满足要求时的原因
+fn check_co_signatures(
+    info: &LicenseInfo,
+    primary_kid: &str,
+    policy: &ValidationPolicy,
+    profile: Option<&str>,
+) -> Result<Option<String>, LicenseError> {
+    let required = info.required_signatures as usize;
+    if required <= 1 {
+        return Ok(None);
+    }
+
+    let payload = signing_payload(info)?;
+    let cosigners = load_cosigner_public_keys(profile)?;
+    let mut verified_kids: Vec<&str> = Vec::new();
+    for entry in &info.co_signatures {
+        if entry.kid == primary_kid || verified_kids.contains(&entry.kid.as_str()) {
+            continue;
+        }
+        let Some((_, key)) = cosigners.iter().find(|(kid, _)| *kid == entry.kid) else {
+            continue;
+        };
+        if is_trusted_key(key, policy)?
+            && verify_with_key(key, &payload, &entry.signature, info.hash_algorithm)?
+        {
+            verified_kids.push(&entry.kid);
+        }
+    }
+
+    let verified = verified_kids.len() + 1;
+    if verified < required {
+        return Ok(Some(format!(
+            "许可证需要{}个有效签名，当前只有{}个",
+            required, verified
+        )));
+    }
+    Ok(None)
+}
+
+/// 使用指定配置文件的私钥为许可证添加联合签名，返回新的许可证密钥。
+/// 不检查主签名，签名密钥不能与主签名或已有的联合签名相同
+pub fn add_signature(license_key: &str, profile: Option<&str>) -> Result<String, LicenseError> {
+    if !has_signing_capability(profile) {
+        return Err(LicenseError::ValidationError(
+            "该配置文件没有私钥，无法添加签名".to_string(),
+        ));
+    }
+
+    let _lock = store::lock_license_db();
+    let mut info = decode_license_key(license_key)?;
+    let (_, public_key) = load_or_generate_keys(profile)?;
+    let kid = key_id(&public_key)?;
+    if info.kid.as_deref() == Some(kid.as_str())
+        || info.co_signatures.iter().any(|entry| entry.kid == kid)
+    {
+        return Err(LicenseError::ValidationError(
+            "该密钥已经签过此许可证".to_string(),
+        ));
+    }
+
+    let signature = generate_signature(&signing_payload(&info)?, info.hash_algorithm, profile)?;
+    info.co_signatures.push(SignatureEntry { kid, signature });
+
+    // 数据库中有该许可证时同步保存联合签名
+    store::update_license_db(|db| {
+        if let Some(existing) = db
+            .licenses
+            .iter_mut()
+            .find(|l| l.license_id == info.license_id && l.signature == info.signature)
+        {
+            existing.co_signatures = info.co_signatures.clone();
+        }
+        Ok(())
+    })?;
+
+    encode_license_key(&info)
+}
+
+/// 安装联合签名方的公钥（PEM），返回密钥标识
+pub fn install_cosigner_key(
+    public_key_pem: &str,
+    profile: Option<&str>,
+) -> Result<String, LicenseError> {
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem.trim())
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析公钥: {}", e)))?;
+    let kid = key_id(&public_key)?;
+
+    let dir = get_cosigner_keys_dir(profile)?;
+    fs::create_dir_all(&dir).map_err(|e| {
+        file_error(
+            &e,
+            &dir,
+            "创建目录",
+            format!("创建联合签名公钥目录失败: {}", e),
+        )
+    })?;
+    let pem = public_key
+        .to_public_key_pem(pkcs8::LineEnding::LF)
+        .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
+    write_key_file(&dir.join(format!("{}.pem", kid)), &pem, "联合签名公钥")?;
+
+    Ok(kid)
+}
+
+// 签发时间允许超前当前时间的秒数，容忍签发方与本机之间的少量时钟偏差
+pub(crate) const ISSUE_DATE_TOLERANCE_SECS: i64 = 300;
+
+// 签发时间是否超出容差地晚于当前时间
+pub(crate) fn is_issued_in_future(info: &LicenseInfo, now: DateTime<Utc>) -> bool {
+    info.issue_date - now > Duration::seconds(ISSUE_DATE_TOLERANCE_SECS)
+}
+
+// 签名验证通过后检查签发时间、生效时间、有效期、心跳和兑换状态
+pub(crate) fn check_license_terms(
+    license_data: LicenseInfo,
+    policy: &ValidationPolicy,
+    check_redemption: bool,
+) -> Result<LicenseValidationResult, LicenseError> {
+    // 签名验证通过后再展开压缩的功能位
+    let mut license_data = license_data;
+    if license_data.feature_bits.is_some() {
+        expand_feature_bits(&mut license_data, &settings::get_feature_registry()?)?;
+    }
+
+    // 已吊销的许可证不再有效
+    if load_revocations()?.contains_key(&license_data.license_id) {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_data),
+            "许可证已被吊销",
+        ));
+    }
+
+    // 系统时钟早于可信时间锚点时以锚点为准，防止回拨时钟绕过过期检查
+    let now = time_anchor::trusted_now();
+    if policy.require_trusted_clock && clock::now() < now {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_data),
+            "系统时钟早于可信时间，请校正本机时钟后重试",
+        ));
+    }
+
+    // 签发时间在未来说明签发方时钟错误或许可证被伪造
+    if is_issued_in_future(&license_data, now) {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_data),
+            "许可证签发时间晚于当前时间，请检查签发方或本机的时钟",
+        ));
+    }
+
+    // 检查是否已到生效时间
+    if license_data
+        .not_before
+        .is_some_and(|not_before| now < not_before)
+    {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_data),
+            "许可证尚未生效",
+        ));
+    }
+
+    // 验证策略允许宽限期时，过期不超过宽限天数的许可证仍然有效
+    let mut in_grace_period = false;
+    if is_license_expired(&license_data, now) {
+        let grace_end = license_data
+            .expiry_date
+            .checked_add_signed(Duration::days(policy.grace_period_days as i64));
+        if policy.grace_period_days == 0 || grace_end.is_none_or(|end| end < now) {
+            return Ok(LicenseValidationResult::new(
+                false,
+                Some(license_data),
+                "许可证已过期",
+            ));
+        }
+        in_grace_period = true;
+    }
+
+    // 检查订阅许可证的联网心跳，尚未联网时以生效时间（未设置时为签发时间）为起点
+    if let Some(interval_days) = license_data.heartbeat_interval_days {
+        let last_contact = load_heartbeats()?
+            .get(&license_data.license_id)
+            .copied()
+            .unwrap_or(license_data.not_before.unwrap_or(license_data.issue_date));
+
+        if now - last_contact > Duration::days(interval_days as i64) {
+            let message = format!(
+                "许可证需要定期联网验证，距上次联网已超过{}天",
+                interval_days
+            );
+            return Ok(LicenseValidationResult::new(
+                false,
+                Some(license_data),
+                &message,
+            ));
+        }
+    }
+
+    // 一次性许可证必须先兑换
+    if check_redemption
+        && license_data.single_use
+        && !load_redemptions()?.contains_key(&license_data.license_id)
+    {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_data),
+            "一次性许可证尚未兑换",
+        ));
+    }
+
+    // 有效许可证，单独到期的功能另行列出
+    let expired_features = expired_features(&license_data, now);
+    let message = if in_grace_period {
+        "许可证已过期，当前处于宽限期，请尽快续期"
+    } else {
+        "许可证有效"
+    };
+    Ok(LicenseValidationResult {
+        expired_features,
+        in_grace_period,
+        ..LicenseValidationResult::new(true, Some(license_data), message)
+    })
+}
+
+// 已过单独到期时间的功能
+fn expired_features(info: &LicenseInfo, now: DateTime<Utc>) -> Vec<String> {
+    info.feature_expiry
+        .iter()
+        .filter(|(_, expires)| **expires <= now)
+        .map(|(feature, _)| feature.clone())
+        .collect()
+}
+
+// 许可证是否包含该功能且功能未单独到期（不检查许可证整体的有效性）
+pub(crate) fn is_feature_active(info: &LicenseInfo, feature: &str, now: DateTime<Utc>) -> bool {
+    let base = feature_base_name(feature);
+    let included = info.features.iter().any(|f| feature_base_name(f) == base);
+    included
+        && info
+            .feature_expiry
+            .get(base)
+            .is_none_or(|expires| now < *expires)
+}
+
+// 功能可用状态
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureStatus {
+    Available,
+    // 许可证不包含该功能
+    NotLicensed,
+    // 功能已单独到期
+    Expired,
+    // 许可证包含该功能，但已被运行时的停用列表关闭
+    DisabledByPolicy,
+}
+
+// 运行时停用的功能（按基础名称），未设置时读取 DRILLING_DISABLED_FEATURES 环境变量（逗号分隔）
+static DISABLED_FEATURES: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+// 设置运行时停用的功能，用于分阶段发布时临时关闭功能，无需重新签发许可证
+pub fn set_disabled_features(features: Vec<String>) {
+    let features = features
+        .iter()
+        .map(|f| feature_base_name(f).to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+    *DISABLED_FEATURES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(features);
+}
+
+// 获取运行时停用的功能
+pub fn get_disabled_features() -> Vec<String> {
+    DISABLED_FEATURES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get_or_insert_with(|| {
+            std::env::var("DRILLING_DISABLED_FEATURES")
+                .unwrap_or_default()
+                .split(',')
+                .map(|f| feature_base_name(f).to_string())
+                .filter(|f| !f.is_empty())
+                .collect()
+        })
+        .clone()
+}
+
+// 验证许可证并检查指定功能是否可用：许可证有效、包含该功能、功能未单独到期且未被停用
+pub fn validate_license_for_feature(
+    license_key: &str,
+    feature: &str,
+    profile: Option<&str>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    let result = validate_license(license_key, profile)?;
+    let Some(info) = result.info.as_ref().filter(|_| result.is_valid) else {
+        return Ok(result);
+    };
+
+    let base = feature_base_name(feature);
+    let (status, message) = if !info.features.iter().any(|f| feature_base_name(f) == base) {
+        (
+            FeatureStatus::NotLicensed,
+            format!("许可证不包含功能 {}", base),
+        )
+    } else if !is_feature_active(info, feature, clock::now()) {
+        (
+            FeatureStatus::Expired,
+            format!(
+                "功能 {} 已于 {} 过期",
+                base,
+                info.feature_expiry[base].format("%Y-%m-%d")
+            ),
+        )
+    } else if get_disabled_features().iter().any(|f| f == base) {
+        (
+            FeatureStatus::DisabledByPolicy,
+            format!("功能 {} 已被停用", base),
+        )
+    } else {
+        return Ok(LicenseValidationResult {
+            feature_status: Some(FeatureStatus::Available),
+            ..result
+        });
+    };
+
+    Ok(LicenseValidationResult {
+        is_valid: false,
+        message,
+        feature_status: Some(status),
+        ..result
+    })
+}
+
+// 获取所有许可证
+pub fn get_all_licenses() -> Result<Vec<LicenseInfo>, LicenseError> {
+    let db = load_license_db()?;
+    Ok(db.licenses)
+}
+
+// 许可证与机器的绑定方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MachineBindingSource {
+    // 签发时写入了机器码
+    Issued,
+    // 首次使用时绑定
+    FirstUse,
+    // 一次性许可证在该机器上兑换
+    Redeemed,
+}
+
+// 绑定到某台机器的许可证
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MachineBoundLicense {
+    pub license: LicenseInfo,
+    pub binding: MachineBindingSource,
+}
+
+/// 列出数据库中绑定到指定机器码的许可证：签发时绑定、首次使用绑定和一次性兑换的记录都会列出，
+/// 每个许可证只列出一次（按上述顺序取第一种绑定方式）
+pub fn get_licenses_for_machine(
+    machine_code: &str,
+) -> Result<Vec<MachineBoundLicense>, LicenseError> {
+    let machine_code = normalize_machine_code(machine_code)?;
+    let bindings = load_bindings()?;
+    let redemptions = load_redemptions()?;
+    let matches = |code: &str| code.trim().eq_ignore_ascii_case(&machine_code);
+
+    Ok(load_license_db()?
+        .licenses
+        .into_iter()
+        .filter_map(|license| {
+            let binding = if license.machine_code.as_deref().is_some_and(matches) {
+                MachineBindingSource::Issued
+            } else if bindings
+                .get(&license.license_id)
+                .is_some_and(|binding| matches(&binding.machine_code))
+            {
+                MachineBindingSource::FirstUse
+            } else if redemptions
+                .get(&license.license_id)
+                .is_some_and(|redemption| matches(&redemption.machine_code))
+            {
+                MachineBindingSource::Redeemed
+            } else {
+                return None;
+            };
+            Some(MachineBoundLicense { license, binding })
+        })
+        .collect())
+}
+
+// 按ID获取单个许可证，不存在时返回 None
+pub fn get_license_by_id(license_id: &str) -> Result<Option<LicenseInfo>, LicenseError> {
+    Ok(load_license_db()?
+        .licenses
+        .into_iter()
+        .find(|license| license.license_id == license_id))
+}
+
+// 保留首个字符，其余替换为 ***
+fn mask_text(value: &str) -> String {
+    match value.chars().next() {
+        Some(first) => format!("{}***", first),
+        None => String::new(),
+    }
+}
+
+// 遮盖邮箱用户名部分，保留域名，如 j***@example.com
+fn mask_email(email: &str) -> String {
+    match email.rsplit_once('@') {
+        Some((user, domain)) => format!("{}@{}", mask_text(user), domain),
+        None => mask_text(email),
+    }
+}
+
+// 获取遮盖了客户信息的许可证列表（用于屏幕共享等场景），签名一并清除，
+// 遮盖在后端完成，原始数据不会传到前端
+pub fn get_licenses_redacted(redact_name: bool) -> Result<Vec<LicenseInfo>, LicenseError> {
+    Ok(get_all_licenses()?
+        .into_iter()
+        .map(|license| LicenseInfo {
+            customer_email: mask_email(&license.customer_email),
+            customer_name: if redact_name {
+                mask_text(&license.customer_name)
+            } else {
+                license.customer_name
+            },
+            signature: String::new(),
+            co_signatures: Vec::new(),
+            ..license
+        })
+        .collect())
+}
+
+// 统计许可证数量，供管理面板展示
+pub fn get_license_stats() -> Result<LicenseStats, LicenseError> {
+    let db = load_license_db()?;
+    let revocations = load_revocations()?;
+    let now = clock::now();
+    let soon = now + Duration::days(30);
+
+    let mut stats = LicenseStats {
+        total: db.licenses.len(),
+        ..Default::default()
+    };
+    for license in &db.licenses {
+        if revocations.contains_key(&license.license_id) {
+            stats.revoked += 1;
+        } else if is_license_expired(license, now) {
+            stats.expired += 1;
+        } else {
+            stats.active += 1;
+            if is_license_expired(license, soon) {
+                stats.expiring_soon += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+// 获取未来指定天数内到期的有效许可证，按到期时间升序排列，不包含永久、已过期和已吊销的许可证
+pub fn get_expiring_licenses(days: u32) -> Result<Vec<LicenseInfo>, LicenseError> {
+    let now = clock::now();
+    let deadline = now
+        .checked_add_signed(Duration::days(days as i64))
+        .ok_or_else(|| LicenseError::ValidationError("天数超出范围".to_string()))?;
+
+    let revocations = load_revocations()?;
+    let mut licenses: Vec<LicenseInfo> = load_license_db()?
+        .licenses
+        .into_iter()
+        .filter(|l| !is_perpetual(l) && !is_license_expired(l, now) && l.expiry_date <= deadline)
+        .filter(|l| !revocations.contains_key(&l.license_id))
+        .collect();
+    licenses.sort_by_key(|l| l.expiry_date);
+
+    Ok(licenses)
+}
+
+// 功能的基础名称：去掉配额值（name=value 或 name:value）和通配后缀（.*）
+pub(crate) fn feature_base_name(feature: &str) -> &str {
+    let base = feature.split(['=', ':']).next().unwrap_or(feature).trim();
+    base.strip_suffix(".*").unwrap_or(base)
+}
+
+// 统计每个功能被多少个未过期且未吊销的许可证包含，配额和通配功能按基础名称计数
+pub fn feature_usage_report() -> Result<BTreeMap<String, usize>, LicenseError> {
+    let db = load_license_db()?;
+    let revocations = load_revocations()?;
+    let registry = settings::get_feature_registry()?;
+    let now = clock::now();
+
+    let mut report = BTreeMap::new();
+    for license in db.licenses {
+        if is_license_expired(&license, now) || revocations.contains_key(&license.license_id) {
+            continue;
+        }
+        let mut license = license;
+        expand_feature_bits(&mut license, &registry)?;
+
+        // 同一许可证中的同名功能只计一次
+        let mut bases: Vec<&str> = license
+            .features
+            .iter()
+            .map(|f| feature_base_name(f))
+            .collect();
+        bases.sort_unstable();
+        bases.dedup();
+        for base in bases.into_iter().filter(|b| !b.is_empty()) {
+            *report.entry(base.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+// 导出公钥，公钥不存在时返回错误
+pub fn export_public_key(profile: Option<&str>) -> Result<String, LicenseError> {
+    let public_key_path = get_public_key_path(profile)?;
+    if !public_key_path.exists() {
+        return Err(LicenseError::PublicKeyNotFound);
+    }
+
+    read_key_file(&public_key_path, "公钥")
+}
+
+// 按指定格式导出公钥，二进制格式以Base64编码返回
+pub fn export_public_key_as(
+    format: PublicKeyFormat,
+    profile: Option<&str>,
+) -> Result<String, LicenseError> {
+    let public_key = load_public_key(profile)?;
+
+    match format {
+        PublicKeyFormat::PemSpki => public_key
+            .to_public_key_pem(pkcs8::LineEnding::LF)
+            .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e))),
+        PublicKeyFormat::DerSpki => public_key
+            .to_public_key_der()
+            .map(|der| general_purpose::STANDARD.encode(der.as_bytes()))
+            .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e))),
+        PublicKeyFormat::PemPkcs1 => public_key
+            .to_pkcs1_pem(pkcs8::LineEnding::LF)
+            .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e))),
+    }
+}
+
+// 公钥指纹：SPKI DER编码的SHA-256十六进制值，用于核对客户端与签发端使用的是同一把公钥
+pub fn public_key_fingerprint(profile: Option<&str>) -> Result<String, LicenseError> {
+    key_fingerprint(&load_public_key(profile)?)
+}
+
+fn key_fingerprint(public_key: &RsaPublicKey) -> Result<String, LicenseError> {
+    let der = public_key
+        .to_public_key_der()
+        .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
+
+    Ok(hex::encode(Sha256::digest(der.as_bytes())))
+}
+
+// 计算PEM格式公钥的指纹
+pub(crate) fn public_key_pem_fingerprint(public_key_pem: &str) -> Result<String, LicenseError> {
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem.trim())
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析公钥: {}", e)))?;
+    key_fingerprint(&public_key)
+}
+
+// 安装外部提供的公钥，返回其指纹；指纹与预期不符或已安装不同的公钥时拒绝安装
+pub(crate) fn install_public_key(
+    public_key_pem: &str,
+    expected_fingerprint: Option<&str>,
+    profile: Option<&str>,
+) -> Result<String, LicenseError> {
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem.trim())
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析公钥: {}", e)))?;
+    let fingerprint = key_fingerprint(&public_key)?;
+    if let Some(expected) = expected_fingerprint {
+        if !fingerprint.eq_ignore_ascii_case(expected.trim()) {
+            return Err(LicenseError::ValidationError("公钥指纹不匹配".to_string()));
+        }
+    }
+
+    let public_key_path = get_public_key_path(profile)?;
+    if public_key_path.exists() {
+        if public_key_fingerprint(profile)? != fingerprint {
+            return Err(LicenseError::ValidationError(
+                "公钥与已安装的公钥不一致".to_string(),
+            ));
+        }
+        return Ok(fingerprint);
+    }
+
+    let public_key_pem = public_key
+        .to_public_key_pem(pkcs8::LineEnding::LF)
+        .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
+    write_key_file(&public_key_path, &public_key_pem, "公钥")?;
+
+    Ok(fingerprint)
+}
+
+// 公钥修复结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyRepair {
+    // 是否重新写入了公钥文件
+    pub repaired: bool,
+    // 数据库中能用恢复的公钥验证签名的许可证数量
+    pub matched_licenses: usize,
+    // 签名无法验证的许可证数量（如由已停用的密钥签发）
+    pub unmatched_licenses: usize,
+    pub message: String,
+}
+
+/// 公钥文件缺失而私钥仍在时，由私钥导出公钥并重新写入公钥文件。
+/// 写入前用导出的公钥验证数据库中的许可证，数据库中有许可证但没有一个匹配时不修复
+pub fn repair_public_key(profile: Option<&str>) -> Result<PublicKeyRepair, LicenseError> {
+    let public_key_path = get_public_key_path(profile)?;
+    let private_key_path = get_private_key_path(profile)?;
+    if public_key_path.exists() {
+        return Ok(PublicKeyRepair {
+            repaired: false,
+            matched_licenses: 0,
+            unmatched_licenses: 0,
+            message: "公钥文件存在，无需修复".to_string(),
+        });
+    }
+    if !private_key_path.exists() {
+        return Err(LicenseError::KeyNotFound(
+            "私钥和公钥都不存在，无法恢复公钥".to_string(),
+        ));
+    }
+
+    let private_key_pem = read_key_file(&private_key_path, "私钥")?;
+    let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem)
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析私钥: {}", e)))?;
+    let public_key = RsaPublicKey::from(&private_key);
+
+    let mut matched_licenses = 0;
+    let mut unmatched_licenses = 0;
+    for license in &load_license_db()?.licenses {
+        let matched = verify_with_key(
+            &public_key,
+            &signing_payload(license)?,
+            &license.signature,
+            license.hash_algorithm,
+        )
+        .unwrap_or(false);
+        if matched {
+            matched_licenses += 1;
+        } else {
+            unmatched_licenses += 1;
+        }
+    }
+    if matched_licenses == 0 && unmatched_licenses > 0 {
+        return Err(LicenseError::ValidationError(format!(
+            "私钥与数据库中的{}个许可证都不匹配，未修复公钥",
+            unmatched_licenses
+        )));
+    }
+
+    let public_key_pem = public_key
+        .to_public_key_pem(pkcs8::LineEnding::LF)
+        .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
+    write_key_file(&public_key_path, &public_key_pem, "公钥")?;
+
+    Ok(PublicKeyRepair {
+        repaired: true,
+        matched_licenses,
+        unmatched_licenses,
+        message: format!(
+            "已从私钥恢复公钥，{}个许可证验证通过，{}个无法验证",
+            matched_licenses, unmatched_licenses
+        ),
+    })
+}
+
+// 检查私钥能否正常读取和解析，私钥不存在时返回 Ok(false)，不会触发密钥生成
+pub(crate) fn check_private_key(profile: Option<&str>) -> Result<bool, LicenseError> {
+    let private_key_path = get_private_key_path(profile)?;
+    if !private_key_path.exists() {
+        return Ok(false);
+    }
+
+    let private_key_pem = read_key_file(&private_key_path, "私钥")?;
+    RsaPrivateKey::from_pkcs8_pem(&private_key_pem)
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析私钥: {}", e)))?;
+
+    Ok(true)
+}
+
+// 确保密钥对存在，不存在时生成（仅用于签发端），返回公钥
+pub fn ensure_keys(profile: Option<&str>) -> Result<String, LicenseError> {
+    load_or_generate_keys(profile)?;
+    export_public_key(profile)
+}
+
+// 检查指定配置文件是否已安装公钥
+pub(crate) fn has_public_key(profile: Option<&str>) -> bool {
+    get_public_key_path(profile)
+        .map(|path| path.exists())
+        .unwrap_or(false)
+}
+
+// 检查当前安装是否具备签发能力（存在私钥），不会触发密钥生成
+pub fn has_signing_capability(profile: Option<&str>) -> bool {
+    get_private_key_path(profile)
+        .map(|path| path.exists())
+        .unwrap_or(false)
+}
+
+// 生成新的RSA密钥对。已有密钥时必须确认，旧密钥会先备份
+pub fn generate_new_key_pair(
+    bits: usize,
+    profile: Option<&str>,
+    confirm: bool,
+) -> Result<KeyPairResult, LicenseError> {
+    // 生成大尺寸密钥耗时较长，先检查确认再生成
+    check_overwrite_confirmed(profile, confirm)?;
+    let private_key = generate_private_key(bits)?;
+    install_key_pair(&private_key, profile, confirm)
+}
+
+// 生成随机的RSA私钥（大尺寸密钥耗时较长，不写入文件）
+pub(crate) fn generate_private_key(bits: usize) -> Result<RsaPrivateKey, LicenseError> {
+    RsaPrivateKey::new(&mut OsRng, bits)
+        .map_err(|e| LicenseError::ValidationError(format!("生成RSA密钥失败: {}", e)))
+}
+
+// 密钥对生成或恢复的结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyPairResult {
+    pub private_key: String,
+    pub public_key: String,
+    // 覆盖前备份的旧密钥目录，原先没有密钥时为空
+    pub backup_path: Option<String>,
+}
+
+// 密钥备份目录名称
+const KEY_BACKUPS_DIR: &str = "backups";
+
+// 配置文件下是否已有密钥文件
+fn keys_exist(profile: Option<&str>) -> Result<bool, LicenseError> {
+    Ok(get_private_key_path(profile)?.exists() || get_public_key_path(profile)?.exists())
+}
+
+// 覆盖已有密钥会使所有已签发的许可证失效，必须显式确认
+pub(crate) fn check_overwrite_confirmed(
+    profile: Option<&str>,
+    confirm: bool,
+) -> Result<(), LicenseError> {
+    if !confirm && keys_exist(profile)? {
+        return Err(LicenseError::ValidationError(
+            "已存在密钥对，覆盖后用旧密钥签发的许可证将无法通过验证，请确认后重试".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// 将当前密钥对备份到 backups/<时间戳> 目录，返回备份目录
+fn backup_key_pair(profile: Option<&str>) -> Result<Option<PathBuf>, LicenseError> {
+    if !keys_exist(profile)? {
+        return Ok(None);
+    }
+
+    let backup_dir = get_keys_dir(profile)?
+        .join(KEY_BACKUPS_DIR)
+        .join(clock::now().format("%Y%m%d-%H%M%S-%3f").to_string());
+    fs::create_dir_all(&backup_dir).map_err(|e| {
+        file_error(
+            &e,
+            &backup_dir,
+            "创建目录",
+            format!("创建密钥备份目录失败: {}", e),
+        )
+    })?;
+
+    for (path, label) in [
+        (get_private_key_path(profile)?, "私钥"),
+        (get_public_key_path(profile)?, "公钥"),
+    ] {
+        if path.exists() {
+            let pem = read_key_file(&path, label)?;
+            let file_name = path.file_name().unwrap_or_default();
+            write_key_file(&backup_dir.join(file_name), &pem, label)?;
+        }
+    }
+    let metadata_path = get_key_metadata_path(profile)?;
+    if metadata_path.exists() {
+        let backup_metadata_path = backup_dir.join("key_info.json");
+        fs::copy(&metadata_path, &backup_metadata_path).map_err(|e| {
+            file_error(
+                &e,
+                &backup_metadata_path,
+                "写入",
+                format!("备份密钥信息失败: {}", e),
+            )
+        })?;
+    }
+
+    Ok(Some(backup_dir))
+}
+
+// 将私钥及其公钥保存为指定配置文件的密钥对，覆盖前备份旧密钥
+pub(crate) fn install_key_pair(
+    private_key: &RsaPrivateKey,
+    profile: Option<&str>,
+    confirm: bool,
+) -> Result<KeyPairResult, LicenseError> {
+    check_overwrite_confirmed(profile, confirm)?;
+
+    // 从私钥导出公钥
+    let public_key = RsaPublicKey::from(private_key);
+
+    // 转换为PEM格式
+    let private_key_pem = private_key
+        .to_pkcs8_pem(pkcs8::LineEnding::LF)
+        .map_err(|e| LicenseError::ValidationError(format!("转换私钥格式失败: {}", e)))?
+        .to_string();
+
+    let public_key_pem = public_key
+        .to_public_key_pem(pkcs8::LineEnding::LF)
+        .map_err(|e| LicenseError::ValidationError(format!("转换公钥格式失败: {}", e)))?;
+
+    // 备份旧密钥，并保留旧公钥用于识别旧密钥签发的许可证
+    let backup_path = backup_key_pair(profile)?;
+    retire_current_public_key(profile)?;
+
+    // 保存到文件
+    write_key_file(&get_private_key_path(profile)?, &private_key_pem, "私钥")?;
+    write_key_file(&get_public_key_path(profile)?, &public_key_pem, "公钥")?;
+    write_active_key_metadata(profile)?;
+
+    Ok(KeyPairResult {
+        private_key: private_key_pem,
+        public_key: public_key_pem,
+        backup_path: backup_path.map(|path| path.display().to_string()),
+    })
+}
+
+// 从备份目录恢复密钥对，恢复前同样备份当前密钥
+pub fn restore_key_pair(
+    backup_dir: &Path,
+    profile: Option<&str>,
+    confirm: bool,
+) -> Result<KeyPairResult, LicenseError> {
+    let private_key_pem = read_key_file(&backup_dir.join("private_key.pem"), "备份私钥")?;
+    let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem)
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析备份私钥: {}", e)))?;
+
+    // 备份中的公钥必须与私钥匹配
+    let backup_public_key_path = backup_dir.join("public_key.pem");
+    if backup_public_key_path.exists() {
+        let public_key_pem = read_key_file(&backup_public_key_path, "备份公钥")?;
+        let public_key = RsaPublicKey::from_public_key_pem(&public_key_pem)
+            .map_err(|e| LicenseError::ValidationError(format!("无法解析备份公钥: {}", e)))?;
+        if public_key != RsaPublicKey::from(&private_key) {
+            return Err(LicenseError::ValidationError(
+                "备份中的公钥与私钥不匹配".to_string(),
+            ));
+        }
+    }
+
+    let mut result = install_key_pair(&private_key, profile, confirm)?;
+
+    // 恢复原来的创建时间
+    let backup_metadata_path = backup_dir.join("key_info.json");
+    if backup_metadata_path.exists() {
+        let metadata_path = get_key_metadata_path(profile)?;
+        fs::copy(&backup_metadata_path, &metadata_path).map_err(|e| {
+            file_error(
+                &e,
+                &metadata_path,
+                "写入",
+                format!("恢复密钥信息失败: {}", e),
+            )
+        })?;
+    }
+    result.private_key = private_key_pem;
+    Ok(result)
+}
+
+// 导出私钥时口令的最小长度
+pub const MIN_KEY_EXPORT_PASSPHRASE_LEN: usize = 12;
+
+// 检查导出口令，口令过短时离线备份容易被暴力破解
+fn check_export_passphrase(passphrase: &str) -> Result<(), LicenseError> {
+    if passphrase.chars().count() < MIN_KEY_EXPORT_PASSPHRASE_LEN {
+        return Err(LicenseError::ValidationError(format!(
+            "口令长度不能少于{}个字符",
+            MIN_KEY_EXPORT_PASSPHRASE_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// 导出用口令加密的私钥（PKCS#8 加密PEM，scrypt + AES-256-CBC），用于离线备份。
+/// 必须显式确认，避免误触发；不会导出未加密的私钥
+pub fn export_private_key(
+    passphrase: &str,
+    profile: Option<&str>,
+    confirm: bool,
+) -> Result<String, LicenseError> {
+    if !confirm {
+        return Err(LicenseError::ValidationError(
+            "导出私钥需要确认，请妥善保管导出的文件和口令".to_string(),
+        ));
+    }
+    check_export_passphrase(passphrase)?;
+
+    let private_key_path = get_private_key_path(profile)?;
+    if !private_key_path.exists() {
+        return Err(LicenseError::KeyNotFound("私钥不存在".to_string()));
+    }
+    let private_key_pem = read_key_file(&private_key_path, "私钥")?;
+    let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem)
+        .map_err(|e| LicenseError::ValidationError(format!("无法解析私钥: {}", e)))?;
+
+    let encrypted_pem = private_key
+        .to_pkcs8_encrypted_pem(&mut OsRng, passphrase, pkcs8::LineEnding::LF)
+        .map_err(|e| LicenseError::ValidationError(format!("加密私钥失败: {}", e)))?
+        .to_string();
+
+    let fingerprint = key_fingerprint(&RsaPublicKey::from(&private_key))?;
+    audit::record("export_private_key", "", &fingerprint)?;
+
+    Ok(encrypted_pem)
+}
+
+/// 导入 export_private_key 导出的加密私钥，作为指定配置文件的密钥对。
+/// 覆盖现有密钥时需要确认，旧密钥会先备份；返回结果中不包含私钥明文
+pub fn import_private_key(
+    encrypted_pem: &str,
+    passphrase: &str,
+    profile: Option<&str>,
+    confirm: bool,
+) -> Result<KeyPairResult, LicenseError> {
+    check_overwrite_confirmed(profile, confirm)?;
+
+    let private_key = RsaPrivateKey::from_pkcs8_encrypted_pem(encrypted_pem.trim(), passphrase)
+        .map_err(|e| {
+            LicenseError::ValidationError(format!("无法解密私钥，请检查口令是否正确: {}", e))
+        })?;
+
+    let mut result = install_key_pair(&private_key, profile, confirm)?;
+    audit::record(
+        "import_private_key",
+        "",
+        &key_fingerprint(&RsaPublicKey::from(&private_key))?,
+    )?;
+
+    result.private_key = String::new();
+    Ok(result)
+}
+
+pub fn generate_license_with_machine_code(
+    customer_name: &str,
+    customer_email: &str,
+    expiry_days: u32,
+    features: Vec<String>,
+    options: &LicenseOptions,
+    machine_code: &MachineCodeInput,
+    profile: Option<&str>,
+) -> Result<LicenseIssuance, LicenseError> {
+    let machine_code = normalize_machine_code_input(machine_code)?;
+    issue_license(
+        customer_name,
+        customer_email,
+        LicenseExpiry::Days(expiry_days),
+        features,
+        options,
+        Some(&machine_code),
+        profile,
+    )
+}
+
+// 单次批量签发的最大机器数量
+pub const MAX_BATCH_MACHINES: usize = 1000;
+
+/// 为每个机器码签发一个绑定该机器的许可证，每个机器码按其自身的算法版本记录，全部签发成功后
+/// 一次性保存到数据库；任一机器码无效或重复时不保存任何许可证。
+/// 返回规范化后的机器码到许可证密钥的映射和签发警告
+pub fn generate_licenses_with_machine_codes(
+    customer_name: &str,
+    customer_email: &str,
+    expiry_days: u32,
+    features: Vec<String>,
+    options: &LicenseOptions,
+    machine_codes: &[MachineCodeInput],
+    profile: Option<&str>,
+) -> Result<BulkLicenseIssuance, LicenseError> {
+    if machine_codes.is_empty() {
+        return Err(LicenseError::ValidationError(
+            "机器码列表不能为空".to_string(),
+        ));
+    }
+    if machine_codes.len() > MAX_BATCH_MACHINES {
+        return Err(LicenseError::ValidationError(format!(
+            "单次最多为{}台机器签发许可证",
+            MAX_BATCH_MACHINES
+        )));
+    }
+
+    let mut normalized = Vec::with_capacity(machine_codes.len());
+    for machine_code in machine_codes {
+        let machine_code = normalize_machine_code_input(machine_code)?;
+        if normalized
+            .iter()
+            .any(|existing: &MachineCodeInput| existing.code == machine_code.code)
+        {
+            return Err(LicenseError::ValidationError(format!(
+                "机器码重复: {}",
+                machine_code.code
+            )));
+        }
+        normalized.push(machine_code);
+    }
+
+    let _lock = store::lock_license_db();
+    let mut issued = Vec::with_capacity(normalized.len());
+    let mut license_keys = BTreeMap::new();
+    let mut warnings: Vec<String> = Vec::new();
+    for machine_code in normalized {
+        let (info, issuance) = build_license(
+            customer_name,
+            customer_email,
+            LicenseExpiry::Days(expiry_days),
+            features.clone(),
+            options,
+            Some(&machine_code),
+            profile,
+        )?;
+        issued.push(info);
+        license_keys.insert(machine_code.code, issuance.license_key);
+        // 同一批次的功能相同，警告只保留一份
+        for warning in issuance.warnings {
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+
+    // 已用相同输入签发过的许可证返回已有的密钥，只保存和通知新签发的许可证
+    let existing = store::update_license_db(|db| {
+        let mut existing = Vec::new();
+        for info in &issued {
+            match find_issued_license(db, &info.license_id) {
+                Some(license) => existing.push(license),
+                None => db.licenses.push(info.clone()),
+            }
+        }
+        Ok(existing)
+    })?;
+    for license in &existing {
+        if let Some(ref machine_code) = license.machine_code {
+            license_keys.insert(machine_code.clone(), encode_license_key(license)?);
+        }
+    }
+
+    for info in issued
+        .iter()
+        .filter(|info| !existing.iter().any(|l| l.license_id == info.license_id))
+    {
+        webhook::notify_license_issued(info);
+    }
+
+    Ok(BulkLicenseIssuance {
+        license_keys,
+        warnings,
+    })
+}
+
+// 规范化机器码（去除首尾空白并转为小写），并校验其为 get_machine_id 生成的32位十六进制格式；
+// 也接受 get_machine_id_formatted 的分组格式，校验每组的校验字符后还原
+pub(crate) fn normalize_machine_code(machine_code: &str) -> Result<String, LicenseError> {
+    if let Some(parsed) = machine_code::parse_formatted_machine_code(machine_code)
+        .map_err(LicenseError::ValidationError)?
+    {
+        return Ok(parsed);
+    }
+
+    let normalized = machine_code.trim().to_ascii_lowercase();
+    if normalized.len() != MACHINE_CODE_LEN || !normalized.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(LicenseError::ValidationError(format!(
+            "机器码格式无效，应为{}位十六进制字符",
+            MACHINE_CODE_LEN
+        )));
+    }
+
+    Ok(normalized)
+}
+
+// 规范化客户提供的机器码，并校验算法版本是本程序支持的版本
+fn normalize_machine_code_input(
+    input: &MachineCodeInput,
+) -> Result<MachineCodeInput, LicenseError> {
+    if input.version == 0 || input.version > CURRENT_MACHINE_CODE_VERSION {
+        return Err(LicenseError::ValidationError(format!(
+            "不支持的机器码算法版本: {}",
+            input.version
+        )));
+    }
+
+    Ok(MachineCodeInput {
+        code: normalize_machine_code(&input.code)?,
+        version: input.version,
+    })
+}
+
+// 添加验证许可证并检查机器码的函数
+pub fn validate_license_with_machine_code(
+    license_key: &str,
+    machine_code: &str,
+    profile: Option<&str>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    let machine_code = normalize_machine_code(machine_code)?;
+    let result = validate_license(license_key, profile)?;
+    let result = apply_first_use_binding(result, &machine_code, profile)?;
+    let bound_license_key = result.bound_license_key.clone();
+    let identifiers = result
+        .info
+        .as_ref()
+        .and_then(|info| local_identifiers_for(info, &machine_code));
+    let mut result = check_machine_binding(result, &machine_code, identifiers.as_ref())?;
+    result.bound_license_key = bound_license_key;
+    Ok(result)
+}
+
+// 部分硬件绑定的标识项只能在本机读取。给定的机器码是本机的机器码时返回本机的分项标识，
+// 否则返回 None，此时无法检查部分硬件绑定
+fn local_identifiers_for(
+    info: &LicenseInfo,
+    machine_code: &str,
+) -> Option<machine_code::MachineIdentifiers> {
+    if info.hardware_binding.is_empty() {
+        return None;
+    }
+
+    let version = info
+        .machine_code_version
+        .unwrap_or(CURRENT_MACHINE_CODE_VERSION);
+    get_machine_id_versioned(version)
+        .ok()
+        .filter(|local| constant_time_eq(local, machine_code))
+        .map(|_| machine_code::get_machine_identifiers())
+}
+
+// 处理首次使用绑定的许可证：已绑定时使用本地记录的机器码，未绑定时绑定到当前机器。
+// 具备签发能力时同时重新签发绑定了机器码的许可证密钥，之后原密钥在本机不再有效
+fn apply_first_use_binding(
+    mut result: LicenseValidationResult,
+    machine_code: &str,
+    profile: Option<&str>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    let Some(info) = result.info.as_mut() else {
+        return Ok(result);
+    };
+    if !result.is_valid || !info.bind_on_first_use || info.machine_code.is_some() {
+        return Ok(result);
+    }
+
+    // 绑定记录、重新签发和数据库更新在同一个锁内完成
+    let _lock = store::lock_license_db();
+    let mut bindings = load_bindings()?;
+    if let Some(binding) = bindings.get(&info.license_id) {
+        if binding.resigned {
+            let info = result.info.take();
+            return Ok(LicenseValidationResult::new(
+                false,
+                info,
+                "该许可证已绑定机器并重新签发，请使用绑定后重新签发的许可证密钥",
+            )
+            .with_kid(result.kid));
+        }
+        info.machine_code = Some(binding.machine_code.clone());
+        return Ok(result);
+    }
+
+    let binding_path = get_binding_db_path()?;
+    bindings.insert(
+        info.license_id.clone(),
+        FirstUseBinding {
+            machine_code: machine_code.to_string(),
+            bound_at: clock::now(),
+            resigned: false,
+        },
+    );
+    write_json_file(&binding_path, &bindings, "绑定记录")?;
+    info.machine_code = Some(machine_code.to_string());
+
+    if has_signing_capability(profile) {
+        let bound = sign_license_info(
+            LicenseInfo {
+                bind_on_first_use: false,
+                ..info.clone()
+            },
+            profile,
+        )?;
+
+        // 数据库中有该许可证时替换为绑定后的版本
+        store::update_license_db(|db| {
+            if let Some(existing) = db
+                .licenses
+                .iter_mut()
+                .find(|l| l.license_id == bound.license_id)
+            {
+                *existing = bound.clone();
+            }
+            Ok(())
+        })?;
+        result.bound_license_key = Some(encode_license_key(&bound)?);
+
+        if let Some(binding) = bindings.get_mut(&bound.license_id) {
+            binding.resigned = true;
+        }
+        write_json_file(&binding_path, &bindings, "绑定记录")?;
+    }
+
+    Ok(result)
+}
+
+// 检查许可证的机器码和部分硬件绑定是否与当前机器一致，不一致时返回原因。
+// identifiers 为被验证机器的分项标识，只有验证本机时才能提供
+fn machine_binding_mismatch(
+    info: &LicenseInfo,
+    machine_code: &str,
+    identifiers: Option<&machine_code::MachineIdentifiers>,
+) -> Option<String> {
+    // 旧许可证中的机器码可能是大写，比较前统一转为小写
+    if let Some(ref license_machine_code) = info.machine_code {
+        if !constant_time_eq(
+            &license_machine_code.trim().to_ascii_lowercase(),
+            machine_code,
+        ) {
+            return Some("许可证与当前机器不匹配".to_string());
+        }
+    }
+
+    // 部分硬件绑定只比较许可证中列出的标识项，其余硬件变化不影响验证
+    if !info.hardware_binding.is_empty() {
+        let Some(identifiers) = identifiers else {
+            return Some(
+                "部分硬件绑定的许可证只能在其绑定的机器上验证，无法按其他机器的机器码检查"
+                    .to_string(),
+            );
+        };
+        let mismatched: Vec<&str> = info
+            .hardware_binding
+            .iter()
+            .filter(|(component, expected)| {
+                !identifiers
+                    .component(component)
+                    .flatten()
+                    .is_some_and(|actual| constant_time_eq(actual, expected))
+            })
+            .map(|(component, _)| component.as_str())
+            .collect();
+        if !mismatched.is_empty() {
+            return Some(format!(
+                "许可证与当前机器不匹配（{}）",
+                mismatched.join(", ")
+            ));
+        }
+    }
+
+    None
+}
+
+// 在许可证基本验证结果的基础上检查机器绑定
+fn check_machine_binding(
+    result: LicenseValidationResult,
+    machine_code: &str,
+    identifiers: Option<&machine_code::MachineIdentifiers>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    // 如果许可证本身无效，直接返回结果
+    let license_info = match result.info {
+        Some(ref info) if result.is_valid => info.clone(),
+        _ => return Ok(result),
+    };
+    let kid = result.kid;
+
+    if let Some(message) = machine_binding_mismatch(&license_info, machine_code, identifiers) {
+        return Ok(
+            LicenseValidationResult::new(false, Some(license_info), &message).with_kid(kid.clone()),
+        );
+    }
+
+    // 一次性许可证只在兑换它的机器上有效
+    if license_info.single_use {
+        let redeemed_elsewhere = load_redemptions()?
+            .get(&license_info.license_id)
+            .is_some_and(|redemption| {
+                !constant_time_eq(&redemption.machine_code.to_ascii_lowercase(), machine_code)
+            });
+        if redeemed_elsewhere {
+            return Ok(LicenseValidationResult::new(
+                false,
+                Some(license_info),
+                "一次性许可证已在其他机器上兑换",
+            )
+            .with_kid(kid.clone()));
+        }
+    }
+
+    // 所有检查都通过
+    Ok(
+        LicenseValidationResult::new(true, Some(license_info), "许可证有效且与当前机器匹配")
+            .with_kid(kid),
+    )
+}
+
+// 从许可证文件（.lic）读取并验证许可证
+pub fn validate_license_file(
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| file_error(&e, path, "读取", format!("读取许可证文件失败: {}", e)))?;
+
+    // 文件末尾可能带有换行或空白
+    validate_license(contents.trim(), profile)
+}
+
+// 多个许可证中选出的最佳许可证
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BestLicenseResult {
+    // 最佳许可证在输入列表中的位置，没有有效许可证时为 None
+    pub best_index: Option<usize>,
+    pub best: Option<LicenseValidationResult>,
+    // 所有有效许可证授予的功能（不含已单独到期的功能），按名称排序
+    pub combined_features: Vec<String>,
+    pub valid_count: usize,
+}
+
+// 许可证的优先级：永久许可证优先，其次到期时间晚的，再其次功能多的
+fn license_rank(result: &LicenseValidationResult) -> (bool, DateTime<Utc>, usize) {
+    match result.info.as_ref() {
+        Some(info) => (
+            is_perpetual(info),
+            info.expiry_date,
+            info.features.len() - result.expired_features.len().min(info.features.len()),
+        ),
+        None => (false, DateTime::<Utc>::MIN_UTC, 0),
+    }
+}
+
+// 逐个验证许可证，返回有效的结果及其在输入列表中的位置。
+// 指定机器码时同时检查机器绑定；无法解析的密钥视为无效，机器码格式错误时直接返回错误
+fn validate_keys(
+    keys: &[String],
+    machine_code: Option<&str>,
+    profile: Option<&str>,
+) -> Result<Vec<(usize, LicenseValidationResult)>, LicenseError> {
+    let machine_code = machine_code.map(normalize_machine_code).transpose()?;
+
+    let mut valid = Vec::new();
+    for (index, key) in keys.iter().enumerate() {
+        let result = match machine_code.as_deref() {
+            Some(machine_code) => validate_license_with_machine_code(key, machine_code, profile),
+            None => validate_license(key, profile),
+        };
+        match result {
+            Ok(result) if result.is_valid => valid.push((index, result)),
+            Ok(_)
+            | Err(LicenseError::ValidationError(_))
+            | Err(LicenseError::SerializationError(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(valid)
+}
+
+// 有效许可证中未单独到期的功能
+fn active_features(result: &LicenseValidationResult) -> impl Iterator<Item = &String> {
+    result
+        .info
+        .iter()
+        .flat_map(|info| info.features.iter())
+        .filter(|feature| {
+            !result
+                .expired_features
+                .iter()
+                .any(|expired| expired == feature_base_name(feature))
+        })
+}
+
+/// 验证多个许可证，返回到期最晚、功能最多的有效许可证，以及所有有效许可证的功能合集。
+/// 指定机器码时同时检查机器绑定；无法解析的密钥视为无效
+pub fn validate_best_license(
+    keys: &[String],
+    machine_code: Option<&str>,
+    profile: Option<&str>,
+) -> Result<BestLicenseResult, LicenseError> {
+    let valid = validate_keys(keys, machine_code, profile)?;
+    let valid_count = valid.len();
+
+    let mut combined_features: Vec<String> = Vec::new();
+    for (_, result) in &valid {
+        for feature in active_features(result) {
+            if !combined_features.contains(feature) {
+                combined_features.push(feature.clone());
+            }
+        }
+    }
+    combined_features.sort();
+
+    let mut best: Option<(usize, LicenseValidationResult)> = None;
+    for (index, result) in valid {
+        let is_better = best
+            .as_ref()
+            .is_none_or(|(_, current)| license_rank(&result) > license_rank(current));
+        if is_better {
+            best = Some((index, result));
+        }
+    }
+
+    let (best_index, best) = match best {
+        Some((index, result)) => (Some(index), Some(result)),
+        None => (None, None),
+    };
+
+    Ok(BestLicenseResult {
+        best_index,
+        best,
+        combined_features,
+        valid_count,
+    })
+}
+
+// 多个许可证合并后的有效功能
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EffectiveFeatures {
+    // 合并后的功能，数值配额合并为 name=合计值，按名称排序
+    pub features: Vec<String>,
+    // 数值配额的合计值，按功能基础名称
+    pub quotas: BTreeMap<String, u64>,
+    pub valid_count: usize,
+}
+
+// 功能的数值配额（name=数值 或 name:数值），不是数值配额时返回 None
+fn numeric_quota(feature: &str) -> Option<(&str, u64)> {
+    let (name, value) = feature.split_once(['=', ':'])?;
+    Some((name.trim(), value.trim().parse().ok()?))
+}
+
+/// 验证多个许可证（检查有效期、吊销和机器绑定），返回所有有效许可证的功能合集，
+/// 多个许可证中相同的数值配额累加
+pub fn effective_features(
+    keys: &[String],
+    machine_code: Option<&str>,
+    profile: Option<&str>,
+) -> Result<EffectiveFeatures, LicenseError> {
+    let valid = validate_keys(keys, machine_code, profile)?;
+
+    let mut quotas: BTreeMap<String, u64> = BTreeMap::new();
+    let mut features: Vec<String> = Vec::new();
+    for (_, result) in &valid {
+        for feature in active_features(result) {
+            match numeric_quota(feature) {
+                Some((name, value)) => {
+                    let total = quotas.entry(name.to_string()).or_insert(0);
+                    *total = total.saturating_add(value);
+                }
+                None if !features.contains(feature) => features.push(feature.clone()),
+                None => {}
+            }
+        }
+    }
+
+    features.extend(
+        quotas
+            .iter()
+            .map(|(name, total)| format!("{}={}", name, total)),
+    );
+    features.sort();
+
+    Ok(EffectiveFeatures {
+        features,
+        quotas,
+        valid_count: valid.len(),
+    })
+}
+
+// 目录批量验证中单个文件的结果，读取或解析失败时 result 为空
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicenseFileResult {
+    pub file_name: String,
+    pub result: Option<LicenseValidationResult>,
+    pub error: Option<String>,
+}
+
+// 批量验证的最大并行线程数，避免占满CPU导致界面卡顿
+const MAX_VALIDATION_THREADS: usize = 4;
+
+// 验证目录中的所有许可证文件（.lic），签名验证较耗CPU，使用有限的线程并行处理，结果按文件名排序
+pub fn validate_license_dir(
+    dir: &Path,
+    profile: Option<&str>,
+) -> Result<Vec<LicenseFileResult>, LicenseError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| file_error(&e, dir, "读取", format!("读取许可证目录失败: {}", e)))?;
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("lic"))
+        })
+        .collect();
+    files.sort();
+
+    // 保留一个核心给界面线程
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get().saturating_sub(1))
+        .unwrap_or(1)
+        .clamp(1, MAX_VALIDATION_THREADS)
+        .min(files.len().max(1));
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<(usize, LicenseFileResult)>> =
+        Mutex::new(Vec::with_capacity(files.len()));
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(index) else {
+                    break;
+                };
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let file_result = match validate_license_file(path, profile) {
+                    Ok(result) => LicenseFileResult {
+                        file_name,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(e) => LicenseFileResult {
+                        file_name,
+                        result: None,
+                        error: Some(e.to_string()),
+                    },
+                };
+                results
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push((index, file_result));
+            });
+        }
+    });
+
+    let mut results = results
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+// 将许可证密钥保存为许可证文件（.lic）
+pub fn save_license_to_file(license_key: &str, path: &Path) -> Result<(), LicenseError> {
+    fs::write(path, format!("{}\n", license_key.trim()))
+        .map_err(|e| file_error(&e, path, "写入", format!("写入许可证文件失败: {}", e)))
+}
+
+// 兑换一次性许可证，将其绑定到首次兑换的机器，之后的兑换均会失败
+pub fn redeem_license(
+    license_key: &str,
+    machine_code: &str,
+    profile: Option<&str>,
+) -> Result<LicenseValidationResult, LicenseError> {
+    let machine_code = normalize_machine_code(machine_code)?;
+    let result = validate_license_inner(license_key, profile, false)?;
+    let license_info = match result.info {
+        Some(ref info) if result.is_valid => info.clone(),
+        _ => return Ok(result),
+    };
+    let kid = result.kid;
+
+    if !license_info.single_use {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_info),
+            "该许可证不是一次性许可证，无需兑换",
+        )
+        .with_kid(kid.clone()));
+    }
+
+    if license_info
+        .machine_code
+        .as_ref()
+        .is_some_and(|code| !constant_time_eq(&code.trim().to_ascii_lowercase(), &machine_code))
+    {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_info),
+            "许可证与当前机器不匹配",
+        )
+        .with_kid(kid.clone()));
+    }
+
+    // 读取、检查和写入兑换记录期间持有数据库锁，避免同一许可证被并发兑换两次
+    let _lock = store::lock_license_db();
+    let mut redemptions = load_redemptions()?;
+    if redemptions.contains_key(&license_info.license_id) {
+        return Ok(LicenseValidationResult::new(
+            false,
+            Some(license_info),
+            "该一次性许可证已被兑换",
+        )
+        .with_kid(kid.clone()));
+    }
+
+    redemptions.insert(
+        license_info.license_id.clone(),
+        Redemption {
+            machine_code,
+            redeemed_at: clock::now(),
+        },
+    );
+    write_json_file(&get_redemption_db_path()?, &redemptions, "兑换记录")?;
+
+    Ok(LicenseValidationResult::new(true, Some(license_info), "许可证兑换成功").with_kid(kid))
+}
+
+// 吊销许可证，此后验证该许可证均失败；返回是否新增了吊销记录，已吊销时不重复记录
+pub fn revoke_license(license_id: &str, reason: &str) -> Result<bool, LicenseError> {
+    let license_id = license_id.trim();
+    if license_id.is_empty() {
+        return Err(LicenseError::ValidationError(
+            "许可证ID不能为空".to_string(),
+        ));
+    }
+
+    // 读取、检查和写入吊销记录期间持有数据库锁，避免并发吊销互相覆盖
+    let _lock = store::lock_license_db();
+    let mut revocations = load_revocations()?;
+    if revocations.contains_key(license_id) {
+        return Ok(false);
+    }
+    revocations.insert(
+        license_id.to_string(),
+        Revocation {
+            revoked_at: clock::now(),
+            reason: reason.trim().to_string(),
+        },
+    );
+    write_json_file(&get_revocation_db_path()?, &revocations, "吊销记录")?;
+    clear_validation_cache();
+    audit::record("revoke", license_id, reason.trim())?;
+
+    Ok(true)
+}
+
+// 使用当前机器的机器码验证许可证，前端无需先获取机器码
+pub fn verify_current_machine(
+    license_key: &str,
+    profile: Option<&str>,
+) -> Result<MachineVerificationResult, LicenseError> {
+    let result = validate_license(license_key, profile)?;
+
+    // 按许可证记录的算法版本计算机器码，保证旧版绑定的许可证仍然匹配
+    let version = result
+        .info
+        .as_ref()
+        .and_then(|info| info.machine_code_version)
+        .unwrap_or(1);
+    let machine_id = get_machine_id_versioned(version)
+        .map_err(|e| LicenseError::ValidationError(format!("获取机器码失败: {}", e)))?;
+    let result = apply_first_use_binding(result, &machine_id, profile)?;
+    let bound_license_key = result.bound_license_key.clone();
+    // 验证的是本机，部分硬件绑定直接读取本机的分项标识
+    let identifiers = result
+        .info
+        .as_ref()
+        .filter(|info| !info.hardware_binding.is_empty())
+        .map(|_| machine_code::get_machine_identifiers());
+    let mut result = check_machine_binding(result, &machine_id, identifiers.as_ref())?;
+    result.bound_license_key = bound_license_key;
+
+    // 与验证使用同一个检查，机器码和部分硬件绑定都一致时才算匹配
+    let machine_matched = result.info.as_ref().is_some_and(|info| {
+        machine_binding_mismatch(info, &machine_id, identifiers.as_ref()).is_none()
+    });
+
+    Ok(MachineVerificationResult {
+        machine_id,
+        machine_matched,
+        result,
+    })
+}
+
+// 删除许可证，返回是否有记录被删除；许可证不存在时不报错
+pub fn delete_license(license_id: &str) -> Result<DeleteOutcome, LicenseError> {
+    // 查找并删除许可证，保存更新后的数据库
+    let removed = store::update_license_db(|db| {
+        let index = db
+            .licenses
+            .iter()
+            .position(|license| license.license_id == license_id);
+        Ok(index.map(|idx| db.licenses.remove(idx)))
+    })?;
+
+    let Some(removed) = removed else {
+        return Ok(DeleteOutcome::NotFound);
+    };
+    audit::record("delete", license_id, &removed.customer_email)?;
+
+    Ok(DeleteOutcome::Deleted)
+}
+
+// 删除许可证的结果
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteOutcome {
+    Deleted,
+    NotFound,
+}
+
+// 列出所有密钥配置文件，默认配置始终排在第一位
+pub fn list_profiles() -> Result<Vec<String>, LicenseError> {
+    let mut profiles = Vec::new();
+
+    let keys_dir = get_keys_root_dir()?;
+    let read_error =
+        |e: io::Error| file_error(&e, &keys_dir, "读取", format!("读取密钥目录失败: {}", e));
+    let entries = fs::read_dir(&keys_dir).map_err(read_error)?;
+    for entry in entries {
+        let entry = entry.map_err(read_error)?;
+        let path = entry.path();
+        // 只有包含密钥文件的子目录才视为配置文件
+        let has_keys =
+            path.join("private_key.pem").exists() || path.join("public_key.pem").exists();
+        if path.is_dir() && has_keys {
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                if validate_profile_name(name).is_ok() && name != DEFAULT_PROFILE {
+                    profiles.push(name.to_string());
+                }
+            }
+        }
+    }
+
+    profiles.sort();
+    profiles.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(profiles)
+}
+
+// 创建新的密钥配置文件并为其生成密钥对
+pub fn create_profile(name: &str, bits: usize) -> Result<String, LicenseError> {
+    validate_profile_name(name)?;
+    if name == DEFAULT_PROFILE || get_private_key_path(Some(name))?.exists() {
+        return Err(LicenseError::ValidationError(format!(
+            "配置文件已存在: {}",
+            name
+        )));
+    }
+
+    let public_key_pem = generate_new_key_pair(bits, Some(name), false)?.public_key;
+    Ok(public_key_pem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestEnvironment;
+
+    // 用测试密钥签发许可证。嵌入公钥的构建中验证始终使用嵌入的公钥，
+    // 依赖测试密钥的测试在这种构建中跳过
+    fn issue(customer_name: &str, expiry_days: u32, options: &LicenseOptions) -> String {
+        generate_license(
+            customer_name,
+            "customer@example.com",
+            expiry_days,
+            vec!["basic".to_string()],
+            options,
+            None,
+        )
+        .unwrap()
+        .license_key
+    }
+
+    #[test]
+    fn customer_field_is_trimmed() {
+        assert_eq!(
+            normalize_customer_field("  Acme Ltd \n", "客户名称").unwrap(),
+            "Acme Ltd"
+        );
+    }
+
+    #[test]
+    fn empty_or_whitespace_customer_field_is_rejected() {
+        for value in ["", "   ", "\t\r\n"] {
+            assert!(matches!(
+                normalize_customer_field(value, "客户名称"),
+                Err(LicenseError::ValidationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn oversized_customer_field_is_rejected() {
+        let longest = "字".repeat(MAX_CUSTOMER_FIELD_LEN);
+        assert_eq!(
+            normalize_customer_field(&longest, "客户名称").unwrap(),
+            longest
+        );
+        assert!(matches!(
+            normalize_customer_field(&format!("{}a", longest), "客户名称"),
+            Err(LicenseError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn features_are_trimmed_deduplicated_and_empty_entries_dropped() {
+        let features = vec!["a".to_string(), "a".to_string(), "".to_string()];
+        assert_eq!(normalize_features(features).unwrap(), vec!["a".to_string()]);
+
+        let features = vec![
+            " b".to_string(),
+            "a".to_string(),
+            "b ".to_string(),
+            " ".to_string(),
+        ];
+        assert_eq!(
+            normalize_features(features).unwrap(),
+            vec!["b".to_string(), "a".to_string()]
+        );
+    }
+
+    #[test]
+    fn oversized_feature_is_rejected() {
+        assert!(matches!(
+            normalize_features(vec!["x".repeat(MAX_FEATURE_LEN + 1)]),
+            Err(LicenseError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn license_expires_when_clock_passes_expiry_date() {
+        let _environment = TestEnvironment::with_keys();
+        let issued_at = clock::now();
+        clock::set_fixed_time(issued_at);
+        let license_key = issue("Clock Test", 30, &LicenseOptions::default());
+
+        clock::set_fixed_time(issued_at + Duration::days(29));
+        assert!(validate_license(&license_key, None).unwrap().is_valid);
+
+        clock::set_fixed_time(issued_at + Duration::days(31));
+        let result = validate_license(&license_key, None).unwrap();
+        assert!(!result.is_valid);
+        assert_eq!(result.message, "许可证已过期");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn license_signed_with_a_key_below_the_policy_minimum_is_rejected() {
+        let _environment = TestEnvironment::with_keys();
+        let license_key = issue("Key Size", 30, &LicenseOptions::default());
+
+        for (min_key_bits, expected_valid) in [(0, true), (2048, true), (3072, false)] {
+            policy::set_validation_policy(ValidationPolicy {
+                min_key_bits,
+                ..Default::default()
+            })
+            .unwrap();
+            let result = validate_license(&license_key, None).unwrap();
+            assert_eq!(result.is_valid, expected_valid, "{}", min_key_bits);
+        }
+
+        let result = validate_license(&license_key, None).unwrap();
+        assert_eq!(
+            result.message,
+            "许可证的签名密钥为2048位，低于验证策略要求的3072位"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn reissuing_with_a_deterministic_id_returns_the_existing_license() {
+        let _environment = TestEnvironment::with_keys();
+        clock::set_fixed_time(clock::now());
+        let options = LicenseOptions {
+            deterministic_id: true,
+            ..Default::default()
+        };
+        let first = issue("Deterministic", 30, &options);
+        let second = issue("Deterministic", 30, &options);
+        assert_eq!(first, second);
+
+        let license_id = decode_license_key(&first).unwrap().license_id;
+        let rows = get_all_licenses()
+            .unwrap()
+            .iter()
+            .filter(|license| license.license_id == license_id)
+            .count();
+        assert_eq!(rows, 1);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn delayed_license_is_valid_only_after_not_before() {
+        let _environment = TestEnvironment::with_keys();
+        let issued_at = clock::now();
+        clock::set_fixed_time(issued_at);
+        let options = LicenseOptions {
+            start_offset_days: Some(10),
+            ..Default::default()
+        };
+        let license_key = issue("Not Before Test", 30, &options);
+
+        clock::set_fixed_time(issued_at + Duration::days(9));
+        let result = validate_license(&license_key, None).unwrap();
+        assert!(!result.is_valid);
+        assert_eq!(result.message, "许可证尚未生效");
+
+        // 有效期从生效时间开始计算
+        clock::set_fixed_time(issued_at + Duration::days(39));
+        assert!(validate_license(&license_key, None).unwrap().is_valid);
+        clock::set_fixed_time(issued_at + Duration::days(41));
+        assert!(!validate_license(&license_key, None).unwrap().is_valid);
+    }
+
+    // 按固定列宽折行，模拟邮件客户端对长密钥的处理
+    fn wrap(license_key: &str, width: usize, line_ending: &str) -> String {
+        license_key
+            .as_bytes()
+            .chunks(width)
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect::<Vec<_>>()
+            .join(line_ending)
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn license_key_wrapped_at_64_columns_is_accepted() {
+        let _environment = TestEnvironment::with_keys();
+        let license_key = issue("Wrapped Key", 30, &LicenseOptions::default());
+
+        for line_ending in ["\n", "\r\n"] {
+            let wrapped = wrap(&license_key, 64, line_ending);
+            assert!(wrapped.lines().all(|line| line.trim_end().len() <= 64));
+            assert!(validate_license(&wrapped, None).unwrap().is_valid);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn license_key_with_surrounding_whitespace_is_accepted() {
+        let _environment = TestEnvironment::with_keys();
+        let license_key = issue("Trailing Newline", 30, &LicenseOptions::default());
+
+        for pasted in [
+            format!("{}\r\n", license_key),
+            format!("  {} \r\n\r\n", license_key),
+        ] {
+            assert!(validate_license(&pasted, None).unwrap().is_valid);
+        }
+    }
+
+    #[test]
+    fn malformed_license_key_reports_invalid_format() {
+        let error = decode_license_key("v2:not base64!\r\n").unwrap_err();
+        assert!(error.to_string().contains("许可证格式无效"));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn v2_license_key_round_trips() {
+        let _environment = TestEnvironment::with_keys();
+        let license_key = issue("Version 2", 30, &LicenseOptions::default());
+        assert!(license_key.starts_with("v2:"));
+
+        let info = decode_license_key(&license_key).unwrap();
+        assert_eq!(info.customer_name, "Version 2");
+        assert_eq!(encode_license_key(&info).unwrap(), license_key);
+        assert!(validate_license(&license_key, None).unwrap().is_valid);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn unprefixed_license_key_is_decoded_as_v1() {
+        let _environment = TestEnvironment::with_keys();
+        let license_key = issue("Version 1", 30, &LicenseOptions::default());
+        let legacy_key = license_key.strip_prefix("v2:").unwrap();
+
+        let info = decode_license_key(legacy_key).unwrap();
+        assert_eq!(info.customer_name, "Version 1");
+        assert!(validate_license(legacy_key, None).unwrap().is_valid);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn unknown_license_key_prefix_is_rejected() {
+        let _environment = TestEnvironment::with_keys();
+        let license_key = issue("Unknown Prefix", 30, &LicenseOptions::default());
+        let payload = license_key.strip_prefix("v2:").unwrap();
+
+        let error = decode_license_key(&format!("v9:{}", payload)).unwrap_err();
+        assert!(error.to_string().contains("不支持的许可证格式版本: v9"));
+        let error = decode_license_key(&format!("x2:{}", payload)).unwrap_err();
+        assert!(error.to_string().contains("无法识别的版本前缀 x2"));
+    }
+
+    const HASH_ALGORITHMS: [HashAlgorithm; 3] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha384,
+        HashAlgorithm::Sha512,
+    ];
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn license_signed_with_each_hash_algorithm_round_trips() {
+        let _environment = TestEnvironment::with_keys();
+        for hash_algorithm in HASH_ALGORITHMS {
+            let options = LicenseOptions {
+                hash_algorithm,
+                ..Default::default()
+            };
+            let license_key = issue(hash_algorithm.name(), 30, &options);
+
+            let result = validate_license(&license_key, None).unwrap();
+            assert!(
+                result.is_valid,
+                "{}: {}",
+                hash_algorithm.name(),
+                result.message
+            );
+            assert_eq!(result.info.unwrap().hash_algorithm, hash_algorithm);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn signature_does_not_verify_under_another_hash_algorithm() {
+        let _environment = TestEnvironment::with_keys();
+        let public_key = load_public_key(None).unwrap();
+        for signed_with in HASH_ALGORITHMS {
+            let signature = generate_signature(b"payload", signed_with, None).unwrap();
+            for verified_with in HASH_ALGORITHMS {
+                assert_eq!(
+                    verify_with_key(&public_key, b"payload", &signature, verified_with).unwrap(),
+                    signed_with == verified_with
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn changing_the_recorded_hash_algorithm_invalidates_the_license() {
+        let _environment = TestEnvironment::with_keys();
+        let options = LicenseOptions {
+            hash_algorithm: HashAlgorithm::Sha384,
+            ..Default::default()
+        };
+        let mut info = decode_license_key(&issue("Hash Swap", 30, &options)).unwrap();
+        info.hash_algorithm = HashAlgorithm::Sha512;
+
+        let tampered = encode_license_key(&info).unwrap();
+        assert!(!validate_license(&tampered, None).unwrap().is_valid);
+    }
+
+    #[test]
+    fn constant_time_eq_matches_only_identical_strings() {
+        assert!(constant_time_eq("", ""));
+        assert!(constant_time_eq("0123abcd", "0123abcd"));
+        assert!(!constant_time_eq("0123abcd", "0123abce"));
+        assert!(!constant_time_eq("0123abcd", "0123abc"));
+        assert!(!constant_time_eq("0123abcd", "0123ABCD"));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn machine_bound_license_matches_only_its_machine() {
+        let _environment = TestEnvironment::with_keys();
+        let machine_code = "0123456789abcdef0123456789abcdef";
+        let license_key = generate_license_with_machine_code(
+            "Machine Bound",
+            "customer@example.com",
+            30,
+            vec!["basic".to_string()],
+            &LicenseOptions::default(),
+            &MachineCodeInput {
+                code: machine_code.to_uppercase(),
+                version: 1,
+            },
+            None,
+        )
+        .unwrap()
+        .license_key;
+
+        let result = validate_license_with_machine_code(&license_key, machine_code, None).unwrap();
+        assert!(result.is_valid, "{}", result.message);
+
+        // 只有最后一个字符不同
+        let other_machine = "0123456789abcdef0123456789abcdee";
+        let result = validate_license_with_machine_code(&license_key, other_machine, None).unwrap();
+        assert!(!result.is_valid);
+        assert_eq!(result.message, "许可证与当前机器不匹配");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn partial_binding_is_not_checked_against_a_foreign_machine_code() {
+        let _environment = TestEnvironment::with_keys();
+        let options = LicenseOptions {
+            hardware_binding: BTreeMap::from([(
+                "cpu_id".to_string(),
+                "0123456789abcdef0123456789abcdef".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let license_key = issue("Partial Binding", 30, &options);
+
+        // 其他机器的机器码不能用本机的硬件标识判断，结果与验证所在的机器无关
+        let foreign_machine = "fedcba9876543210fedcba9876543210";
+        let result =
+            validate_license_with_machine_code(&license_key, foreign_machine, None).unwrap();
+        assert!(!result.is_valid);
+        assert!(result
+            .message
+            .contains("部分硬件绑定的许可证只能在其绑定的机器上验证"));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn license_keys_of_every_format_version_validate() {
+        let _environment = TestEnvironment::with_keys();
+        let json_key = issue("JSON Payload", 30, &LicenseOptions::default());
+        let cbor_options = LicenseOptions {
+            payload_format: PayloadFormat::Cbor,
+            ..Default::default()
+        };
+        let cbor_key = issue("CBOR Payload", 30, &cbor_options);
+        assert!(cbor_key.starts_with("v3:"));
+
+        let v1_key = json_key.strip_prefix("v2:").unwrap();
+        for license_key in [v1_key, json_key.as_str(), cbor_key.as_str()] {
+            let result = validate_license(license_key, None).unwrap();
+            assert!(result.is_valid, "{}: {}", license_key, result.message);
+        }
+
+        let info = decode_license_key(&cbor_key).unwrap();
+        assert_eq!(info.customer_name, "CBOR Payload");
+        assert_eq!(encode_license_key(&info).unwrap(), cbor_key);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn cbor_signature_does_not_depend_on_json_field_order() {
+        let _environment = TestEnvironment::with_keys();
+        let options = LicenseOptions {
+            payload_format: PayloadFormat::Cbor,
+            ..Default::default()
+        };
+        let info = decode_license_key(&issue("Field Order", 30, &options)).unwrap();
+
+        // 先转为JSON再以不同方式重新读入，签名数据仍是同一段确定性CBOR
+        let reparsed: LicenseInfo =
+            serde_json::from_str(&serde_json::to_string_pretty(&info).unwrap()).unwrap();
+        assert_eq!(
+            signing_payload(&info).unwrap(),
+            signing_payload(&reparsed).unwrap()
+        );
+        let result = validate_license(&encode_license_key(&reparsed).unwrap(), None).unwrap();
+        assert!(result.is_valid, "{}", result.message);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "embedded-public-key", ignore = "验证使用嵌入的公钥")]
+    fn payload_must_match_its_format_version() {
+        let _environment = TestEnvironment::with_keys();
+        let json_key = issue("JSON Payload", 30, &LicenseOptions::default());
+        let cbor_options = LicenseOptions {
+            payload_format: PayloadFormat::Cbor,
+            ..Default::default()
+        };
+        let cbor_key = issue("CBOR Payload", 30, &cbor_options);
+
+        let json_payload = json_key.strip_prefix("v2:").unwrap();
+        let cbor_payload = cbor_key.strip_prefix("v3:").unwrap();
+        assert!(decode_license_key(&format!("v3:{}", json_payload)).is_err());
+        assert!(decode_license_key(&format!("v2:{}", cbor_payload)).is_err());
+
+        // 声明为JSON格式的载荷不能以v3发布
+        let mut info = decode_license_key(&json_key).unwrap();
+        info.payload_format = PayloadFormat::Json;
+        let value = serde_json::to_value(&info).unwrap();
+        let relabeled = format!(
+            "v3:{}",
+            general_purpose::STANDARD.encode(cbor::to_canonical_vec(&value))
+        );
+        assert!(decode_license_key(&relabeled).is_err());
+    }
+
+    #[test]
+    fn reading_a_key_file_never_writes_sidecars() {
+        let _environment = TestEnvironment::new();
+        let path = get_app_dir().unwrap().join("public_key.pem");
+        fs::write(&path, "-----BEGIN PUBLIC KEY-----\n").unwrap();
+
+        assert!(read_key_file(&path, "公钥").is_ok());
+        assert!(!key_checksum_path(&path).exists());
+        assert!(!key_backup_path(&path).exists());
+    }
+
+    #[test]
+    fn key_file_not_matching_its_checksum_is_rejected() {
+        let _environment = TestEnvironment::new();
+        let path = get_app_dir().unwrap().join("public_key.pem");
+        write_key_file(&path, "original", "公钥").unwrap();
+        fs::write(&path, "tampered").unwrap();
+
+        assert!(matches!(
+            read_key_file(&path, "公钥"),
+            Err(LicenseError::CorruptedKeyFile {
+                backup_available: true,
+                ..
+            })
+        ));
     }
 }
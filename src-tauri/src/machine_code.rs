@@ -1,7 +1,15 @@
+use crate::license::{file_error, get_app_dir, ErrorPayload, LicenseError};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
-use sysinfo::{CpuExt, System, SystemExt};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use sysinfo::{CpuExt, DiskExt, NetworkExt, NetworksExt, System, SystemExt};
 use uuid::Uuid;
 
 #[allow(dead_code)]
@@ -9,6 +17,9 @@ use uuid::Uuid;
 pub enum MachineIdError {
     SystemInfoError(String),
     HashError(String),
+    UnsupportedVersion(u32),
+    // 盐值文件或数据目录读写失败，保留权限不足等具体错误
+    SaltError(LicenseError),
 }
 
 impl fmt::Display for MachineIdError {
@@ -16,14 +27,494 @@ impl fmt::Display for MachineIdError {
         match self {
             MachineIdError::SystemInfoError(e) => write!(f, "获取系统信息错误: {}", e),
             MachineIdError::HashError(e) => write!(f, "计算哈希值错误: {}", e),
+            MachineIdError::UnsupportedVersion(v) => write!(f, "不支持的机器码算法版本: {}", v),
+            MachineIdError::SaltError(e) => write!(f, "机器码盐值错误: {}", e),
         }
     }
 }
 
 impl Error for MachineIdError {}
 
-/// 获取当前机器的唯一标识符
+impl From<MachineIdError> for LicenseError {
+    fn from(error: MachineIdError) -> Self {
+        match error {
+            // 权限不足等文件错误原样返回，前端可按错误码提示
+            MachineIdError::SaltError(e) => e,
+            e => LicenseError::ValidationError(e.to_string()),
+        }
+    }
+}
+
+impl From<MachineIdError> for ErrorPayload {
+    fn from(error: MachineIdError) -> Self {
+        LicenseError::from(error).into()
+    }
+}
+
+/// 当前机器码算法版本，修改算法时递增，并保留旧版本的实现。
+/// 绑定机器的许可证记录计算机器码所用的版本，验证时按该版本重新计算
+pub const CURRENT_MACHINE_CODE_VERSION: u32 = 3;
+
+/// 机器码长度（十六进制字符数）
+pub const MACHINE_CODE_LEN: usize = 32;
+
+/// 获取当前机器的唯一标识符（当前算法版本）
 pub fn get_machine_id() -> Result<String, MachineIdError> {
+    get_machine_id_versioned(CURRENT_MACHINE_CODE_VERSION)
+}
+
+/// 客户提供的机器码及计算它所用的算法版本。签发绑定机器的许可证时版本必须随机器码一起提供，
+/// 旧版客户端计算的机器码按其自身的版本记录，验证时才能重新算出相同的机器码
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MachineCodeInput {
+    pub code: String,
+    pub version: u32,
+}
+
+/// 获取当前机器的机器码及其算法版本，用于签发绑定本机的许可证
+pub fn get_machine_code_input() -> Result<MachineCodeInput, MachineIdError> {
+    Ok(MachineCodeInput {
+        code: get_machine_id()?,
+        version: CURRENT_MACHINE_CODE_VERSION,
+    })
+}
+
+// 分组格式中每组的十六进制字符数，每组后附加一个校验字符
+const FORMATTED_GROUP_LEN: usize = 4;
+
+// 校验字符表（去掉易混淆的 I、L、O、U），取前31个字符
+const CHECK_CHARS: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+// 计算一组机器码的校验字符：按位置加权求和并计入组号，模31后查表。
+// 31为质数，单个字符抄错、相邻字符颠倒或整组换位都会改变校验字符
+fn group_check_char(group: &[u8], index: usize) -> char {
+    let sum = group
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i + 1) * (*c as char).to_digit(16).unwrap_or(0) as usize)
+        .sum::<usize>()
+        + (FORMATTED_GROUP_LEN + 1) * (index + 1);
+    CHECK_CHARS[sum % 31] as char
+}
+
+/// 将32位十六进制机器码转为便于口述的分组格式，如 `1A2B7-3C4DK-...`，
+/// 每4个字符一组并附加一个校验字符
+pub fn format_machine_code(machine_code: &str) -> String {
+    machine_code
+        .to_ascii_uppercase()
+        .as_bytes()
+        .chunks(FORMATTED_GROUP_LEN)
+        .enumerate()
+        .map(|(index, group)| {
+            let mut block = String::from_utf8_lossy(group).into_owned();
+            block.push(group_check_char(group, index));
+            block
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 获取当前机器的分组格式机器码
+pub fn get_machine_id_formatted() -> Result<String, MachineIdError> {
+    get_machine_id().map(|id| format_machine_code(&id))
+}
+
+/// 将分组格式的机器码还原为32位小写十六进制；分隔符和空白可省略，
+/// 口述时常见的 O、I、L 按 0、1、1 处理。输入不是分组格式时返回 Ok(None)
+pub fn parse_formatted_machine_code(input: &str) -> Result<Option<String>, String> {
+    let chars: Vec<u8> = input
+        .bytes()
+        .filter(|c| *c != b'-' && !c.is_ascii_whitespace())
+        .map(|c| match c.to_ascii_uppercase() {
+            b'O' => b'0',
+            b'I' | b'L' => b'1',
+            c => c,
+        })
+        .collect();
+    let group_count = MACHINE_CODE_LEN / FORMATTED_GROUP_LEN;
+    if chars.len() != group_count * (FORMATTED_GROUP_LEN + 1) {
+        return Ok(None);
+    }
+
+    let mut machine_code = String::with_capacity(MACHINE_CODE_LEN);
+    for (index, block) in chars.chunks(FORMATTED_GROUP_LEN + 1).enumerate() {
+        let (group, check) = block.split_at(FORMATTED_GROUP_LEN);
+        if !group.iter().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("第{}组机器码包含非十六进制字符", index + 1));
+        }
+        if check[0] as char != group_check_char(group, index) {
+            return Err(format!("第{}组机器码校验失败，请核对该组字符", index + 1));
+        }
+        machine_code.push_str(&String::from_utf8_lossy(group).to_ascii_lowercase());
+    }
+    Ok(Some(machine_code))
+}
+
+// 已计算的机器码，按算法版本缓存；收集系统信息较慢，验证流程中会反复调用
+static MACHINE_ID_CACHE: Mutex<BTreeMap<u32, String>> = Mutex::new(BTreeMap::new());
+
+/// 使用指定版本的算法计算机器码，结果会被缓存
+pub fn get_machine_id_versioned(version: u32) -> Result<String, MachineIdError> {
+    if let Some(id) = MACHINE_ID_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&version)
+    {
+        return Ok(id.clone());
+    }
+
+    let id = compute_machine_id(version)?;
+    MACHINE_ID_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(version, id.clone());
+    Ok(id)
+}
+
+fn compute_machine_id(version: u32) -> Result<String, MachineIdError> {
+    match version {
+        1 => machine_id_v1(),
+        2 => machine_id_v2(),
+        3 => machine_id_v3(),
+        _ => Err(MachineIdError::UnsupportedVersion(version)),
+    }
+}
+
+/// 清空机器码缓存，数据目录变化（盐值文件随之变化）时调用
+pub fn clear_machine_id_cache() {
+    MACHINE_ID_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clear();
+    *MACHINE_IDENTIFIERS_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+}
+
+/// 清空缓存并重新计算当前版本的机器码（更换硬件后调用）
+pub fn refresh_machine_id() -> Result<String, MachineIdError> {
+    clear_machine_id_cache();
+    get_machine_id()
+}
+
+// 版本1：主机名、系统和CPU信息的SHA-256哈希
+fn machine_id_v1() -> Result<String, MachineIdError> {
+    Ok(hash_machine_info(&collect_machine_info()))
+}
+
+// 版本2：在版本1的系统信息中混入每个安装独有的随机盐值，避免克隆的虚拟机得到相同的机器码
+fn machine_id_v2() -> Result<String, MachineIdError> {
+    let salt = load_or_create_salt()?;
+    Ok(hash_machine_info(&format!(
+        "{}:{}",
+        salt,
+        collect_machine_info()
+    )))
+}
+
+// 版本3：与版本2相同，但CPU型号为空时改用其他来源，且系统信息缺失过多时报错，
+// 避免信息不全的机器得到相同的机器码
+fn machine_id_v3() -> Result<String, MachineIdError> {
+    let salt = load_or_create_salt()?;
+    let mut sys = System::new_all();
+    sys.refresh_all();
+
+    let fields = [
+        sys.host_name(),
+        sys.name(),
+        sys.os_version(),
+        sys.kernel_version(),
+        cpu_brand(&sys),
+    ]
+    .map(|field| field.filter(|value| !value.trim().is_empty()));
+    let unknown = fields.iter().filter(|field| field.is_none()).count();
+    if unknown > MAX_UNKNOWN_FIELDS {
+        return Err(MachineIdError::SystemInfoError(format!(
+            "{}项系统信息无法获取，无法生成可靠的机器码",
+            unknown
+        )));
+    }
+
+    let info = fields
+        .map(|field| field.unwrap_or_else(|| "unknown".to_string()))
+        .join(":");
+    Ok(hash_machine_info(&format!(
+        "{}:{}:{}",
+        salt,
+        info,
+        sys.physical_core_count().unwrap_or(0)
+    )))
+}
+
+// 版本3允许无法获取的系统信息项数（主机名、系统名称、系统版本、内核版本、CPU型号）
+const MAX_UNKNOWN_FIELDS: usize = 2;
+
+// 部分虚拟机和内核上 sysinfo 返回的占位CPU型号
+fn is_placeholder_brand(brand: &str) -> bool {
+    matches!(
+        brand.to_ascii_lowercase().as_str(),
+        "" | "unknown" | "cpu" | "generic" | "virtual cpu"
+    )
+}
+
+// CPU型号：sysinfo 取不到时依次尝试 /proc/cpuinfo 和系统安装时生成的机器UUID
+fn cpu_brand(sys: &System) -> Option<String> {
+    let brand = sys.global_cpu_info().brand().trim();
+    if !is_placeholder_brand(brand) {
+        return Some(brand.to_string());
+    }
+
+    read_cpuinfo_model()
+        .filter(|model| !is_placeholder_brand(model))
+        .or_else(|| read_os_uuid().map(|uuid| format!("os:{}", uuid)))
+}
+
+// /proc/cpuinfo 中的CPU型号，ARM等平台使用 Hardware 或 cpu model 字段
+#[cfg(target_os = "linux")]
+fn read_cpuinfo_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    ["model name", "Hardware", "cpu model", "Processor"]
+        .iter()
+        .find_map(|key| {
+            cpuinfo.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                (name.trim() == *key && !value.trim().is_empty()).then(|| value.trim().to_string())
+            })
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_cpuinfo_model() -> Option<String> {
+    None
+}
+
+// 机器码盐值文件路径
+fn get_salt_path() -> Result<PathBuf, MachineIdError> {
+    get_app_dir()
+        .map(|dir| dir.join("machine_salt"))
+        .map_err(MachineIdError::SaltError)
+}
+
+// 生成新的随机盐值并保存
+fn write_new_salt() -> Result<String, MachineIdError> {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let salt = hex::encode(bytes);
+
+    let path = get_salt_path()?;
+    fs::write(&path, &salt).map_err(|e| {
+        MachineIdError::SaltError(file_error(
+            &e,
+            &path,
+            "写入",
+            format!("写入盐值文件失败: {}", e),
+        ))
+    })?;
+    Ok(salt)
+}
+
+// 读取盐值，首次运行时生成
+fn load_or_create_salt() -> Result<String, MachineIdError> {
+    let path = get_salt_path()?;
+    if !path.exists() {
+        return write_new_salt();
+    }
+
+    let salt = fs::read_to_string(&path).map_err(|e| {
+        MachineIdError::SaltError(file_error(
+            &e,
+            &path,
+            "读取",
+            format!("读取盐值文件失败: {}", e),
+        ))
+    })?;
+    let salt = salt.trim();
+    if salt.is_empty() {
+        return write_new_salt();
+    }
+    Ok(salt.to_string())
+}
+
+/// 重新生成本机的盐值，返回新的机器码。
+/// 版本2及以后的机器码都混入了盐值，重置后都会改变，绑定到这些版本机器码的许可证将不再匹配，
+/// 需要重新签发；版本1的许可证不受影响
+pub fn reset_machine_salt() -> Result<String, MachineIdError> {
+    write_new_salt()?;
+    refresh_machine_id()
+}
+
+/// 分项的机器标识，每项都是独立的哈希，无法获取的项为 None。
+/// 许可证绑定可以只选用其中几项，其余硬件变化时不影响匹配
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MachineIdentifiers {
+    pub cpu_id: Option<String>,
+    pub disk_id: Option<String>,
+    pub mac: Option<String>,
+    pub os_uuid: Option<String>,
+}
+
+/// 可用于部分硬件绑定的机器标识项名称
+pub const MACHINE_IDENTIFIER_COMPONENTS: [&str; 4] = ["cpu_id", "disk_id", "mac", "os_uuid"];
+
+impl MachineIdentifiers {
+    /// 按名称获取标识项，名称无效时返回 None，该项无法获取时返回 Some(None)
+    pub fn component(&self, name: &str) -> Option<Option<&str>> {
+        let value = match name {
+            "cpu_id" => &self.cpu_id,
+            "disk_id" => &self.disk_id,
+            "mac" => &self.mac,
+            "os_uuid" => &self.os_uuid,
+            _ => return None,
+        };
+        Some(value.as_deref())
+    }
+}
+
+// 已计算的分项机器标识，与机器码一样在 clear_machine_id_cache 时清空
+static MACHINE_IDENTIFIERS_CACHE: Mutex<Option<MachineIdentifiers>> = Mutex::new(None);
+
+/// 获取分项的机器标识，结果会被缓存
+pub fn get_machine_identifiers() -> MachineIdentifiers {
+    if let Some(identifiers) = MACHINE_IDENTIFIERS_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .as_ref()
+    {
+        return identifiers.clone();
+    }
+
+    let identifiers = compute_machine_identifiers();
+    *MACHINE_IDENTIFIERS_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(identifiers.clone());
+    identifiers
+}
+
+fn compute_machine_identifiers() -> MachineIdentifiers {
+    let mut sys = System::new_all();
+    sys.refresh_all();
+
+    MachineIdentifiers {
+        cpu_id: cpu_info(&sys).map(|info| hash_identifier("cpu", &info)),
+        disk_id: disk_info(&sys).map(|info| hash_identifier("disk", &info)),
+        mac: mac_info(&sys).map(|info| hash_identifier("mac", &info)),
+        os_uuid: read_os_uuid().map(|info| hash_identifier("os", &info)),
+    }
+}
+
+// 每项标识加上类别前缀后哈希，不同类别的相同原始值得到不同的标识
+fn hash_identifier(kind: &str, value: &str) -> String {
+    hash_machine_info(&format!("{}:{}", kind, value))
+}
+
+// CPU型号、厂商和物理核心数。部分硬件绑定的许可证记录的是该标识的哈希，
+// 不使用版本3的型号回退来源，避免占位型号的机器上已签发的许可证不再匹配
+fn cpu_info(sys: &System) -> Option<String> {
+    let cpu = sys.global_cpu_info();
+    let brand = cpu.brand().trim();
+    if brand.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "{}:{}:{}",
+        brand,
+        cpu.vendor_id().trim(),
+        sys.physical_core_count().unwrap_or(0)
+    ))
+}
+
+// 所有固定磁盘的名称、文件系统和容量，按名称排序保证结果稳定
+fn disk_info(sys: &System) -> Option<String> {
+    let mut disks: Vec<String> = sys
+        .disks()
+        .iter()
+        .filter(|disk| !disk.is_removable())
+        .map(|disk| {
+            format!(
+                "{}:{}:{}",
+                disk.name().to_string_lossy(),
+                String::from_utf8_lossy(disk.file_system()),
+                disk.total_space()
+            )
+        })
+        .collect();
+    if disks.is_empty() {
+        return None;
+    }
+
+    disks.sort();
+    disks.dedup();
+    Some(disks.join(","))
+}
+
+// 取数值最小的有效MAC地址，避免网卡枚举顺序变化影响结果
+fn mac_info(sys: &System) -> Option<String> {
+    sys.networks()
+        .iter()
+        .map(|(_, network)| network.mac_address())
+        .filter(|mac| !mac.is_unspecified())
+        .map(|mac| mac.0)
+        .min()
+        .map(hex::encode)
+}
+
+// 读取操作系统安装时生成的机器UUID
+#[cfg(target_os = "linux")]
+fn read_os_uuid() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .find(|id| !id.is_empty())
+}
+
+#[cfg(target_os = "windows")]
+fn read_os_uuid() -> Option<String> {
+    use std::os::windows::process::CommandExt;
+
+    // 不为 reg 命令弹出控制台窗口
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let output = std::process::Command::new("reg")
+        .creation_flags(CREATE_NO_WINDOW)
+        .args([
+            "query",
+            r"HKLM\SOFTWARE\Microsoft\Cryptography",
+            "/v",
+            "MachineGuid",
+        ])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("MachineGuid"))
+        .and_then(|line| line.split_whitespace().last())
+        .map(|guid| guid.to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn read_os_uuid() -> Option<String> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("IOPlatformUUID"))
+        .and_then(|line| line.split('"').nth(3))
+        .map(|uuid| uuid.to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn read_os_uuid() -> Option<String> {
+    None
+}
+
+// 收集主机名、系统和CPU信息
+fn collect_machine_info() -> String {
     let mut sys = System::new_all();
     sys.refresh_all();
 
@@ -46,11 +537,14 @@ pub fn get_machine_id() -> Result<String, MachineIdError> {
     };
 
     // 组合所有信息
-    let machine_info = format!(
+    format!(
         "{}:{}:{}:{}:{}:{}:{}",
         hostname, os_name, os_version, kernel_version, cpu_brand, cpu_cores, system_uuid
-    );
+    )
+}
 
+// 计算SHA-256哈希值，取前MACHINE_CODE_LEN个十六进制字符作为机器码
+fn hash_machine_info(machine_info: &str) -> String {
     // 计算SHA-256哈希值
     let mut hasher = Sha256::new();
     hasher.update(machine_info.as_bytes());
@@ -58,7 +552,8 @@ pub fn get_machine_id() -> Result<String, MachineIdError> {
 
     // 转换为十六进制字符串，取前32个字符作为机器码
     let hex_string = format!("{:x}", result);
-    let machine_id = hex_string.chars().take(32).collect::<String>();
-
-    Ok(machine_id)
+    hex_string
+        .chars()
+        .take(MACHINE_CODE_LEN)
+        .collect::<String>()
 }
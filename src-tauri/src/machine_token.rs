@@ -0,0 +1,154 @@
+use crate::clock;
+use crate::license::{
+    file_error, generate_private_key, get_app_dir, read_json_file, write_file_atomic,
+    write_json_file, LicenseError,
+};
+use crate::machine_code::get_machine_id;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rand::rngs::OsRng;
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+// 安装密钥位数，仅用于签名机器码令牌
+const INSTALL_KEY_BITS: usize = 2048;
+
+// 令牌默认有效天数
+pub const DEFAULT_TOKEN_MAX_AGE_DAYS: u32 = 7;
+
+// 令牌签发时间允许超前当前时间的秒数
+const TOKEN_CLOCK_TOLERANCE_SECS: i64 = 300;
+
+// 机器码令牌：机器码、生成时间和本机安装密钥的公钥，由安装密钥签名
+#[derive(Debug, Serialize, Deserialize)]
+struct MachineToken {
+    machine_code: String,
+    issued_at: DateTime<Utc>,
+    // SubjectPublicKeyInfo DER 的Base64编码
+    public_key: String,
+    signature: String,
+}
+
+// 安装密钥文件路径，每个安装首次生成令牌时创建
+fn get_install_key_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("install_key.pem"))
+}
+
+// 机器码与首次激活时使用的安装密钥指纹，用于发现冒用的机器码
+fn get_token_key_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("machine_token_keys.json"))
+}
+
+// 读取安装密钥，首次使用时生成
+fn load_or_create_install_key() -> Result<RsaPrivateKey, LicenseError> {
+    let path = get_install_key_path()?;
+    if path.exists() {
+        let pem = fs::read_to_string(&path)
+            .map_err(|e| file_error(&e, &path, "读取", format!("读取安装密钥失败: {}", e)))?;
+        return RsaPrivateKey::from_pkcs8_pem(&pem)
+            .map_err(|e| LicenseError::ValidationError(format!("解析安装密钥失败: {}", e)));
+    }
+
+    let key = generate_private_key(INSTALL_KEY_BITS)?;
+    let pem = key
+        .to_pkcs8_pem(LineEnding::LF)
+        .map_err(|e| LicenseError::SerializationError(format!("编码安装密钥失败: {}", e)))?;
+    write_file_atomic(&path, pem.as_bytes(), "安装密钥")?;
+    Ok(key)
+}
+
+// 令牌的签名数据
+fn token_payload(machine_code: &str, issued_at: &DateTime<Utc>, public_key: &str) -> Vec<u8> {
+    let payload = format!("{}|{}|{}", machine_code, issued_at.to_rfc3339(), public_key);
+    Sha256::digest(payload.as_bytes()).to_vec()
+}
+
+/// 生成本机的机器码令牌（客户端）：用本机安装密钥对机器码和当前时间签名，
+/// 激活时代替机器码发送给签发方
+pub fn generate_machine_token() -> Result<String, LicenseError> {
+    let machine_code =
+        get_machine_id().map_err(|e| LicenseError::ValidationError(e.to_string()))?;
+    let key = load_or_create_install_key()?;
+    let public_key = RsaPublicKey::from(&key)
+        .to_public_key_der()
+        .map_err(|e| LicenseError::SerializationError(format!("编码安装公钥失败: {}", e)))?;
+    let public_key = general_purpose::STANDARD.encode(public_key.as_bytes());
+    let issued_at = clock::now();
+
+    let signature = key
+        .sign_with_rng(
+            &mut OsRng,
+            Pkcs1v15Sign::new::<Sha256>(),
+            &token_payload(&machine_code, &issued_at, &public_key),
+        )
+        .map_err(|e| LicenseError::ValidationError(format!("签名机器码令牌失败: {}", e)))?;
+
+    let token = MachineToken {
+        machine_code,
+        issued_at,
+        public_key,
+        signature: general_purpose::STANDARD.encode(signature),
+    };
+    let json = serde_json::to_string(&token)
+        .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+    Ok(general_purpose::URL_SAFE_NO_PAD.encode(json))
+}
+
+/// 验证机器码令牌（签发方），返回令牌中的机器码。
+/// 检查签名和有效期；同一机器码首次验证时记录其安装密钥，之后必须使用同一密钥，
+/// 其他机器转发的机器码因没有对应的安装密钥而无法通过验证
+pub fn verify_machine_token(token: &str, max_age_days: u32) -> Result<String, LicenseError> {
+    let invalid =
+        |reason: &str| LicenseError::ValidationError(format!("机器码令牌无效: {}", reason));
+
+    let json = general_purpose::URL_SAFE_NO_PAD
+        .decode(token.trim())
+        .map_err(|_| invalid("格式错误"))?;
+    let token: MachineToken = serde_json::from_slice(&json).map_err(|_| invalid("格式错误"))?;
+
+    let der = general_purpose::STANDARD
+        .decode(&token.public_key)
+        .map_err(|_| invalid("公钥格式错误"))?;
+    let public_key =
+        RsaPublicKey::from_public_key_der(&der).map_err(|_| invalid("公钥格式错误"))?;
+    let signature = general_purpose::STANDARD
+        .decode(&token.signature)
+        .map_err(|_| invalid("签名格式错误"))?;
+    public_key
+        .verify(
+            Pkcs1v15Sign::new::<Sha256>(),
+            &token_payload(&token.machine_code, &token.issued_at, &token.public_key),
+            &signature,
+        )
+        .map_err(|_| invalid("签名验证失败"))?;
+
+    let now = clock::now();
+    if token.issued_at - now > Duration::seconds(TOKEN_CLOCK_TOLERANCE_SECS) {
+        return Err(invalid("生成时间晚于当前时间"));
+    }
+    if now - token.issued_at > Duration::days(max_age_days as i64) {
+        return Err(invalid("令牌已过期，请在客户端重新生成"));
+    }
+
+    // 同一机器码只接受首次激活时的安装密钥
+    let fingerprint = hex::encode(Sha256::digest(&der));
+    let path = get_token_key_db_path()?;
+    let mut known: BTreeMap<String, String> = read_json_file(&path, "机器码令牌记录")?;
+    match known.get(&token.machine_code) {
+        Some(existing) if *existing != fingerprint => {
+            return Err(invalid("该机器码此前由另一个安装激活，可能被冒用"));
+        }
+        Some(_) => {}
+        None => {
+            known.insert(token.machine_code.clone(), fingerprint);
+            write_json_file(&path, &known, "机器码令牌记录")?;
+        }
+    }
+
+    Ok(token.machine_code)
+}
@@ -0,0 +1,77 @@
+use crate::audit;
+use crate::license::{file_error, is_signed_by_known_key, LicenseDatabase, LicenseError};
+use crate::store;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+// 合并结果
+#[derive(Debug, Serialize, Default)]
+pub struct MergeReport {
+    // 新增到本地数据库的许可证数量
+    pub merged: usize,
+    // 本地已有且内容相同的许可证数量
+    pub skipped: usize,
+    // 签名无效而被拒绝的许可证ID
+    pub rejected: Vec<String>,
+    // 与本地许可证ID相同但内容不同的许可证ID，保留本地版本
+    pub conflicts: Vec<String>,
+}
+
+// 将另一个许可证数据库（如离线工作的其他机器上的 licenses.json）合并到本地数据库。
+// 每条记录先校验签名；本地没有的许可证加入本地数据库，已有的同ID许可证不会被覆盖
+pub fn merge_database(
+    other_path: &Path,
+    profile: Option<&str>,
+) -> Result<MergeReport, LicenseError> {
+    let contents = fs::read_to_string(other_path).map_err(|e| {
+        file_error(
+            &e,
+            other_path,
+            "读取",
+            format!("读取待合并的数据库失败: {}", e),
+        )
+    })?;
+    let other: LicenseDatabase = serde_json::from_str(&contents)
+        .map_err(|e| LicenseError::SerializationError(format!("解析待合并的数据库失败: {}", e)))?;
+
+    let report = store::update_license_db(|db| {
+        let mut report = MergeReport::default();
+        for license in other.licenses {
+            if !is_signed_by_known_key(&license, profile)? {
+                report.rejected.push(license.license_id);
+                continue;
+            }
+
+            match db
+                .licenses
+                .iter()
+                .find(|l| l.license_id == license.license_id)
+            {
+                // 签名相同即内容相同
+                Some(existing) if existing.signature == license.signature => report.skipped += 1,
+                Some(_) => report.conflicts.push(license.license_id),
+                None => {
+                    db.licenses.push(license);
+                    report.merged += 1;
+                }
+            }
+        }
+        Ok(report)
+    })?;
+
+    audit::record(
+        "merge",
+        "",
+        &format!(
+            "从 {} 合并{}个许可证，跳过{}个，拒绝{}个，冲突{}个",
+            other_path.display(),
+            report.merged,
+            report.skipped,
+            report.rejected.len(),
+            report.conflicts.len()
+        ),
+    )?;
+
+    Ok(report)
+}
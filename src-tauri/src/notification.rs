@@ -0,0 +1,177 @@
+use crate::clock;
+use crate::license::{decode_license_key, get_all_licenses, is_perpetual, LicenseError};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::async_runtime::JoinHandle;
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+// 默认提前提醒的天数
+pub const DEFAULT_WARN_DAYS: u32 = 7;
+
+// 已计划的到期提醒，按许可证ID索引；序号用于区分同一许可证先后的计划
+struct ScheduledNotification {
+    sequence: u64,
+    handle: JoinHandle<()>,
+}
+
+static SCHEDULED: Mutex<Option<HashMap<String, ScheduledNotification>>> = Mutex::new(None);
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// 到期提醒的计划结果
+#[derive(Debug, Serialize)]
+pub struct ExpiryNotificationSchedule {
+    pub license_id: String,
+    pub expiry_date: DateTime<Utc>,
+    // 永久许可证不计划提醒，为 None
+    pub notify_at: Option<DateTime<Utc>>,
+    // 已到提醒时间（包括已过期）时立即发送
+    pub immediate: bool,
+}
+
+// 同一客户有到期时间更晚的许可证时视为已续期
+fn is_renewed(license_id: &str, customer_email: &str, expiry_date: DateTime<Utc>) -> bool {
+    get_all_licenses()
+        .map(|licenses| {
+            licenses.iter().any(|license| {
+                license.license_id != license_id
+                    && license.customer_email.eq_ignore_ascii_case(customer_email)
+                    && license.expiry_date > expiry_date
+            })
+        })
+        .unwrap_or(false)
+}
+
+// 发送系统通知
+fn show_expiry_notification(app: &AppHandle, customer_name: &str, expiry_date: DateTime<Utc>) {
+    let now = clock::now();
+    let body = if expiry_date <= now {
+        format!(
+            "{} 的许可证已于 {} 过期",
+            customer_name,
+            expiry_date.format("%Y-%m-%d")
+        )
+    } else {
+        format!(
+            "{} 的许可证将于 {} 过期，剩余 {} 天",
+            customer_name,
+            expiry_date.format("%Y-%m-%d"),
+            (expiry_date - now).num_days()
+        )
+    };
+
+    let _ = app
+        .notification()
+        .builder()
+        .title("许可证即将到期")
+        .body(body)
+        .show();
+}
+
+/// 计划许可证到期提醒：在到期前 warn_days 天发送系统通知，已到提醒时间的立即发送。
+/// 同一许可证已有计划时会被替换；提醒触发时若该客户已续期则不再发送
+pub fn schedule_expiry_notification(
+    app: AppHandle,
+    license_key: &str,
+    warn_days: u32,
+) -> Result<ExpiryNotificationSchedule, LicenseError> {
+    let info = decode_license_key(license_key)?;
+
+    // 替换同一许可证的旧计划
+    cancel_where(|id, _| id == info.license_id);
+
+    if is_perpetual(&info) {
+        return Ok(ExpiryNotificationSchedule {
+            license_id: info.license_id,
+            expiry_date: info.expiry_date,
+            notify_at: None,
+            immediate: false,
+        });
+    }
+
+    let notify_at = info
+        .expiry_date
+        .checked_sub_signed(Duration::days(i64::from(warn_days)))
+        .ok_or_else(|| LicenseError::ValidationError(format!("提醒天数 {} 超出范围", warn_days)))?;
+    let delay = (notify_at - clock::now()).to_std().unwrap_or_default();
+    let immediate = delay.is_zero();
+
+    let license_id = info.license_id.clone();
+    let expiry_date = info.expiry_date;
+    let sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+
+    // 持有锁直到计划写入列表，避免立即触发的任务先于写入执行移除
+    let mut scheduled = SCHEDULED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let handle = tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if !is_renewed(&info.license_id, &info.customer_email, info.expiry_date) {
+            show_expiry_notification(&app, &info.customer_name, info.expiry_date);
+        }
+        remove_fired(&info.license_id, sequence);
+    });
+    scheduled.get_or_insert_with(HashMap::new).insert(
+        license_id.clone(),
+        ScheduledNotification { sequence, handle },
+    );
+    drop(scheduled);
+
+    Ok(ExpiryNotificationSchedule {
+        license_id,
+        expiry_date,
+        notify_at: Some(notify_at),
+        immediate,
+    })
+}
+
+// 移除已触发的计划，该许可证已被重新计划时保留新计划
+fn remove_fired(license_id: &str, sequence: u64) {
+    let mut scheduled = SCHEDULED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(scheduled) = scheduled.as_mut() {
+        if scheduled
+            .get(license_id)
+            .is_some_and(|notification| notification.sequence == sequence)
+        {
+            scheduled.remove(license_id);
+        }
+    }
+}
+
+// 取消满足条件的计划，返回取消的数量
+fn cancel_where<F>(predicate: F) -> usize
+where
+    F: Fn(&str, &ScheduledNotification) -> bool,
+{
+    let mut scheduled = SCHEDULED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(scheduled) = scheduled.as_mut() else {
+        return 0;
+    };
+
+    let before = scheduled.len();
+    scheduled.retain(|id, notification| {
+        let cancel = predicate(id, notification);
+        if cancel {
+            notification.handle.abort();
+        }
+        !cancel
+    });
+    before - scheduled.len()
+}
+
+/// 取消指定许可证的到期提醒，返回是否存在该计划
+pub fn cancel_expiry_notification(license_id: &str) -> bool {
+    cancel_where(|id, _| id == license_id) > 0
+}
+
+/// 取消所有到期提醒
+pub fn cancel_all_expiry_notifications() {
+    cancel_where(|_, _| true);
+}
@@ -0,0 +1,114 @@
+use crate::license::{
+    export_public_key, public_key_fingerprint, write_file_atomic, LicenseError,
+    CURRENT_LICENSE_KEY_VERSION,
+};
+use serde::Serialize;
+use std::path::Path;
+
+// 离线验证规范的版本，签名方案或规范化规则变化时递增
+const VERIFICATION_SPEC_VERSION: u32 = 1;
+
+/// 离线验证包：公钥和重现签名验证所需的全部规则，供非Rust的验证端（如网页控制台）使用
+#[derive(Debug, Serialize, Clone)]
+pub struct OfflineVerificationBundle {
+    pub spec_version: u32,
+    pub public_key_pem: String,
+    // 公钥SPKI DER的SHA-256指纹（十六进制）
+    pub public_key_fingerprint: String,
+    // 许可证中 kid 字段的取值：指纹的前16位
+    pub kid: String,
+    pub license_key_format: LicenseKeyFormat,
+    pub signature: SignatureScheme,
+    pub canonicalization: Vec<&'static str>,
+    pub validity: Vec<&'static str>,
+}
+
+/// 许可证密钥的编码方式
+#[derive(Debug, Serialize, Clone)]
+pub struct LicenseKeyFormat {
+    pub current_version: u32,
+    pub versions: Vec<&'static str>,
+    pub payload_encoding: &'static str,
+    pub notes: Vec<&'static str>,
+}
+
+/// 签名方案
+#[derive(Debug, Serialize, Clone)]
+pub struct SignatureScheme {
+    pub algorithm: &'static str,
+    // 许可证中指定摘要算法的字段，缺失时使用默认值
+    pub hash_field: &'static str,
+    pub hash_default: &'static str,
+    pub hash_values: Vec<(&'static str, &'static str)>,
+    pub signature_field: &'static str,
+    pub signature_encoding: &'static str,
+}
+
+/// 生成离线验证包，规则与 validate_license 的签名检查一致
+pub fn generate_offline_verification_bundle(
+    profile: Option<&str>,
+) -> Result<OfflineVerificationBundle, LicenseError> {
+    let fingerprint = public_key_fingerprint(profile)?;
+
+    Ok(OfflineVerificationBundle {
+        spec_version: VERIFICATION_SPEC_VERSION,
+        public_key_pem: export_public_key(profile)?,
+        kid: fingerprint[..16].to_string(),
+        public_key_fingerprint: fingerprint,
+        license_key_format: LicenseKeyFormat {
+            current_version: CURRENT_LICENSE_KEY_VERSION,
+            versions: vec![
+                "无前缀：版本1，Base64编码的JSON",
+                "v2:<载荷>：Base64编码的JSON，与版本1相同",
+                "v3:<载荷>：Base64编码的确定性CBOR（RFC 8949 4.2 节：整数最短编码、映射键按编码后的字节序排序、定长编码），解码后与JSON载荷的字段相同，payload_format 字段为 \"cbor\"",
+            ],
+            payload_encoding: "Base64（标准字母表，带填充）编码的UTF-8 JSON",
+            notes: vec![
+                "解码前去除密钥中的所有空白字符（邮件中的换行）",
+                "也接受URL安全字母表（- 和 _）及缺少 = 填充的载荷",
+                "未知的版本前缀视为无效",
+            ],
+        },
+        signature: SignatureScheme {
+            algorithm: "RSASSA-PKCS1-v1_5",
+            hash_field: "hash_algorithm",
+            hash_default: "SHA-256",
+            hash_values: vec![
+                ("sha256", "SHA-256"),
+                ("sha384", "SHA-384"),
+                ("sha512", "SHA-512"),
+            ],
+            signature_field: "signature",
+            signature_encoding: "Base64（标准字母表，带填充）",
+        },
+        canonicalization: vec![
+            "将载荷解析为JSON对象，保留字段原有的顺序",
+            "将 signature 字段设为空字符串 \"\"（字段保留在原位置）",
+            "删除 co_signatures 字段（如存在）",
+            "按原字段顺序序列化为紧凑JSON（无空格、无换行），非ASCII字符原样输出为UTF-8，与 JavaScript 的 JSON.stringify 一致",
+            "对序列化结果的UTF-8字节计算摘要，用公钥验证 signature",
+            "本应用签发的载荷字段顺序即签名时的顺序；手工修改过字段顺序或增加了未知字段的载荷无法按此规则验证",
+            "v3（payload_format 为 \"cbor\"）：同样将 signature 设为空字符串并删除 co_signatures，然后对确定性CBOR编码（而不是JSON）计算摘要，结果与字段顺序无关",
+        ],
+        validity: vec![
+            "kid 字段存在时应与本文件的 kid 一致",
+            "key_fingerprint 字段存在时应与本文件的 public_key_fingerprint 一致，不一致说明应使用其他签发方的公钥",
+            "required_signatures 大于1时，还需验证联合签名，有效签名数（含主签名）须达到该数量（每个联合签名按相同的签名数据用联合签名方公钥验证）；联合签名公钥不包含在本文件中",
+            "expiry_date 早于当前时间即过期；not_before 存在时，早于该时间尚未生效",
+            "issue_date 晚于当前时间超过300秒的许可证无效",
+            "machine_code、hardware_binding 和 single_use 需要本机信息，离线验证端无法检查",
+        ],
+    })
+}
+
+/// 将离线验证包写入JSON文件，返回写入的内容
+pub fn write_offline_verification_bundle(
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<OfflineVerificationBundle, LicenseError> {
+    let bundle = generate_offline_verification_bundle(profile)?;
+    let json = serde_json::to_string_pretty(&bundle)
+        .map_err(|e| LicenseError::SerializationError(format!("序列化离线验证包失败: {}", e)))?;
+    write_file_atomic(path, json.as_bytes(), "离线验证包")?;
+    Ok(bundle)
+}
@@ -0,0 +1,83 @@
+// 全局验证策略，保存在数据目录的 policy.json 中，validate_license 等验证流程统一读取
+use crate::license::{
+    clear_validation_cache, get_app_dir, read_json_file, write_json_file, LicenseError,
+};
+use crate::settings;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// 宽限期天数上限
+const MAX_GRACE_PERIOD_DAYS: u32 = 365;
+
+/// 验证策略，文件不存在时使用默认值（不额外限制，与未引入策略前的行为一致）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ValidationPolicy {
+    // 签名公钥的最小RSA位数，低于该值的许可证验证失败；0表示不检查
+    pub min_key_bits: usize,
+    // 许可证过期后仍视为有效的天数，0表示不允许宽限期
+    pub grace_period_days: u32,
+    // 系统时钟早于可信时间锚点（时钟被回拨）时验证失败，而不是按锚点时间继续验证
+    pub require_trusted_clock: bool,
+    // 受信任的公钥指纹，与设置中的受信任指纹列表都非空时取交集
+    pub trusted_key_fingerprints: Vec<String>,
+}
+
+// 策略文件路径
+fn get_policy_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("policy.json"))
+}
+
+/// 读取验证策略
+pub fn get_validation_policy() -> Result<ValidationPolicy, LicenseError> {
+    read_json_file(&get_policy_path()?, "验证策略")
+}
+
+/// 校验并保存验证策略，公钥指纹统一保存为小写十六进制
+pub fn set_validation_policy(policy: ValidationPolicy) -> Result<(), LicenseError> {
+    if policy.grace_period_days > MAX_GRACE_PERIOD_DAYS {
+        return Err(LicenseError::ValidationError(format!(
+            "宽限期不能超过{}天",
+            MAX_GRACE_PERIOD_DAYS
+        )));
+    }
+    if policy.min_key_bits > 16384 {
+        return Err(LicenseError::ValidationError(
+            "最小密钥位数不能超过16384".to_string(),
+        ));
+    }
+
+    let policy = ValidationPolicy {
+        trusted_key_fingerprints: settings::normalize_fingerprints(
+            policy.trusted_key_fingerprints,
+        )?,
+        ..policy
+    };
+    write_json_file(&get_policy_path()?, &policy, "验证策略")?;
+    // 受信任指纹变化后缓存的签名校验结果不再可靠
+    clear_validation_cache();
+    Ok(())
+}
+
+/// 当前生效的受信任公钥指纹，None 表示不限制。策略和设置中都配置了指纹时取两者的交集，
+/// 只配置了其中一处时以该列表为准；因此任一文件被改写都不能把新的公钥加入信任范围
+pub fn trusted_key_fingerprints(
+    policy: &ValidationPolicy,
+) -> Result<Option<Vec<String>>, LicenseError> {
+    let from_settings = settings::get_trusted_key_fingerprints()?;
+    let trusted = match (
+        policy.trusted_key_fingerprints.is_empty(),
+        from_settings.is_empty(),
+    ) {
+        (true, true) => return Ok(None),
+        (false, true) => policy.trusted_key_fingerprints.clone(),
+        (true, false) => from_settings,
+        (false, false) => policy
+            .trusted_key_fingerprints
+            .iter()
+            .filter(|fingerprint| from_settings.contains(fingerprint))
+            .cloned()
+            .collect(),
+    };
+    Ok(Some(trusted))
+}
@@ -0,0 +1,70 @@
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// 二维码的纠错等级，等级越高容量越小
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QrErrorCorrection {
+    L,
+    #[default]
+    M,
+    Q,
+    H,
+}
+
+impl QrErrorCorrection {
+    /// 最大尺寸（版本40）的二维码在字节模式下可容纳的字节数
+    pub fn byte_capacity(&self) -> usize {
+        match self {
+            QrErrorCorrection::L => 2953,
+            QrErrorCorrection::M => 2331,
+            QrErrorCorrection::Q => 1663,
+            QrErrorCorrection::H => 1273,
+        }
+    }
+}
+
+/// 许可证密钥的大小信息，界面据此决定是否拆分为多个二维码
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LicenseSizeInfo {
+    // 去除空白后的密钥长度（字节）
+    pub raw_bytes: usize,
+    // 密钥经 DEFLATE 压缩后的长度（字节）
+    pub compressed_bytes: usize,
+    pub error_correction: QrErrorCorrection,
+    pub qr_capacity: usize,
+    pub fits_single_qr: bool,
+    // 按单个二维码容量拆分时需要的二维码数量
+    pub qr_codes_needed: usize,
+}
+
+// DEFLATE压缩后的长度
+fn compressed_len(data: &[u8]) -> usize {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    // 写入内存缓冲区不会失败
+    if encoder.write_all(data).is_err() {
+        return data.len();
+    }
+    encoder.finish().map(|out| out.len()).unwrap_or(data.len())
+}
+
+/// 计算许可证密钥的原始和压缩后大小，以及能否放入指定纠错等级的单个二维码
+pub fn license_size_info(
+    license_key: &str,
+    error_correction: QrErrorCorrection,
+) -> LicenseSizeInfo {
+    // 与解码时一致，忽略密钥中的空白和换行
+    let compact: String = license_key.chars().filter(|c| !c.is_whitespace()).collect();
+    let raw_bytes = compact.len();
+    let qr_capacity = error_correction.byte_capacity();
+
+    LicenseSizeInfo {
+        raw_bytes,
+        compressed_bytes: compressed_len(compact.as_bytes()),
+        error_correction,
+        qr_capacity,
+        fits_single_qr: raw_bytes <= qr_capacity,
+        qr_codes_needed: raw_bytes.div_ceil(qr_capacity).max(1),
+    }
+}
@@ -0,0 +1,139 @@
+// 托管签发方的远程公钥：从HTTPS地址下载公钥并固定其指纹，之后的下载必须得到同一个公钥。
+// 固定记录防的是下载地址被劫持或托管方被攻破后换成别的公钥；记录和公钥一样保存在可写的数据目录中，
+// 能修改数据目录的本地用户同时删除 pinned_keys.json 和 public_key.pem 即可重新首次信任，
+// 需要抵御本地篡改时应使用 embedded-public-key 功能把公钥编译进程序
+use crate::clock;
+use crate::license::{
+    clear_validation_cache, get_app_dir, install_public_key, public_key_pem_fingerprint,
+    read_json_file, write_json_file, LicenseError,
+};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// 公钥下载的超时时间
+const FETCH_TIMEOUT_SECS: u64 = 10;
+
+// 公钥PEM的最大字节数，16384位RSA公钥的PEM也不超过4KB
+const MAX_PUBLIC_KEY_BYTES: usize = 16 * 1024;
+
+/// 已固定的远程公钥
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinnedPublicKey {
+    pub url: String,
+    pub fingerprint: String,
+    pub pinned_at: DateTime<Utc>,
+}
+
+// 固定记录文件路径，按配置文件名称保存
+fn get_pinned_keys_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("pinned_keys.json"))
+}
+
+fn load_pinned_keys() -> Result<BTreeMap<String, PinnedPublicKey>, LicenseError> {
+    read_json_file(&get_pinned_keys_path()?, "公钥固定记录")
+}
+
+fn profile_key(profile: Option<&str>) -> String {
+    profile.unwrap_or("default").to_string()
+}
+
+/// 配置文件已固定的公钥指纹，未固定时返回 None
+pub fn pinned_fingerprint(profile: Option<&str>) -> Result<Option<String>, LicenseError> {
+    Ok(load_pinned_keys()?
+        .remove(&profile_key(profile))
+        .map(|pinned| pinned.fingerprint))
+}
+
+// 下载公钥PEM，只接受HTTPS地址
+async fn fetch_public_key_pem(url: &Url) -> Result<String, LicenseError> {
+    // 只允许HTTPS，重定向到HTTP地址时请求失败
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .https_only(true)
+        .build()
+        .map_err(|e| LicenseError::ValidationError(format!("创建下载客户端失败: {}", e)))?;
+    let mut response = client
+        .get(url.clone())
+        .send()
+        .await
+        .map_err(|e| LicenseError::ValidationError(format!("下载公钥失败: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(LicenseError::ValidationError(format!(
+            "下载公钥失败: HTTP状态码 {}",
+            response.status()
+        )));
+    }
+
+    // 先按声明的长度拒绝，再边读边检查，不缓存超出上限的内容
+    let too_large = || LicenseError::ValidationError("下载的公钥文件过大".to_string());
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_PUBLIC_KEY_BYTES as u64)
+    {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| LicenseError::ValidationError(format!("下载公钥失败: {}", e)))?
+    {
+        if body.len() + chunk.len() > MAX_PUBLIC_KEY_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    String::from_utf8(body)
+        .map_err(|_| LicenseError::ValidationError("下载的公钥不是文本格式".to_string()))
+}
+
+/// 从HTTPS地址下载签发方的公钥（PEM）并固定其指纹，返回固定记录。
+/// 首次调用时安装公钥并记录指纹；之后的调用（包括更换地址）下载到的公钥指纹必须与已固定的一致，
+/// 否则视为密钥替换并拒绝。验证许可证时，已固定指纹的配置文件只信任该公钥
+pub async fn fetch_and_pin_public_key(
+    url: &str,
+    profile: Option<&str>,
+) -> Result<PinnedPublicKey, LicenseError> {
+    let parsed = Url::parse(url.trim())
+        .map_err(|e| LicenseError::ValidationError(format!("公钥地址无效: {}", e)))?;
+    if parsed.scheme() != "https" {
+        return Err(LicenseError::ValidationError(
+            "公钥地址必须使用HTTPS".to_string(),
+        ));
+    }
+
+    let public_key_pem = fetch_public_key_pem(&parsed).await?;
+    let mut pinned_keys = load_pinned_keys()?;
+    let key = profile_key(profile);
+    let fingerprint = public_key_pem_fingerprint(&public_key_pem)?;
+    let pinned = match pinned_keys.get(&key) {
+        Some(pinned) if pinned.fingerprint != fingerprint => {
+            return Err(LicenseError::ValidationError(format!(
+                "下载的公钥指纹 {} 与已固定的指纹 {} 不一致，可能遭到密钥替换，已拒绝",
+                fingerprint, pinned.fingerprint
+            )));
+        }
+        // 已固定时保留首次固定的时间，只更新地址
+        Some(pinned) => PinnedPublicKey {
+            url: parsed.to_string(),
+            ..pinned.clone()
+        },
+        None => PinnedPublicKey {
+            url: parsed.to_string(),
+            fingerprint: fingerprint.clone(),
+            pinned_at: clock::now(),
+        },
+    };
+    // 本地已有不同的公钥时拒绝安装
+    install_public_key(&public_key_pem, Some(&fingerprint), profile)?;
+
+    pinned_keys.insert(key, pinned.clone());
+    write_json_file(&get_pinned_keys_path()?, &pinned_keys, "公钥固定记录")?;
+    clear_validation_cache();
+
+    Ok(pinned)
+}
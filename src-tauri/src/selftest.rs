@@ -0,0 +1,178 @@
+use crate::license::{
+    self, decode_license_key, encode_license_key, generate_license, LicenseError, LicenseOptions,
+};
+use crate::store::{self, FileStore};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// 自检使用的密钥长度，只验证流程，不需要生产环境的密钥强度
+const SELF_TEST_KEY_BITS: usize = 2048;
+
+/// 自检的单个步骤
+#[derive(Debug, Serialize, Clone)]
+pub struct SelfTestStep {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// 自检报告，所有步骤通过时 passed 为 true
+#[derive(Debug, Serialize, Clone)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    // 记录步骤结果，返回该步骤是否通过
+    fn record(&mut self, name: &'static str, result: Result<String, String>) -> bool {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        self.steps.push(SelfTestStep {
+            name,
+            passed,
+            detail,
+        });
+        passed
+    }
+}
+
+// 自检使用的临时环境：创建时把全局数据目录和存储后端切换到临时目录，释放时（包括panic时）恢复。
+// 数据目录是进程级的，自检期间本进程的其他读写都会落到临时目录，因此只在独立的 --self-test 命令行进程中使用
+struct SelfTestEnvironment {
+    temp_dir: PathBuf,
+    previous_dir: Option<PathBuf>,
+    previous_store: Arc<dyn store::LicenseStore>,
+    _lock: store::LicenseDbLock,
+}
+
+impl SelfTestEnvironment {
+    fn enter() -> Self {
+        let lock = store::lock_license_db();
+        let temp_dir = std::env::temp_dir().join(format!("drilling-self-test-{}", Uuid::new_v4()));
+        let previous_dir = license::data_dir_override();
+        let previous_store = store::current_store();
+        license::set_data_dir_override(Some(temp_dir.clone()));
+        store::set_store(Arc::new(FileStore));
+
+        SelfTestEnvironment {
+            temp_dir,
+            previous_dir,
+            previous_store,
+            _lock: lock,
+        }
+    }
+}
+
+impl Drop for SelfTestEnvironment {
+    fn drop(&mut self) {
+        store::set_store(self.previous_store.clone());
+        license::set_data_dir_override(self.previous_dir.take());
+        let _ = fs::remove_dir_all(&self.temp_dir);
+    }
+}
+
+/// 在临时数据目录中完成一次密钥生成、签发、验证和篡改检测，不读写真实的密钥和数据库。
+/// 自检期间全局数据目录指向临时目录，完成后恢复，不能在运行中的应用进程内调用
+pub fn self_test() -> SelfTestReport {
+    let _environment = SelfTestEnvironment::enter();
+
+    let mut report = SelfTestReport {
+        passed: false,
+        steps: Vec::new(),
+    };
+    run_steps(&mut report);
+    report.passed = report.steps.iter().all(|step| step.passed);
+
+    report
+}
+
+// 依次执行各步骤，某一步失败时跳过后续步骤
+fn run_steps(report: &mut SelfTestReport) {
+    let key_pair = license::generate_new_key_pair(SELF_TEST_KEY_BITS, None, false);
+    let public_key = key_pair
+        .as_ref()
+        .map(|pair| pair.public_key.clone())
+        .map_err(|e| e.to_string());
+    if !report.record(
+        "generate_key_pair",
+        public_key
+            .clone()
+            .map(|_| format!("已生成{}位密钥对", SELF_TEST_KEY_BITS)),
+    ) {
+        return;
+    }
+    let public_key = public_key.unwrap_or_default();
+
+    let license_key = generate_license(
+        "Self Test",
+        "self-test@example.com",
+        30,
+        vec!["self_test".to_string()],
+        &LicenseOptions::default(),
+        None,
+    )
+    .map(|issuance| issuance.license_key)
+    .map_err(|e| e.to_string());
+    if !report.record(
+        "issue_license",
+        license_key
+            .as_ref()
+            .map(|key| format!("许可证密钥长度 {} 字节", key.len()))
+            .map_err(|e| e.clone()),
+    ) {
+        return;
+    }
+    let license_key = license_key.unwrap_or_default();
+
+    let valid = check_key(&license_key, &public_key);
+    if !report.record(
+        "validate_license",
+        match valid {
+            Ok(true) => Ok("许可证验证通过".to_string()),
+            Ok(false) => Err("新签发的许可证未通过验证".to_string()),
+            Err(e) => Err(e),
+        },
+    ) {
+        return;
+    }
+
+    let tampered = tamper(&license_key).map_err(|e| e.to_string());
+    let tampered_valid = tampered.and_then(|key| check_key(&key, &public_key));
+    report.record(
+        "reject_tampered_license",
+        match tampered_valid {
+            Ok(false) => Ok("篡改后的许可证被拒绝".to_string()),
+            Ok(true) => Err("篡改后的许可证仍然通过了验证".to_string()),
+            Err(e) => Err(e),
+        },
+    );
+}
+
+// 修改客户名称但保留原签名
+fn tamper(license_key: &str) -> Result<String, LicenseError> {
+    let mut info = decode_license_key(license_key)?;
+    info.customer_name.push_str(" (tampered)");
+    encode_license_key(&info)
+}
+
+// 验证许可证密钥
+#[cfg(not(feature = "embedded-public-key"))]
+fn check_key(license_key: &str, _public_key: &str) -> Result<bool, String> {
+    license::validate_license(license_key, None)
+        .map(|result| result.is_valid)
+        .map_err(|e| e.to_string())
+}
+
+// 嵌入公钥的构建中 validate_license 始终使用嵌入的公钥，改为用临时密钥对的公钥验证签名
+#[cfg(feature = "embedded-public-key")]
+fn check_key(license_key: &str, public_key: &str) -> Result<bool, String> {
+    decode_license_key(license_key)
+        .and_then(|info| license::verify_license_with_public_key(&info, public_key))
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,270 @@
+use crate::license::{
+    clear_validation_cache, get_app_dir, read_json_file, write_json_file, LicenseError,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// 应用设置，持久化到 settings.json
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AppSettings {
+    // 签发许可证后通知的Webhook地址
+    pub webhook_url: Option<String>,
+    // 功能注册表：功能名称 -> 位序号（0-63），用于压缩功能列表
+    pub feature_registry: BTreeMap<String, u8>,
+    // 签发许可证时的数量和大小限制
+    pub license_limits: LicenseLimits,
+    // 运行时指定的更新地址，为空时使用编译时配置的地址
+    pub update_endpoint: Option<String>,
+    // 签发许可证时默认写入的签发方名称
+    pub default_issuer: Option<String>,
+    // 受信任的公钥指纹（SHA-256，十六进制），当前和已停用的公钥都要在列表中；为空时信任磁盘上的公钥
+    pub trusted_key_fingerprints: Vec<String>,
+    // 签发方允许的功能目录，为空时不检查功能名称
+    pub feature_catalog: FeatureCatalog,
+}
+
+// 功能目录的检查方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureCatalogMode {
+    // 拒绝签发包含未知功能的许可证
+    Strict,
+    // 允许签发，仅给出警告
+    #[default]
+    Lenient,
+}
+
+// 功能目录：签发时用于发现拼写错误的功能名称
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FeatureCatalog {
+    pub features: Vec<String>,
+    pub mode: FeatureCatalogMode,
+}
+
+// 签发限制，避免生成无法传输（如无法放入二维码）的超大许可证
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct LicenseLimits {
+    // 单个许可证的最大功能数量
+    pub max_features: usize,
+    // 许可证密钥的最大长度（字节）
+    pub max_key_bytes: usize,
+}
+
+impl Default for LicenseLimits {
+    fn default() -> Self {
+        LicenseLimits {
+            max_features: 256,
+            max_key_bytes: 16 * 1024,
+        }
+    }
+}
+
+// 设置文件路径
+fn get_settings_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("settings.json"))
+}
+
+// 加载应用设置
+pub fn load_settings() -> Result<AppSettings, LicenseError> {
+    read_json_file(&get_settings_path()?, "设置文件")
+}
+
+// 保存应用设置
+fn save_settings(settings: &AppSettings) -> Result<(), LicenseError> {
+    write_json_file(&get_settings_path()?, settings, "设置文件")
+}
+
+// 设置Webhook地址，只接受 http/https 地址
+pub fn set_webhook_url(url: &str) -> Result<(), LicenseError> {
+    let parsed = Url::parse(url.trim())
+        .map_err(|e| LicenseError::ValidationError(format!("无效的Webhook地址: {}", e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(LicenseError::ValidationError(
+            "Webhook地址必须使用http或https协议".to_string(),
+        ));
+    }
+
+    let mut settings = load_settings()?;
+    settings.webhook_url = Some(parsed.to_string());
+    save_settings(&settings)
+}
+
+// 清除Webhook地址
+pub fn clear_webhook_url() -> Result<(), LicenseError> {
+    let mut settings = load_settings()?;
+    settings.webhook_url = None;
+    save_settings(&settings)
+}
+
+// 检查功能注册表：名称不能为空，位序号必须小于64且互不重复
+fn validate_feature_registry(registry: &BTreeMap<String, u8>) -> Result<(), LicenseError> {
+    let mut used_bits = 0u64;
+    for (name, bit) in registry {
+        if name.trim().is_empty() {
+            return Err(LicenseError::ValidationError(
+                "功能名称不能为空".to_string(),
+            ));
+        }
+        let Some(flag) = 1u64.checked_shl(u32::from(*bit)) else {
+            return Err(LicenseError::ValidationError(format!(
+                "功能 {} 的位序号 {} 超出范围（0-63）",
+                name, bit
+            )));
+        };
+        if used_bits & flag != 0 {
+            return Err(LicenseError::ValidationError(format!(
+                "位序号 {} 被多个功能使用",
+                bit
+            )));
+        }
+        used_bits |= flag;
+    }
+    Ok(())
+}
+
+// 设置功能注册表
+pub fn set_feature_registry(registry: BTreeMap<String, u8>) -> Result<(), LicenseError> {
+    validate_feature_registry(&registry)?;
+
+    let mut settings = load_settings()?;
+    settings.feature_registry = registry;
+    save_settings(&settings)
+}
+
+// 获取功能注册表，手工编辑过的注册表同样要通过检查
+pub fn get_feature_registry() -> Result<BTreeMap<String, u8>, LicenseError> {
+    let registry = load_settings()?.feature_registry;
+    validate_feature_registry(&registry)?;
+    Ok(registry)
+}
+
+// 设置签发限制，限制值必须大于0
+pub fn set_license_limits(limits: LicenseLimits) -> Result<(), LicenseError> {
+    if limits.max_features == 0 || limits.max_key_bytes == 0 {
+        return Err(LicenseError::ValidationError(
+            "签发限制必须大于0".to_string(),
+        ));
+    }
+
+    let mut settings = load_settings()?;
+    settings.license_limits = limits;
+    save_settings(&settings)
+}
+
+// 获取签发限制
+pub fn get_license_limits() -> Result<LicenseLimits, LicenseError> {
+    Ok(load_settings()?.license_limits)
+}
+
+// 设置更新地址，为空时恢复使用编译时配置的地址；除非显式允许，否则只接受https地址
+pub fn set_update_endpoint(url: Option<&str>, allow_insecure: bool) -> Result<(), LicenseError> {
+    let endpoint = match url.map(str::trim).filter(|u| !u.is_empty()) {
+        Some(url) => {
+            let parsed = Url::parse(url)
+                .map_err(|e| LicenseError::ValidationError(format!("无效的更新地址: {}", e)))?;
+            match parsed.scheme() {
+                "https" => {}
+                "http" if allow_insecure => {}
+                "http" => {
+                    return Err(LicenseError::ValidationError(
+                        "更新地址必须使用https协议".to_string(),
+                    ))
+                }
+                _ => {
+                    return Err(LicenseError::ValidationError(
+                        "更新地址必须使用http或https协议".to_string(),
+                    ))
+                }
+            }
+            Some(parsed.to_string())
+        }
+        None => None,
+    };
+
+    let mut settings = load_settings()?;
+    settings.update_endpoint = endpoint;
+    save_settings(&settings)
+}
+
+// 设置默认签发方，为空时清除
+pub fn set_default_issuer(issuer: Option<&str>) -> Result<(), LicenseError> {
+    let mut settings = load_settings()?;
+    settings.default_issuer = issuer
+        .map(str::trim)
+        .filter(|issuer| !issuer.is_empty())
+        .map(|issuer| issuer.to_string());
+    save_settings(&settings)
+}
+
+// 规范化公钥指纹：去除冒号和空白并转为小写，校验为64位十六进制SHA-256指纹，去除重复项
+pub(crate) fn normalize_fingerprints(
+    fingerprints: Vec<String>,
+) -> Result<Vec<String>, LicenseError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(fingerprints.len());
+    for fingerprint in fingerprints {
+        let fingerprint: String = fingerprint
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ':')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if fingerprint.len() != 64 || !fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(LicenseError::ValidationError(format!(
+                "无效的公钥指纹: {}，应为64位十六进制SHA-256指纹",
+                fingerprint
+            )));
+        }
+        if !normalized.contains(&fingerprint) {
+            normalized.push(fingerprint);
+        }
+    }
+    Ok(normalized)
+}
+
+// 设置受信任的公钥指纹，指纹可带冒号或空格分隔，统一保存为小写十六进制；为空时取消限制
+pub fn set_trusted_key_fingerprints(fingerprints: Vec<String>) -> Result<(), LicenseError> {
+    let normalized = normalize_fingerprints(fingerprints)?;
+
+    let mut settings = load_settings()?;
+    settings.trusted_key_fingerprints = normalized;
+    save_settings(&settings)?;
+    clear_validation_cache();
+    Ok(())
+}
+
+// 获取受信任的公钥指纹
+pub fn get_trusted_key_fingerprints() -> Result<Vec<String>, LicenseError> {
+    Ok(load_settings()?.trusted_key_fingerprints)
+}
+
+// 设置功能目录，功能名称去除首尾空白并去重；功能列表为空时不检查
+pub fn set_feature_catalog(catalog: FeatureCatalog) -> Result<(), LicenseError> {
+    let mut features: Vec<String> = Vec::with_capacity(catalog.features.len());
+    for feature in catalog.features {
+        let feature = feature.trim();
+        if feature.is_empty() {
+            return Err(LicenseError::ValidationError(
+                "功能名称不能为空".to_string(),
+            ));
+        }
+        if !features.iter().any(|f| f == feature) {
+            features.push(feature.to_string());
+        }
+    }
+
+    let mut settings = load_settings()?;
+    settings.feature_catalog = FeatureCatalog {
+        features,
+        mode: catalog.mode,
+    };
+    save_settings(&settings)
+}
+
+// 获取功能目录
+pub fn get_feature_catalog() -> Result<FeatureCatalog, LicenseError> {
+    Ok(load_settings()?.feature_catalog)
+}
@@ -0,0 +1,185 @@
+use crate::clock;
+use crate::license::{
+    decode_license_key, encode_license_key, generate_license, get_app_dir, get_license_by_id,
+    normalize_machine_code, read_json_file, redeem_license, validate_license_with_machine_code,
+    write_json_file, LicenseError, LicenseOptions, LicenseValidationResult,
+};
+use crate::store;
+use chrono::{DateTime, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// Crockford Base32字符集：不含 I L O U，便于电话中口述
+const CROCKFORD_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+// 兑换码长度（字符数），每个字符5位，共60位随机数
+const SHORT_CODE_LEN: usize = 12;
+
+// 兑换码记录：对应的许可证，以及兑换所在的机器
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ShortCodeRecord {
+    license_id: String,
+    created_at: DateTime<Utc>,
+    redeemed_by: Option<String>,
+    redeemed_at: Option<DateTime<Utc>>,
+}
+
+// 兑换码的兑换结果
+#[derive(Debug, Serialize)]
+pub struct ShortCodeRedemption {
+    // 应保存到客户端的许可证密钥（首次使用绑定后为重新签名的密钥）
+    pub license_key: String,
+    pub result: LicenseValidationResult,
+}
+
+// 签发的许可证密钥及其兑换码
+#[derive(Debug, Serialize)]
+pub struct IssuedLicense {
+    pub license_key: String,
+    pub short_code: String,
+    // 签发时的警告，见 LicenseIssuance
+    pub warnings: Vec<String>,
+}
+
+// 兑换码文件路径
+fn get_short_code_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("short_codes.json"))
+}
+
+// 整理用户输入的兑换码：去除分隔符和空白，转为大写，并按Crockford规则纠正易混淆的字符
+fn normalize_short_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+// 生成随机兑换码
+fn random_short_code() -> String {
+    let bits = OsRng.next_u64();
+    (0..SHORT_CODE_LEN)
+        .map(|i| CROCKFORD_ALPHABET[((bits >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+// 兑换码按4个字符一组显示，如 ABCD-EFGH-JKMN
+fn format_short_code(code: &str) -> String {
+    code.as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 为数据库中的许可证创建兑换码，返回分组显示的兑换码
+pub fn create_short_code(license_id: &str) -> Result<String, LicenseError> {
+    if get_license_by_id(license_id)?.is_none() {
+        return Err(LicenseError::NotFound(license_id.to_string()));
+    }
+
+    let _lock = store::lock_license_db();
+    let path = get_short_code_db_path()?;
+    let mut codes: BTreeMap<String, ShortCodeRecord> = read_json_file(&path, "兑换码记录")?;
+    let code = loop {
+        let code = random_short_code();
+        if !codes.contains_key(&code) {
+            break code;
+        }
+    };
+
+    codes.insert(
+        code.clone(),
+        ShortCodeRecord {
+            license_id: license_id.to_string(),
+            created_at: clock::now(),
+            redeemed_by: None,
+            redeemed_at: None,
+        },
+    );
+    write_json_file(&path, &codes, "兑换码记录")?;
+
+    Ok(format_short_code(&code))
+}
+
+/// 签发许可证并同时创建兑换码，用于无法扫描二维码时通过电话激活
+pub fn generate_license_with_short_code(
+    customer_name: &str,
+    customer_email: &str,
+    expiry_days: u32,
+    features: Vec<String>,
+    options: &LicenseOptions,
+    profile: Option<&str>,
+) -> Result<IssuedLicense, LicenseError> {
+    let issuance = generate_license(
+        customer_name,
+        customer_email,
+        expiry_days,
+        features,
+        options,
+        profile,
+    )?;
+    let short_code = create_short_code(&decode_license_key(&issuance.license_key)?.license_id)?;
+
+    Ok(IssuedLicense {
+        license_key: issuance.license_key,
+        short_code,
+        warnings: issuance.warnings,
+    })
+}
+
+/// 兑换兑换码：查找对应的许可证并按机器码验证（一次性许可证同时完成兑换，首次使用绑定的许可证同时绑定）。
+/// 兑换码只能在一台机器上兑换，同一台机器可以重复兑换以重新获取许可证密钥
+pub fn redeem_short_code(
+    code: &str,
+    machine_code: &str,
+    profile: Option<&str>,
+) -> Result<ShortCodeRedemption, LicenseError> {
+    let machine_code = normalize_machine_code(machine_code)?;
+    let code = normalize_short_code(code);
+
+    // 读取、检查和写入兑换码记录期间持有数据库锁，避免同一兑换码在两台机器上同时兑换成功
+    let _lock = store::lock_license_db();
+    let path = get_short_code_db_path()?;
+    let mut codes: BTreeMap<String, ShortCodeRecord> = read_json_file(&path, "兑换码记录")?;
+    let record = codes
+        .get_mut(&code)
+        .ok_or_else(|| LicenseError::ValidationError("兑换码无效".to_string()))?;
+
+    let redeemed_here = match record.redeemed_by.as_deref() {
+        Some(redeemed_by) if redeemed_by != machine_code => {
+            return Err(LicenseError::ValidationError(
+                "兑换码已在其他机器上兑换".to_string(),
+            ))
+        }
+        Some(_) => true,
+        None => false,
+    };
+
+    let info = get_license_by_id(&record.license_id)?
+        .ok_or_else(|| LicenseError::ValidationError("兑换码对应的许可证不存在".to_string()))?;
+    let license_key = encode_license_key(&info)?;
+
+    let result = if info.single_use && !redeemed_here {
+        redeem_license(&license_key, &machine_code, profile)?
+    } else {
+        validate_license_with_machine_code(&license_key, &machine_code, profile)?
+    };
+
+    if result.is_valid && !redeemed_here {
+        record.redeemed_by = Some(machine_code);
+        record.redeemed_at = Some(clock::now());
+        write_json_file(&path, &codes, "兑换码记录")?;
+    }
+
+    Ok(ShortCodeRedemption {
+        license_key: result.bound_license_key.clone().unwrap_or(license_key),
+        result,
+    })
+}
@@ -0,0 +1,66 @@
+// 模拟许可证验证状态，供前端调试各状态的界面；仅在调试构建且启用 simulate-validation 功能时编译
+use crate::clock;
+use crate::license::{LicenseInfo, LicenseTier, LicenseValidationResult};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+/// 可模拟的验证状态
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulatedValidationState {
+    Valid,
+    // 许可证已过期但仍在宽限期内
+    InGrace,
+    Expired,
+    InvalidSignature,
+    Revoked,
+}
+
+/// 返回指定状态的示例验证结果，不读取密钥和数据库
+pub fn simulate_validation_state(state: SimulatedValidationState) -> LicenseValidationResult {
+    let now = clock::now();
+    let expiry_date = match state {
+        SimulatedValidationState::Valid
+        | SimulatedValidationState::InvalidSignature
+        | SimulatedValidationState::Revoked => now + Duration::days(30),
+        SimulatedValidationState::InGrace => now - Duration::days(3),
+        SimulatedValidationState::Expired => now - Duration::days(30),
+    };
+    let info = LicenseInfo {
+        license_id: "simulated".to_string(),
+        customer_name: "模拟用户".to_string(),
+        customer_email: "simulated@localhost".to_string(),
+        issue_date: now - Duration::days(335),
+        expiry_date,
+        features: vec!["simulated".to_string()],
+        tier: LicenseTier::Pro,
+        ..Default::default()
+    };
+
+    let (is_valid, message) = match state {
+        SimulatedValidationState::Valid => (true, "许可证有效"),
+        SimulatedValidationState::InGrace => (true, "许可证已过期，当前处于宽限期，请尽快续期"),
+        SimulatedValidationState::Expired => (false, "许可证已过期"),
+        SimulatedValidationState::InvalidSignature => (false, "许可证签名无效"),
+        SimulatedValidationState::Revoked => (false, "许可证已被吊销"),
+    };
+    // 签名无效时真实验证不会返回许可证内容
+    let info = (state != SimulatedValidationState::InvalidSignature).then_some(info);
+    let kid = match state {
+        SimulatedValidationState::InvalidSignature => "unknown",
+        _ => "simulated",
+    };
+
+    LicenseValidationResult {
+        is_valid,
+        tier: info.as_ref().map(|info| info.tier),
+        issuer: None,
+        info,
+        message: message.to_string(),
+        kid: Some(kid.to_string()),
+        bound_license_key: None,
+        expired_features: Vec::new(),
+        feature_status: None,
+        in_grace_period: state == SimulatedValidationState::InGrace,
+    }
+}
@@ -0,0 +1,372 @@
+use crate::license::{
+    file_error, get_license_db_path, write_file_atomic, LicenseDatabase, LicenseError,
+    CURRENT_DB_SCHEMA_VERSION,
+};
+use serde_json::Value;
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::Read;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+/// 许可证数据库的存储后端
+pub trait LicenseStore: Send + Sync {
+    fn load(&self) -> Result<LicenseDatabase, LicenseError>;
+    fn save(&self, db: &LicenseDatabase) -> Result<(), LicenseError>;
+
+    /// 逐条读取未解析的许可证记录，单条记录损坏时不影响读取其他记录（用于完整性检查）
+    fn load_raw(&self) -> Result<Vec<serde_json::Value>, LicenseError> {
+        self.load()?
+            .licenses
+            .iter()
+            .map(|license| {
+                serde_json::to_value(license)
+                    .map_err(|e| LicenseError::SerializationError(e.to_string()))
+            })
+            .collect()
+    }
+}
+
+// 数据库文件的外层结构，许可证记录保持未解析状态
+#[derive(serde::Deserialize)]
+struct RawLicenseDatabase {
+    licenses: Vec<serde_json::Value>,
+}
+
+/// 默认后端：数据目录下的 licenses.json
+pub struct FileStore;
+
+// 读取数据库文件内容，文件不存在时返回 None
+fn read_db_file() -> Result<Option<String>, LicenseError> {
+    let db_path = get_license_db_path()?;
+
+    if !db_path.exists() {
+        return Ok(None);
+    }
+
+    let mut file = File::open(&db_path)
+        .map_err(|e| file_error(&e, &db_path, "读取", format!("打开数据库文件失败: {}", e)))?;
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .map_err(|e| file_error(&e, &db_path, "读取", format!("读取数据库文件失败: {}", e)))?;
+
+    Ok(Some(contents))
+}
+
+// 将旧版结构的数据库逐版本升级到当前版本
+fn migrate_database(db: &mut Value, from_version: u32) -> Result<(), LicenseError> {
+    for version in from_version..CURRENT_DB_SCHEMA_VERSION {
+        match version {
+            // 版本1 -> 2：增加 schema_version 字段；许可证记录此后新增的字段都有默认值，无需改写
+            1 => {}
+            _ => {
+                return Err(LicenseError::SerializationError(format!(
+                    "不支持从版本{}升级数据库",
+                    version
+                )))
+            }
+        }
+    }
+
+    let Some(object) = db.as_object_mut() else {
+        return Err(LicenseError::SerializationError(
+            "数据库格式无效".to_string(),
+        ));
+    };
+    object.insert(
+        "schema_version".to_string(),
+        Value::from(CURRENT_DB_SCHEMA_VERSION),
+    );
+    Ok(())
+}
+
+// 数据库的结构版本，缺少该字段的为版本1；版本高于当前程序支持的版本时报错
+fn schema_version(value: &Value) -> Result<u32, LicenseError> {
+    let version = value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(1) as u32;
+
+    if version > CURRENT_DB_SCHEMA_VERSION {
+        return Err(LicenseError::SerializationError(format!(
+            "数据库版本{}高于当前程序支持的版本{}，请升级程序",
+            version, CURRENT_DB_SCHEMA_VERSION
+        )));
+    }
+    Ok(version)
+}
+
+// 解析数据库文件内容，返回其结构版本
+fn parse_db_contents(contents: &str) -> Result<(Value, u32), LicenseError> {
+    let value: Value = serde_json::from_str(contents).map_err(db_parse_error)?;
+    let version = schema_version(&value)?;
+    Ok((value, version))
+}
+
+/// 将其他来源（如加密备份）的数据库升级到当前结构版本，不读写数据库文件
+pub(crate) fn upgrade_database(mut value: Value) -> Result<LicenseDatabase, LicenseError> {
+    let version = schema_version(&value)?;
+    if version < CURRENT_DB_SCHEMA_VERSION {
+        migrate_database(&mut value, version)?;
+    }
+    serde_json::from_value(value).map_err(db_parse_error)
+}
+
+fn db_parse_error(e: serde_json::Error) -> LicenseError {
+    LicenseError::SerializationError(format!("解析数据库失败: {}", e))
+}
+
+impl LicenseStore for FileStore {
+    fn load(&self) -> Result<LicenseDatabase, LicenseError> {
+        let Some(contents) = read_db_file()? else {
+            return Ok(LicenseDatabase::default());
+        };
+        let (value, version) = parse_db_contents(&contents)?;
+        if version == CURRENT_DB_SCHEMA_VERSION {
+            return serde_json::from_value(value).map_err(db_parse_error);
+        }
+
+        // 升级会改写数据库文件，只读的调用方也会走到这里，备份、升级和保存需持有数据库锁。
+        // 获取锁期间其他线程可能已完成升级，因此加锁后重新读取
+        let _lock = lock_license_db();
+        let Some(contents) = read_db_file()? else {
+            return Ok(LicenseDatabase::default());
+        };
+        let (mut value, version) = parse_db_contents(&contents)?;
+        if version == CURRENT_DB_SCHEMA_VERSION {
+            return serde_json::from_value(value).map_err(db_parse_error);
+        }
+
+        // 升级前备份原文件，如 licenses.json.v1.bak
+        let db_path = get_license_db_path()?;
+        let backup_path = db_path.with_file_name(format!("licenses.json.v{}.bak", version));
+        fs::copy(&db_path, &backup_path).map_err(|e| {
+            file_error(
+                &e,
+                &backup_path,
+                "写入",
+                format!("备份旧版数据库失败: {}", e),
+            )
+        })?;
+
+        migrate_database(&mut value, version)?;
+        let db: LicenseDatabase = serde_json::from_value(value).map_err(db_parse_error)?;
+        self.save(&db)?;
+        Ok(db)
+    }
+
+    fn load_raw(&self) -> Result<Vec<serde_json::Value>, LicenseError> {
+        let Some(contents) = read_db_file()? else {
+            return Ok(vec![]);
+        };
+
+        serde_json::from_str::<RawLicenseDatabase>(&contents)
+            .map(|db| db.licenses)
+            .map_err(db_parse_error)
+    }
+
+    fn save(&self, db: &LicenseDatabase) -> Result<(), LicenseError> {
+        let db_path = get_license_db_path()?;
+
+        let json = serde_json::to_string_pretty(db)
+            .map_err(|e| LicenseError::SerializationError(format!("序列化数据库失败: {}", e)))?;
+
+        write_file_atomic(&db_path, json.as_bytes(), "数据库")
+    }
+}
+
+/// 内存后端：不读写磁盘，进程退出后数据丢失（用于测试和临时使用）
+#[derive(Default)]
+pub struct InMemoryStore {
+    db: Mutex<LicenseDatabase>,
+}
+
+impl LicenseStore for InMemoryStore {
+    fn load(&self) -> Result<LicenseDatabase, LicenseError> {
+        let db = self
+            .db
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Ok(db.clone())
+    }
+
+    fn save(&self, db: &LicenseDatabase) -> Result<(), LicenseError> {
+        let mut stored = self
+            .db
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *stored = db.clone();
+        Ok(())
+    }
+}
+
+// 当前使用的存储后端，首次使用时按环境选择
+static STORE: RwLock<Option<Arc<dyn LicenseStore>>> = RwLock::new(None);
+
+// 测试构建或设置 DRILLING_EPHEMERAL=1 时使用内存后端
+fn use_ephemeral_store() -> bool {
+    cfg!(test) || std::env::var("DRILLING_EPHEMERAL").is_ok_and(|v| v == "1")
+}
+
+/// 获取当前存储后端
+pub fn current_store() -> Arc<dyn LicenseStore> {
+    if let Some(store) = STORE
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .as_ref()
+    {
+        return store.clone();
+    }
+
+    let mut store = STORE
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    store
+        .get_or_insert_with(|| {
+            if use_ephemeral_store() {
+                Arc::new(InMemoryStore::default())
+            } else {
+                Arc::new(FileStore)
+            }
+        })
+        .clone()
+}
+
+/// 替换存储后端（用于测试和自检）
+pub fn set_store(store: Arc<dyn LicenseStore>) {
+    let mut current = STORE
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *current = Some(store);
+}
+
+// 数据库“读取-修改-保存”的锁，异步命令可能在多个线程上同时修改数据库
+static LICENSE_DB_WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    // 当前线程是否已持有数据库锁，用于支持同一线程内嵌套加锁
+    static LICENSE_DB_LOCK_HELD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 数据库锁，持有期间其他线程不能修改数据库；同一线程内可以嵌套获取
+pub struct LicenseDbLock {
+    guard: Option<MutexGuard<'static, ()>>,
+}
+
+impl Drop for LicenseDbLock {
+    fn drop(&mut self) {
+        if self.guard.take().is_some() {
+            LICENSE_DB_LOCK_HELD.set(false);
+        }
+    }
+}
+
+/// 获取数据库锁，需要在修改数据库前后保持一致状态的流程（如签发、自检）使用
+pub fn lock_license_db() -> LicenseDbLock {
+    if LICENSE_DB_LOCK_HELD.get() {
+        return LicenseDbLock { guard: None };
+    }
+
+    let guard = LICENSE_DB_WRITE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    LICENSE_DB_LOCK_HELD.set(true);
+    LicenseDbLock { guard: Some(guard) }
+}
+
+/// 在数据库锁内读取、修改并保存数据库，闭包返回错误时不保存。
+/// 所有修改数据库的流程都必须通过此函数，否则并发写入时可能丢失其他流程的修改
+pub fn update_license_db<T>(
+    update: impl FnOnce(&mut LicenseDatabase) -> Result<T, LicenseError>,
+) -> Result<T, LicenseError> {
+    let _lock = lock_license_db();
+    let store = current_store();
+    let mut db = store.load()?;
+    let result = update(&mut db)?;
+    store.save(&db)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license::LicenseTier;
+    use crate::test_support::TestEnvironment;
+
+    // 版本1的数据库：没有 schema_version，许可证记录只有最初的字段
+    const V1_DATABASE: &str = r#"{
+  "licenses": [
+    {
+      "license_id": "7d0c5c1e-3f4e-4a4b-9a57-1a2f3b4c5d6e",
+      "customer_name": "Legacy Customer",
+      "customer_email": "legacy@example.com",
+      "issue_date": "2023-01-01T00:00:00Z",
+      "expiry_date": "2024-01-01T00:00:00Z",
+      "features": ["basic", "export"],
+      "signature": "c2lnbmF0dXJl",
+      "machine_code": null
+    }
+  ]
+}"#;
+
+    #[test]
+    fn v1_database_is_migrated_and_backed_up() {
+        let _environment = TestEnvironment::new();
+        let db_path = get_license_db_path().unwrap();
+        fs::write(&db_path, V1_DATABASE).unwrap();
+
+        let db = FileStore.load().unwrap();
+        assert_eq!(db.schema_version, CURRENT_DB_SCHEMA_VERSION);
+        assert_eq!(db.licenses.len(), 1);
+        let license = &db.licenses[0];
+        assert_eq!(license.customer_name, "Legacy Customer");
+        assert_eq!(license.features, vec!["basic", "export"]);
+        assert_eq!(license.tier, LicenseTier::default());
+        assert_eq!(license.machine_code_version, None);
+
+        // 原文件备份为 licenses.json.v1.bak，升级后的文件带有当前版本号
+        let backup = db_path.with_file_name("licenses.json.v1.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), V1_DATABASE);
+        let migrated: Value = serde_json::from_str(&fs::read_to_string(&db_path).unwrap()).unwrap();
+        assert_eq!(
+            migrated["schema_version"],
+            Value::from(CURRENT_DB_SCHEMA_VERSION)
+        );
+        assert_eq!(
+            migrated["licenses"][0]["license_id"],
+            "7d0c5c1e-3f4e-4a4b-9a57-1a2f3b4c5d6e"
+        );
+    }
+
+    #[test]
+    fn v1_database_from_a_backup_is_upgraded_without_writing_files() {
+        let _environment = TestEnvironment::new();
+        let db = upgrade_database(serde_json::from_str(V1_DATABASE).unwrap()).unwrap();
+        assert_eq!(db.schema_version, CURRENT_DB_SCHEMA_VERSION);
+        assert_eq!(db.licenses[0].customer_name, "Legacy Customer");
+
+        let db_path = get_license_db_path().unwrap();
+        assert!(!db_path.exists());
+        assert!(!db_path.with_file_name("licenses.json.v1.bak").exists());
+    }
+
+    #[test]
+    fn newer_database_version_is_refused() {
+        let _environment = TestEnvironment::new();
+        let contents = format!(
+            r#"{{"schema_version": {}, "licenses": []}}"#,
+            CURRENT_DB_SCHEMA_VERSION + 1
+        );
+        fs::write(get_license_db_path().unwrap(), &contents).unwrap();
+
+        assert!(matches!(
+            FileStore.load(),
+            Err(LicenseError::SerializationError(_))
+        ));
+        // 无法识别的版本不改写原文件
+        assert_eq!(
+            fs::read_to_string(get_license_db_path().unwrap()).unwrap(),
+            contents
+        );
+    }
+}
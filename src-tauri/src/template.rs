@@ -0,0 +1,152 @@
+use crate::license::{
+    file_error, generate_license, generate_licenses_with_machine_codes, get_app_dir,
+    BulkLicenseIssuance, LicenseError, LicenseIssuance, LicenseOptions, LicenseTier,
+};
+use crate::machine_code::MachineCodeInput;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// 许可证模板：常用的有效期、功能和等级组合
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LicenseTemplate {
+    pub name: String,
+    pub expiry_days: u32,
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub tier: LicenseTier,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct TemplateDatabase {
+    templates: Vec<LicenseTemplate>,
+}
+
+// 模板文件路径
+fn get_template_db_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("license_templates.json"))
+}
+
+// 加载模板数据库
+fn load_template_db() -> Result<TemplateDatabase, LicenseError> {
+    let db_path = get_template_db_path()?;
+
+    if !db_path.exists() {
+        return Ok(TemplateDatabase::default());
+    }
+
+    let contents = fs::read_to_string(&db_path)
+        .map_err(|e| file_error(&e, &db_path, "读取", format!("读取模板文件失败: {}", e)))?;
+
+    serde_json::from_str(&contents)
+        .map_err(|e| LicenseError::SerializationError(format!("解析模板文件失败: {}", e)))
+}
+
+// 保存模板数据库
+fn save_template_db(db: &TemplateDatabase) -> Result<(), LicenseError> {
+    let json = serde_json::to_string_pretty(db)
+        .map_err(|e| LicenseError::SerializationError(format!("序列化模板失败: {}", e)))?;
+
+    let db_path = get_template_db_path()?;
+    fs::write(&db_path, json)
+        .map_err(|e| file_error(&e, &db_path, "写入", format!("写入模板文件失败: {}", e)))
+}
+
+// 保存模板，同名模板会被覆盖
+pub fn save_template(template: LicenseTemplate) -> Result<(), LicenseError> {
+    let name = template.name.trim().to_string();
+    if name.is_empty() {
+        return Err(LicenseError::ValidationError(
+            "模板名称不能为空".to_string(),
+        ));
+    }
+
+    let template = LicenseTemplate { name, ..template };
+    let mut db = load_template_db()?;
+    match db.templates.iter_mut().find(|t| t.name == template.name) {
+        Some(existing) => *existing = template,
+        None => db.templates.push(template),
+    }
+
+    save_template_db(&db)
+}
+
+// 获取所有模板
+pub fn get_templates() -> Result<Vec<LicenseTemplate>, LicenseError> {
+    Ok(load_template_db()?.templates)
+}
+
+// 按名称获取模板
+pub fn get_template(name: &str) -> Result<LicenseTemplate, LicenseError> {
+    load_template_db()?
+        .templates
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| LicenseError::ValidationError(format!("模板不存在: {}", name)))
+}
+
+// 删除模板
+pub fn delete_template(name: &str) -> Result<(), LicenseError> {
+    let mut db = load_template_db()?;
+
+    let index = db.templates.iter().position(|t| t.name == name);
+    match index {
+        Some(idx) => {
+            db.templates.remove(idx);
+            save_template_db(&db)
+        }
+        None => Err(LicenseError::ValidationError(format!(
+            "模板不存在: {}",
+            name
+        ))),
+    }
+}
+
+// 使用模板签发许可证
+pub fn generate_license_from_template(
+    template_name: &str,
+    customer_name: &str,
+    customer_email: &str,
+    profile: Option<&str>,
+) -> Result<LicenseIssuance, LicenseError> {
+    let template = get_template(template_name)?;
+    let options = LicenseOptions {
+        tier: template.tier,
+        ..Default::default()
+    };
+
+    generate_license(
+        customer_name,
+        customer_email,
+        template.expiry_days,
+        template.features,
+        &options,
+        profile,
+    )
+}
+
+// 使用模板为一批机器各签发一个绑定该机器的许可证，全部成功后一次性保存；
+// 返回机器码到许可证密钥的映射和签发警告
+pub fn generate_licenses_for_machines(
+    template_name: &str,
+    customer_name: &str,
+    customer_email: &str,
+    machine_codes: &[MachineCodeInput],
+    profile: Option<&str>,
+) -> Result<BulkLicenseIssuance, LicenseError> {
+    let template = get_template(template_name)?;
+    let options = LicenseOptions {
+        tier: template.tier,
+        ..Default::default()
+    };
+
+    generate_licenses_with_machine_codes(
+        customer_name,
+        customer_email,
+        template.expiry_days,
+        template.features,
+        &options,
+        machine_codes,
+        profile,
+    )
+}
@@ -0,0 +1,54 @@
+// 单元测试共用的临时环境，只在测试构建中编译
+use crate::clock;
+use crate::license::{self, generate_private_key, install_key_pair};
+use rsa::RsaPrivateKey;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use uuid::Uuid;
+
+// 测试密钥长度，取生成较快的2048位；默认验证策略不检查密钥位数
+const TEST_KEY_BITS: usize = 2048;
+
+// 数据目录和时钟是进程级的全局状态，使用它们的测试依次执行
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+// 所有测试共用一个密钥，生成RSA密钥较慢
+static TEST_KEY: OnceLock<RsaPrivateKey> = OnceLock::new();
+
+/// 测试环境：创建时把数据目录指向新的临时目录，释放时恢复系统时钟和数据目录并删除临时目录
+pub(crate) struct TestEnvironment {
+    dir: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TestEnvironment {
+    /// 不含密钥的空数据目录
+    pub(crate) fn new() -> Self {
+        let guard = TEST_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let dir = std::env::temp_dir().join(format!("drilling-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("创建测试数据目录失败");
+        license::set_data_dir_override(Some(dir.clone()));
+
+        TestEnvironment { dir, _guard: guard }
+    }
+
+    /// 安装了测试密钥对的数据目录
+    pub(crate) fn with_keys() -> Self {
+        let environment = Self::new();
+        let key =
+            TEST_KEY.get_or_init(|| generate_private_key(TEST_KEY_BITS).expect("生成测试密钥失败"));
+        install_key_pair(key, None, false).expect("安装测试密钥失败");
+        environment
+    }
+}
+
+impl Drop for TestEnvironment {
+    fn drop(&mut self) {
+        clock::reset_clock();
+        license::set_data_dir_override(None);
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
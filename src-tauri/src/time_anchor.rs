@@ -0,0 +1,97 @@
+use crate::clock;
+use crate::license::{
+    get_app_dir, read_json_file, sign_data, verify_data, write_json_file, LicenseError,
+};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// 签名数据前缀，避免其他用途的签名被当作时间锚点
+const ANCHOR_SIGNING_PREFIX: &str = "drilling-time-anchor:";
+
+// 签发方签名的时间锚点：签名时的时间，证明当前时间不早于该时刻
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimeAnchor {
+    pub timestamp: DateTime<Utc>,
+    pub signature: String,
+}
+
+// 已安装的时间锚点文件路径
+fn get_anchor_path() -> Result<PathBuf, LicenseError> {
+    Ok(get_app_dir()?.join("time_anchor.json"))
+}
+
+fn anchor_signing_data(timestamp: &DateTime<Utc>) -> String {
+    format!("{}{}", ANCHOR_SIGNING_PREFIX, timestamp.to_rfc3339())
+}
+
+// 构建时间，由 build.rs 写入
+fn build_time() -> Option<DateTime<Utc>> {
+    env!("DRILLING_BUILD_TIMESTAMP")
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+}
+
+/// 用签发密钥为当前时间签名，返回时间锚点令牌（签发方）
+pub fn create_time_anchor(profile: Option<&str>) -> Result<String, LicenseError> {
+    let timestamp = clock::now();
+    let anchor = TimeAnchor {
+        timestamp,
+        signature: sign_data(&anchor_signing_data(&timestamp), profile)?,
+    };
+    let json = serde_json::to_string(&anchor)
+        .map_err(|e| LicenseError::SerializationError(e.to_string()))?;
+    Ok(general_purpose::STANDARD.encode(json))
+}
+
+/// 验证并安装时间锚点令牌（客户端），只保留最新的锚点，返回当前生效的锚点时间
+pub fn install_time_anchor(
+    token: &str,
+    profile: Option<&str>,
+) -> Result<DateTime<Utc>, LicenseError> {
+    let invalid = || LicenseError::ValidationError("时间锚点格式无效".to_string());
+    let json = general_purpose::STANDARD
+        .decode(token.trim())
+        .map_err(|_| invalid())?;
+    let anchor: TimeAnchor = serde_json::from_slice(&json).map_err(|_| invalid())?;
+
+    if !verify_data(
+        &anchor_signing_data(&anchor.timestamp),
+        &anchor.signature,
+        profile,
+    )? {
+        return Err(LicenseError::ValidationError(
+            "时间锚点签名无效".to_string(),
+        ));
+    }
+
+    let path = get_anchor_path()?;
+    let current: Option<TimeAnchor> = read_json_file(&path, "时间锚点")?;
+    match current {
+        Some(current) if current.timestamp >= anchor.timestamp => Ok(current.timestamp),
+        _ => {
+            write_json_file(&path, &Some(anchor.clone()), "时间锚点")?;
+            Ok(anchor.timestamp)
+        }
+    }
+}
+
+/// 当前可信的时间下限：已安装的时间锚点和构建时间中较晚的一个
+pub fn get_time_anchor() -> Option<DateTime<Utc>> {
+    // 锚点文件在安装时已验证签名；读取失败时只使用构建时间
+    let installed = get_anchor_path()
+        .and_then(|path| read_json_file::<Option<TimeAnchor>>(&path, "时间锚点"))
+        .ok()
+        .flatten()
+        .map(|anchor| anchor.timestamp);
+
+    installed.into_iter().chain(build_time()).max()
+}
+
+/// 用于有效期判断的当前时间：max(系统时间, 时间锚点)，回拨系统时钟无法绕过过期检查
+pub fn trusted_now() -> DateTime<Utc> {
+    let now = clock::now();
+    get_time_anchor().map_or(now, |anchor| anchor.max(now))
+}
@@ -0,0 +1,534 @@
+// 按IANA时区计算本地日期的结束时刻，时区数据读取系统的 zoneinfo（TZif）文件；
+// 系统没有时区数据库（如Windows）时使用内置的各时区当前规则
+use crate::license::LicenseError;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use std::fs;
+use std::path::PathBuf;
+
+// 系统时区数据库的常见位置，设置 TZDIR 环境变量时优先使用
+const ZONEINFO_DIRS: [&str; 3] = [
+    "/usr/share/zoneinfo",
+    "/usr/lib/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+];
+
+// 一天的秒数
+const SECS_PER_DAY: i64 = 86_400;
+
+// 内置的时区规则，每行为时区名称和该时区当前的POSIX TZ规则，由 tzdata 生成。
+// 只有当前规则而没有历史转换记录，按规则变更之前的日期计算时可能与系统数据库不同
+const BUNDLED_RULES: &str = include_str!("tz_rules.txt");
+
+/// 时区：历史转换记录，以及最后一次转换之后适用的POSIX规则
+pub struct TimeZone {
+    // (转换时刻的Unix时间戳, 转换后的UTC偏移秒数)，按时间排序
+    transitions: Vec<(i64, i32)>,
+    // 第一次转换之前的UTC偏移
+    initial_offset: i32,
+    // TZif文件末尾的POSIX TZ规则，没有时沿用最后一次转换的偏移
+    rule: Option<PosixRule>,
+}
+
+// POSIX TZ规则，偏移已转换为东正西负的秒数
+struct PosixRule {
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+struct DstRule {
+    offset: i32,
+    start: RuleDate,
+    // 开始时刻，以标准时间表示的当天秒数
+    start_time: i32,
+    end: RuleDate,
+    // 结束时刻，以夏令时表示的当天秒数
+    end_time: i32,
+}
+
+enum RuleDate {
+    // Jn：1-365，不计2月29日
+    Julian(u32),
+    // n：0-365，计2月29日
+    ZeroBased(u32),
+    // Mm.w.d：m月第w个星期d（w=5表示最后一个，d=0表示星期日）
+    MonthWeekDay(u32, u32, u32),
+}
+
+impl TimeZone {
+    /// 按IANA名称（如 America/Los_Angeles）加载时区，UTC 不需要时区数据库
+    pub fn load(name: &str) -> Result<TimeZone, LicenseError> {
+        let name = name.trim();
+        if matches!(name, "UTC" | "Etc/UTC" | "Etc/GMT" | "GMT" | "Z") {
+            return Ok(TimeZone {
+                transitions: Vec::new(),
+                initial_offset: 0,
+                rule: None,
+            });
+        }
+
+        let is_valid_name = !name.is_empty()
+            && !name.split('/').any(|part| part.is_empty() || part == "..")
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+'));
+        if !is_valid_name {
+            return Err(LicenseError::ValidationError(format!(
+                "无效的时区名称: {}",
+                name
+            )));
+        }
+
+        let Some(data) = zoneinfo_dirs()
+            .into_iter()
+            .find_map(|dir| fs::read(dir.join(name)).ok())
+        else {
+            return TimeZone::from_bundled_rules(name).ok_or_else(|| {
+                LicenseError::ValidationError(format!(
+                    "找不到时区 {}，请确认时区名称正确，或通过 TZDIR 指定时区数据库的位置",
+                    name
+                ))
+            });
+        };
+
+        parse_tzif(&data)
+            .ok_or_else(|| LicenseError::ValidationError(format!("时区数据文件格式无效: {}", name)))
+    }
+
+    // 使用内置的时区规则，没有该时区时返回 None
+    fn from_bundled_rules(name: &str) -> Option<TimeZone> {
+        let rule = BUNDLED_RULES
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .find_map(|line| {
+                let (zone, rule) = line.split_once(' ')?;
+                (zone == name).then_some(rule)
+            })
+            .and_then(parse_posix_rule)?;
+
+        Some(TimeZone {
+            transitions: Vec::new(),
+            initial_offset: rule.std_offset,
+            rule: Some(rule),
+        })
+    }
+
+    /// 指定时刻（Unix时间戳）的UTC偏移秒数，东正西负
+    pub fn offset_at(&self, timestamp: i64) -> i32 {
+        let index = self.transitions.partition_point(|(at, _)| *at <= timestamp);
+        if index < self.transitions.len() {
+            return match index {
+                0 => self.initial_offset,
+                _ => self.transitions[index - 1].1,
+            };
+        }
+
+        // 最后一次转换之后（或没有转换记录时）按POSIX规则计算
+        match &self.rule {
+            Some(rule) => rule.offset_at(timestamp),
+            None => self
+                .transitions
+                .last()
+                .map_or(self.initial_offset, |(_, offset)| *offset),
+        }
+    }
+
+    /// 本地日期的最后一秒（23:59:59）对应的UTC时刻。
+    ///
+    /// 夏令时边界在午夜时：若次日 00:00 因拨快而不存在，当天在拨快的时刻结束；
+    /// 若拨回导致 23:00-23:59 出现两次，当天在第二次 23:59:59 结束
+    pub fn end_of_day(&self, date: NaiveDate) -> Result<DateTime<Utc>, LicenseError> {
+        let next_day = date
+            .succ_opt()
+            .ok_or_else(|| LicenseError::ValidationError("日期超出范围".to_string()))?;
+        // 次日零点的本地时间，按UTC解释得到的时间戳
+        let next_midnight = next_day
+            .and_hms_opt(0, 0, 0)
+            .map(|time| time.and_utc().timestamp())
+            .ok_or_else(|| LicenseError::ValidationError("日期超出范围".to_string()))?;
+
+        // 当天结束时刻 T 满足 T = 次日零点 - 偏移，且 T 的前一秒使用该偏移（本地时间为 23:59:59）。
+        // 偏移相差不超过一天，取前后一天的偏移作为候选
+        let candidates = [
+            self.offset_at(next_midnight - SECS_PER_DAY),
+            self.offset_at(next_midnight),
+            self.offset_at(next_midnight + SECS_PER_DAY),
+        ];
+        let end = candidates
+            .iter()
+            .map(|offset| next_midnight - *offset as i64)
+            .filter(|end| {
+                let offset = next_midnight - end;
+                self.offset_at(end - 1) as i64 == offset
+            })
+            .max()
+            .ok_or_else(|| {
+                LicenseError::ValidationError(format!("无法确定 {} 的结束时刻", date))
+            })?;
+
+        DateTime::from_timestamp(end - 1, 0)
+            .ok_or_else(|| LicenseError::ValidationError("日期超出范围".to_string()))
+    }
+}
+
+// 查找时区数据库的目录
+fn zoneinfo_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(dir) = std::env::var("TZDIR") {
+        if !dir.trim().is_empty() {
+            dirs.push(PathBuf::from(dir));
+        }
+    }
+    dirs.extend(ZONEINFO_DIRS.iter().map(PathBuf::from));
+    dirs
+}
+
+// 按顺序读取大端整数的游标
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.u32().map(|v| v as i32)
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        self.take(8)
+            .map(|b| i64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+}
+
+// TZif头部中的各项数量：isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt
+struct TzifCounts([usize; 6]);
+
+fn read_header(reader: &mut Reader) -> Option<(u8, TzifCounts)> {
+    if reader.take(4)? != b"TZif" {
+        return None;
+    }
+    let version = reader.u8()?;
+    reader.take(15)?;
+    let mut counts = [0usize; 6];
+    for count in counts.iter_mut() {
+        *count = reader.u32()? as usize;
+    }
+    Some((version, TzifCounts(counts)))
+}
+
+// 解析TZif文件（RFC 8536），版本2及以上使用64位数据块和末尾的POSIX规则
+fn parse_tzif(data: &[u8]) -> Option<TimeZone> {
+    let mut reader = Reader { data, pos: 0 };
+    let (version, counts) = read_header(&mut reader)?;
+    let (counts, time_size) = if version >= b'2' {
+        // 跳过32位数据块，读取其后64位数据块的头部
+        let [isut, isstd, leap, time, types, chars] = counts.0;
+        reader.take(time * 5 + types * 6 + chars + leap * 8 + isstd + isut)?;
+        (read_header(&mut reader)?.1, 8)
+    } else {
+        (counts, 4)
+    };
+    let [isut, isstd, leap, time_count, type_count, char_count] = counts.0;
+
+    let mut times = Vec::with_capacity(time_count);
+    for _ in 0..time_count {
+        times.push(if time_size == 8 {
+            reader.i64()?
+        } else {
+            reader.i32()? as i64
+        });
+    }
+    let indices = reader.take(time_count)?.to_vec();
+    let mut offsets = Vec::with_capacity(type_count);
+    for _ in 0..type_count {
+        let offset = reader.i32()?;
+        reader.take(2)?;
+        offsets.push(offset);
+    }
+    reader.take(char_count + leap * (time_size + 4) + isstd + isut)?;
+
+    let transitions = times
+        .into_iter()
+        .zip(indices)
+        .map(|(at, index)| offsets.get(index as usize).map(|offset| (at, *offset)))
+        .collect::<Option<Vec<_>>>()?;
+
+    // 末尾的POSIX规则位于两个换行符之间
+    let rule = if version >= b'2' {
+        std::str::from_utf8(&data[reader.pos..])
+            .ok()
+            .and_then(|footer| footer.trim_matches('\n').lines().next())
+            .filter(|footer| !footer.is_empty())
+            .and_then(parse_posix_rule)
+    } else {
+        None
+    };
+
+    Some(TimeZone {
+        transitions,
+        initial_offset: *offsets.first()?,
+        rule,
+    })
+}
+
+// 解析POSIX TZ字符串，如 PST8PDT,M3.2.0,M11.1.0
+fn parse_posix_rule(spec: &str) -> Option<PosixRule> {
+    let mut rest = skip_zone_name(spec)?;
+    let (std_posix, after) = parse_posix_offset(rest)?;
+    rest = after;
+    // POSIX偏移为西正东负
+    let std_offset = -std_posix;
+    if rest.is_empty() {
+        return Some(PosixRule {
+            std_offset,
+            dst: None,
+        });
+    }
+
+    rest = skip_zone_name(rest)?;
+    let dst_offset = match parse_posix_offset(rest) {
+        Some((offset, after)) => {
+            rest = after;
+            -offset
+        }
+        _ => std_offset + 3600,
+    };
+
+    // 缺少规则时按美国规则处理
+    let rules = rest.strip_prefix(',').unwrap_or("M3.2.0,M11.1.0");
+    let (start, end) = rules.split_once(',')?;
+    let (start, start_time) = parse_rule_date(start)?;
+    let (end, end_time) = parse_rule_date(end)?;
+
+    Some(PosixRule {
+        std_offset,
+        dst: Some(DstRule {
+            offset: dst_offset,
+            start,
+            start_time,
+            end,
+            end_time,
+        }),
+    })
+}
+
+// 跳过时区缩写：字母序列或 <...> 括起的名称
+fn skip_zone_name(spec: &str) -> Option<&str> {
+    if let Some(quoted) = spec.strip_prefix('<') {
+        return quoted.split_once('>').map(|(_, rest)| rest);
+    }
+    let len = spec
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(spec.len());
+    (len >= 3).then(|| &spec[len..])
+}
+
+// 解析 [+-]hh[:mm[:ss]]，返回秒数和剩余部分
+fn parse_posix_offset(spec: &str) -> Option<(i32, &str)> {
+    let (sign, body) = match spec.as_bytes().first()? {
+        b'-' => (-1, &spec[1..]),
+        b'+' => (1, &spec[1..]),
+        _ => (1, spec),
+    };
+    let len = body
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(body.len());
+    if len == 0 {
+        return None;
+    }
+
+    let mut secs = 0i32;
+    for (i, part) in body[..len].split(':').enumerate() {
+        let value: i32 = part.parse().ok()?;
+        secs += value * [3600, 60, 1].get(i)?;
+    }
+    Some((sign * secs, &body[len..]))
+}
+
+// 解析规则日期及可选的 /时间，时间默认为 02:00:00
+fn parse_rule_date(spec: &str) -> Option<(RuleDate, i32)> {
+    let (date, time) = match spec.split_once('/') {
+        Some((date, time)) => (date, parse_posix_offset(time)?.0),
+        None => (spec, 7200),
+    };
+
+    let date = if let Some(day) = date.strip_prefix('J') {
+        RuleDate::Julian(day.parse().ok()?)
+    } else if let Some(mwd) = date.strip_prefix('M') {
+        let mut parts = mwd.split('.').map(|part| part.parse::<u32>().ok());
+        RuleDate::MonthWeekDay(parts.next()??, parts.next()??, parts.next()??)
+    } else {
+        RuleDate::ZeroBased(date.parse().ok()?)
+    };
+    Some((date, time))
+}
+
+impl RuleDate {
+    // 规则在指定年份对应的日期
+    fn date_in(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            RuleDate::Julian(day) => {
+                let date = NaiveDate::from_yo_opt(year, day.min(365))?;
+                // Jn 不计2月29日，闰年3月起的日期顺延一天
+                if date.leap_year() && day >= 60 {
+                    date.succ_opt()
+                } else {
+                    Some(date)
+                }
+            }
+            RuleDate::ZeroBased(day) => NaiveDate::from_yo_opt(year, day + 1),
+            RuleDate::MonthWeekDay(month, week, weekday) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week.max(1) - 1) * 7;
+                let days_in_month = first
+                    .checked_add_months(chrono::Months::new(1))?
+                    .pred_opt()?
+                    .day();
+                while day > days_in_month {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd_opt(year, month, day)
+            }
+        }
+    }
+}
+
+impl PosixRule {
+    fn offset_at(&self, timestamp: i64) -> i32 {
+        let Some(dst) = &self.dst else {
+            return self.std_offset;
+        };
+        let Some(year) = DateTime::from_timestamp(timestamp + self.std_offset as i64, 0)
+            .map(|local| local.year())
+        else {
+            return self.std_offset;
+        };
+
+        // 开始时刻以标准时间表示，结束时刻以夏令时表示
+        let transition = |date: &RuleDate, time: i32, offset: i32| {
+            date.date_in(year)
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|midnight| midnight.and_utc().timestamp() + time as i64 - offset as i64)
+        };
+        let (Some(start), Some(end)) = (
+            transition(&dst.start, dst.start_time, self.std_offset),
+            transition(&dst.end, dst.end_time, dst.offset),
+        ) else {
+            return self.std_offset;
+        };
+
+        // 南半球的夏令时跨年，开始时刻晚于结束时刻
+        let in_dst = if start < end {
+            start <= timestamp && timestamp < end
+        } else {
+            !(end <= timestamp && timestamp < start)
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled(name: &str) -> TimeZone {
+        TimeZone::from_bundled_rules(name).unwrap()
+    }
+
+    fn end_of_day(zone: &TimeZone, year: i32, month: u32, day: u32) -> String {
+        let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        zone.end_of_day(date).unwrap().to_rfc3339()
+    }
+
+    #[test]
+    fn every_bundled_rule_parses() {
+        for line in BUNDLED_RULES.lines().filter(|line| !line.starts_with('#')) {
+            let (zone, rule) = line.split_once(' ').unwrap();
+            assert!(parse_posix_rule(rule).is_some(), "{}: {}", zone, rule);
+        }
+        assert!(TimeZone::from_bundled_rules("Nowhere/City").is_none());
+    }
+
+    #[test]
+    fn end_of_day_follows_the_local_offset() {
+        let los_angeles = bundled("America/Los_Angeles");
+        // 夏令时在 02:00 开始，前后两天各自在本地 23:59:59 结束
+        assert_eq!(
+            end_of_day(&los_angeles, 2024, 3, 9),
+            "2024-03-10T07:59:59+00:00"
+        );
+        assert_eq!(
+            end_of_day(&los_angeles, 2024, 3, 10),
+            "2024-03-11T06:59:59+00:00"
+        );
+        assert_eq!(
+            end_of_day(&bundled("Asia/Shanghai"), 2024, 3, 31),
+            "2024-03-31T15:59:59+00:00"
+        );
+    }
+
+    #[test]
+    fn day_ends_at_spring_forward_when_midnight_is_skipped() {
+        // 圣地亚哥在9月第一个星期六的 24:00 拨快到 01:00，次日零点不存在
+        let santiago = bundled("America/Santiago");
+        assert_eq!(
+            end_of_day(&santiago, 2024, 9, 6),
+            "2024-09-07T03:59:59+00:00"
+        );
+        assert_eq!(
+            end_of_day(&santiago, 2024, 9, 7),
+            "2024-09-08T03:59:59+00:00"
+        );
+        // 拨快后的第一天按夏令时（UTC-3）结束
+        assert_eq!(
+            end_of_day(&santiago, 2024, 9, 8),
+            "2024-09-09T02:59:59+00:00"
+        );
+    }
+
+    #[test]
+    fn day_ends_at_the_second_late_hour_after_fall_back() {
+        // 圣地亚哥在4月第一个星期六的 24:00 拨回到 23:00，23:00-23:59 出现两次
+        let santiago = bundled("America/Santiago");
+        assert_eq!(
+            end_of_day(&santiago, 2024, 4, 5),
+            "2024-04-06T02:59:59+00:00"
+        );
+        assert_eq!(
+            end_of_day(&santiago, 2024, 4, 6),
+            "2024-04-07T03:59:59+00:00"
+        );
+    }
+
+    #[test]
+    fn system_zoneinfo_agrees_with_bundled_rules() {
+        // 没有系统时区数据库时 load 使用内置规则，两者自然一致
+        let system = TimeZone::load("Europe/London").unwrap();
+        let bundled = bundled("Europe/London");
+        for (month, day) in [(3, 30), (3, 31), (10, 26), (10, 27), (12, 31)] {
+            assert_eq!(
+                end_of_day(&system, 2024, month, day),
+                end_of_day(&bundled, 2024, month, day)
+            );
+        }
+    }
+}
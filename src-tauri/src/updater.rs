@@ -0,0 +1,356 @@
+use crate::settings;
+use base64::{engine::general_purpose, Engine as _};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_updater::{Error as UpdaterError, Update, Updater, UpdaterExt};
+
+// 单次重试等待时间上限
+const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+// 更新操作的重试参数
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 3,
+            base_delay_ms: 1_000,
+        }
+    }
+}
+
+// 重试事件，前端据此显示“正在重试…”
+#[derive(Debug, Clone, Serialize)]
+struct RetryEvent {
+    operation: &'static str,
+    attempt: u32,
+    max_attempts: u32,
+    delay_ms: u64,
+    error: String,
+}
+
+// 下载进度事件
+#[derive(Debug, Clone, Serialize)]
+struct ProgressEvent {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+// 只有网络类错误才值得重试
+fn is_transient(error: &UpdaterError) -> bool {
+    matches!(error, UpdaterError::Reqwest(_) | UpdaterError::Network(_))
+}
+
+// 按指数退避重试异步操作，重试次数用尽后返回最后一次的错误
+async fn with_retry<T, F, Fut>(
+    app: &AppHandle,
+    operation: &'static str,
+    config: RetryConfig,
+    mut action: F,
+) -> Result<T, UpdaterError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, UpdaterError>>,
+{
+    let max_attempts = config.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match action().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < max_attempts && is_transient(&e) => {
+                let delay_ms = config
+                    .base_delay_ms
+                    .saturating_mul(1u64 << (attempt - 1).min(16))
+                    .min(MAX_RETRY_DELAY_MS);
+                let _ = app.emit(
+                    "update://retry",
+                    RetryEvent {
+                        operation,
+                        attempt,
+                        max_attempts,
+                        delay_ms,
+                        error: e.to_string(),
+                    },
+                );
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// 创建更新器，运行时设置了更新地址时使用该地址
+fn build_updater(app: &AppHandle) -> Result<Updater, String> {
+    let endpoint = settings::load_settings()
+        .map_err(|e| e.to_string())?
+        .update_endpoint;
+
+    match endpoint {
+        Some(endpoint) => {
+            let url = Url::parse(&endpoint).map_err(|e| e.to_string())?;
+            app.updater_builder()
+                .endpoints(vec![url])
+                .and_then(|builder| builder.build())
+                .map_err(|e| e.to_string())
+        }
+        None => app.updater().map_err(|e| e.to_string()),
+    }
+}
+
+// 获取当前生效的更新地址：运行时设置的地址，或编译时配置的第一个地址
+pub fn get_update_endpoint(app: &AppHandle) -> Result<Option<String>, String> {
+    if let Some(endpoint) = settings::load_settings()
+        .map_err(|e| e.to_string())?
+        .update_endpoint
+    {
+        return Ok(Some(endpoint));
+    }
+
+    Ok(app
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("endpoints"))
+        .and_then(|endpoints| endpoints.get(0))
+        .and_then(|endpoint| endpoint.as_str())
+        .map(|endpoint| endpoint.to_string()))
+}
+
+// 更新器配置状态，只包含公钥的标识信息
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdaterConfigStatus {
+    pub pubkey_configured: bool,
+    // minisign 公钥中的密钥ID，与 tauri signer 生成密钥时显示的一致
+    pub key_id: Option<String>,
+    // 公钥的SHA-256指纹
+    pub fingerprint: Option<String>,
+    pub endpoint: Option<String>,
+    // 更新地址来自运行时设置而非编译时配置
+    pub endpoint_overridden: bool,
+    // 发现的配置问题，为空表示可以接收签名的更新
+    pub problems: Vec<String>,
+}
+
+// 解析 tauri.conf.json 中的 minisign 公钥（Base64编码的公钥文件内容），返回密钥ID和指纹
+fn parse_updater_pubkey(pubkey: &str) -> Result<(String, String), String> {
+    let text = general_purpose::STANDARD
+        .decode(pubkey.trim())
+        .map_err(|e| format!("公钥不是有效的Base64: {}", e))?;
+    let text = String::from_utf8(text).map_err(|_| "公钥文件内容不是文本".to_string())?;
+    // 第一行为注释，第二行为Base64编码的公钥：2字节算法、8字节密钥ID（小端）、32字节Ed25519公钥
+    let key_line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .ok_or_else(|| "公钥文件中没有公钥".to_string())?;
+    let key = general_purpose::STANDARD
+        .decode(key_line)
+        .map_err(|e| format!("公钥不是有效的Base64: {}", e))?;
+    if key.len() != 42 || &key[..2] != b"Ed" {
+        return Err("公钥不是 minisign Ed25519 公钥".to_string());
+    }
+
+    let key_id: Vec<u8> = key[2..10].iter().rev().copied().collect();
+    Ok((
+        hex::encode_upper(key_id),
+        hex::encode(Sha256::digest(&key[10..])),
+    ))
+}
+
+/// 检查更新器的公钥和更新地址配置，用于发布前确认应用能够接收并验证签名的更新
+pub fn get_updater_config_status(app: &AppHandle) -> Result<UpdaterConfigStatus, String> {
+    let config = app.config().plugins.0.get("updater").cloned();
+    let pubkey = config
+        .as_ref()
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|pubkey| pubkey.as_str())
+        .filter(|pubkey| !pubkey.trim().is_empty());
+    let endpoint_overridden = settings::load_settings()
+        .map_err(|e| e.to_string())?
+        .update_endpoint
+        .is_some();
+    let endpoint = get_update_endpoint(app)?;
+
+    let mut problems = Vec::new();
+    let (key_id, fingerprint) = match pubkey.map(parse_updater_pubkey) {
+        Some(Ok((key_id, fingerprint))) => (Some(key_id), Some(fingerprint)),
+        Some(Err(e)) => {
+            problems.push(format!("更新器公钥无效: {}", e));
+            (None, None)
+        }
+        None => {
+            problems.push("未配置更新器公钥，无法验证更新包签名".to_string());
+            (None, None)
+        }
+    };
+    match endpoint.as_deref().map(Url::parse) {
+        Some(Ok(url)) if url.scheme() == "https" => {}
+        Some(Ok(_)) => problems.push("更新地址未使用HTTPS".to_string()),
+        Some(Err(e)) => problems.push(format!("更新地址无效: {}", e)),
+        None => problems.push("未配置更新地址".to_string()),
+    }
+
+    Ok(UpdaterConfigStatus {
+        pubkey_configured: pubkey.is_some(),
+        key_id,
+        fingerprint,
+        endpoint,
+        endpoint_overridden,
+        problems,
+    })
+}
+
+// 检查是否有可用更新
+pub async fn check_update(app: &AppHandle, config: RetryConfig) -> Result<String, String> {
+    let updater = build_updater(app)?;
+
+    let update_response = with_retry(app, "check", config, || updater.check())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if update_response.is_some() {
+        Ok("有更新可用".to_string())
+    } else {
+        Ok("当前已是最新版本".to_string())
+    }
+}
+
+// 仅下载不安装的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateDownloadResult {
+    pub version: String,
+    pub current_version: String,
+    pub downloaded_bytes: usize,
+    // 更新包的签名是否通过更新器公钥验证
+    pub signature_verified: bool,
+    pub message: String,
+}
+
+// 下载更新包，失败时重试，每次重试重新统计进度；下载完成后由更新器验证签名
+async fn download_with_progress(
+    app: &AppHandle,
+    update: &Update,
+    config: RetryConfig,
+) -> Result<Vec<u8>, UpdaterError> {
+    with_retry(app, "download", config, || {
+        let mut downloaded = 0u64;
+        update.download(
+            move |chunk_length, content_length| {
+                downloaded += chunk_length as u64;
+                let _ = app.emit(
+                    "update://progress",
+                    ProgressEvent {
+                        downloaded,
+                        total: content_length,
+                    },
+                );
+            },
+            || {},
+        )
+    })
+    .await
+}
+
+// 签名验证失败的错误
+fn is_signature_error(error: &UpdaterError) -> bool {
+    matches!(
+        error,
+        UpdaterError::Minisign(_) | UpdaterError::SignatureUtf8(_) | UpdaterError::Base64(_)
+    )
+}
+
+// 检查并下载更新、验证签名，但不安装，用于发布前验证更新包
+pub async fn download_update_only(
+    app: &AppHandle,
+    config: RetryConfig,
+) -> Result<UpdateDownloadResult, String> {
+    let updater = build_updater(app)?;
+
+    let update = with_retry(app, "check", config, || updater.check())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let Some(update) = update else {
+        return Err("没有可用的更新".to_string());
+    };
+
+    let (downloaded_bytes, signature_verified, message) =
+        match download_with_progress(app, &update, config).await {
+            Ok(bytes) => (bytes.len(), true, "更新包已下载，签名验证通过".to_string()),
+            Err(e) if is_signature_error(&e) => (0, false, format!("更新包签名验证失败: {}", e)),
+            Err(e) => return Err(e.to_string()),
+        };
+
+    Ok(UpdateDownloadResult {
+        version: update.version.clone(),
+        current_version: update.current_version.clone(),
+        downloaded_bytes,
+        signature_verified,
+        message,
+    })
+}
+
+// 安装结果，同时作为 update://installed 事件发送
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateInstallResult {
+    pub installed_version: String,
+    // 新版本在重启应用后才会运行
+    pub restart_required: bool,
+    // 各平台安装方式的说明
+    pub note: String,
+}
+
+// 各平台的安装行为说明
+fn install_note() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "Windows 安装程序（NSIS/MSI）会关闭当前应用并完成安装，安装后重新启动应用即可使用新版本"
+    } else if cfg!(target_os = "macos") {
+        "已替换应用包，重启应用后生效"
+    } else {
+        "已替换 AppImage 文件，重启应用后生效"
+    }
+}
+
+// 下载并安装更新，下载过程中发送进度事件，安装完成后发送 update://installed 事件
+pub async fn install_update(
+    app: &AppHandle,
+    config: RetryConfig,
+) -> Result<UpdateInstallResult, String> {
+    let updater = build_updater(app)?;
+
+    // 检查是否有可用更新
+    let update = with_retry(app, "check", config, || updater.check())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let Some(update) = update else {
+        return Err("没有可用的更新".to_string());
+    };
+
+    let bytes = download_with_progress(app, &update, config)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 安装不涉及网络，不重试
+    update.install(bytes).map_err(|e| e.to_string())?;
+
+    // 各平台的新版本都要在进程重启后才会运行
+    let result = UpdateInstallResult {
+        installed_version: update.version.clone(),
+        restart_required: true,
+        note: install_note().to_string(),
+    };
+    let _ = app.emit("update://installed", result.clone());
+    Ok(result)
+}
@@ -0,0 +1,88 @@
+use crate::clock;
+use crate::license::validate_license;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter};
+
+// 最短检查间隔，避免频繁读取密钥和数据库
+const MIN_WATCH_INTERVAL_SECS: u64 = 10;
+
+// 当前运行中的监控任务，同一时间只保留一个
+static WATCH_TASK: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+// 许可证状态变化事件
+#[derive(Debug, Clone, Serialize)]
+struct LicenseStatusEvent {
+    is_valid: bool,
+    message: String,
+    checked_at: DateTime<Utc>,
+}
+
+// 重新验证一次许可证，验证本身出错时视为无效
+async fn check_once(license_key: &str, profile: Option<&str>) -> (bool, String) {
+    let key = license_key.to_string();
+    let profile = profile.map(|p| p.to_string());
+    let result =
+        tauri::async_runtime::spawn_blocking(move || validate_license(&key, profile.as_deref()))
+            .await;
+
+    match result {
+        Ok(Ok(validation)) => (validation.is_valid, validation.message),
+        Ok(Err(e)) => (false, e.to_string()),
+        Err(e) => (false, e.to_string()),
+    }
+}
+
+/// 启动后台监控：定期重新验证许可证，状态变化时发送 `license://status` 事件
+pub fn start_license_watch(
+    app: AppHandle,
+    license_key: String,
+    interval_secs: u64,
+    profile: Option<String>,
+) {
+    let interval = Duration::from_secs(interval_secs.max(MIN_WATCH_INTERVAL_SECS));
+
+    let handle = tauri::async_runtime::spawn(async move {
+        let mut last: Option<(bool, String)> = None;
+        loop {
+            let status = check_once(&license_key, profile.as_deref()).await;
+            if last.as_ref() != Some(&status) {
+                let _ = app.emit(
+                    "license://status",
+                    LicenseStatusEvent {
+                        is_valid: status.0,
+                        message: status.1.clone(),
+                        checked_at: clock::now(),
+                    },
+                );
+                last = Some(status);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+
+    // 替换已有任务，旧任务直接取消
+    let mut task = WATCH_TASK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(previous) = task.replace(handle) {
+        previous.abort();
+    }
+}
+
+/// 停止后台监控，返回是否有任务在运行
+pub fn stop_license_watch() -> bool {
+    let mut task = WATCH_TASK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match task.take() {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => false,
+    }
+}
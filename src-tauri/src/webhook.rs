@@ -0,0 +1,91 @@
+use crate::audit;
+use crate::license::LicenseInfo;
+use crate::settings::load_settings;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+
+// 发送给CRM的许可证签发通知
+#[derive(Debug, Clone, Serialize)]
+struct LicenseIssuedPayload {
+    event: &'static str,
+    license_id: String,
+    customer_email: String,
+    expiry_date: DateTime<Utc>,
+}
+
+// 发送一次通知请求
+async fn post_payload(
+    client: &reqwest::Client,
+    url: &str,
+    payload: &LicenseIssuedPayload,
+) -> Result<(), String> {
+    let response = client
+        .post(url)
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP状态码 {}", response.status()))
+    }
+}
+
+// 将通知结果写入审计日志；通知在后台执行，写入失败时无处上报，只能忽略
+fn record_outcome(license_id: &str, detail: &str) {
+    let _ = audit::record("webhook", license_id, detail);
+}
+
+// 在后台通知Webhook许可证已签发；不阻塞也不影响签发结果，失败时重试一次
+pub fn notify_license_issued(info: &LicenseInfo) {
+    let url = match load_settings() {
+        Ok(settings) => settings.webhook_url,
+        Err(e) => {
+            record_outcome(&info.license_id, &format!("读取Webhook设置失败: {}", e));
+            return;
+        }
+    };
+    let Some(url) = url else {
+        return;
+    };
+
+    let payload = LicenseIssuedPayload {
+        event: "license.issued",
+        license_id: info.license_id.clone(),
+        customer_email: info.customer_email.clone(),
+        expiry_date: info.expiry_date,
+    };
+
+    tauri::async_runtime::spawn(async move {
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                record_outcome(
+                    &payload.license_id,
+                    &format!("创建Webhook客户端失败: {}", e),
+                );
+                return;
+            }
+        };
+
+        let result = match post_payload(&client, &url, &payload).await {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                post_payload(&client, &url, &payload).await
+            }
+        };
+
+        let detail = match result {
+            Ok(()) => "Webhook通知成功".to_string(),
+            Err(e) => format!("Webhook通知重试后仍失败: {}", e),
+        };
+        record_outcome(&payload.license_id, &detail);
+    });
+}
@@ -19,17 +19,35 @@ interface LicenseInfo {
   machine_code?: string;
 }
 
+interface LicenseIssuance {
+  license_key: string;
+  warnings: string[];
+}
+
+interface MachineCodeInput {
+  code: string;
+  version: number;
+}
+
 interface LicenseValidationResult {
   is_valid: boolean;
   info?: LicenseInfo;
   message: string;
 }
 
+interface MachineVerificationResult {
+  machine_id: string;
+  machine_matched: boolean;
+  result: LicenseValidationResult;
+}
+
 function App() {
   const [activeTab, setActiveTab] = useState<'generate' | 'validate' | 'history'>('generate');
   
   // 生成许可证状态
   const [machineCode, setMachineCode] = useState("");
+  // 客户计算机器码时使用的算法版本，随机器码一起由客户提供
+  const [machineCodeVersion, setMachineCodeVersion] = useState("");
   const [customerName, setCustomerName] = useState("");
   const [customerEmail, setCustomerEmail] = useState("");
   const [expiryDays, setExpiryDays] = useState("365");
@@ -40,6 +58,8 @@ function App() {
   const [licenseKey, setLicenseKey] = useState("");
   const [validationResult, setValidationResult] = useState<LicenseValidationResult | null>(null);
   const [validationMachineCode, setValidationMachineCode] = useState("");
+  // 机器码来自“获取当前机器码”且未被修改时，按许可证记录的算法版本在本机重新计算机器码
+  const [validateOnCurrentMachine, setValidateOnCurrentMachine] = useState(false);
   
   // 历史许可证
   const [licenseHistory, setLicenseHistory] = useState<LicenseInfo[]>([]);
@@ -69,17 +89,20 @@ function App() {
   // 获取当前机器码
   async function getCurrentMachineId() {
     try {
-      const machineId = await invoke<string>("get_current_machine_id");
       if (activeTab === 'generate') {
-        setMachineCode(machineId);
+        const input = await invoke<MachineCodeInput>("get_machine_code_input");
+        setMachineCode(input.code);
+        setMachineCodeVersion(String(input.version));
       } else if (activeTab === 'validate') {
+        const machineId = await invoke<string>("get_current_machine_id");
         setValidationMachineCode(machineId);
+        setValidateOnCurrentMachine(true);
       }
-    } catch (error) {
+    } catch (error: any) {
       console.error("获取机器码失败:", error);
       Modal.error({
         title: '获取机器码失败',
-        content: `错误: ${error}`
+        content: `错误: ${error?.message ?? error}`
       });
     }
   }
@@ -95,15 +118,23 @@ function App() {
       const actualExpiryDays = parsedExpiryDays <= 0 ? 0 : parsedExpiryDays;
       
       if (machineCode) {
-        license = await invoke<string>("generate_license_key_with_machine_code", { 
+        const version = parseInt(machineCodeVersion);
+        if (!(version > 0)) {
+          Modal.error({
+            title: '缺少机器码版本',
+            content: '请填写客户提供的机器码算法版本'
+          });
+          return;
+        }
+        license = await invoke<LicenseIssuance>("generate_license_key_with_machine_code", { 
           customerName, 
           customerEmail, 
           expiryDays: actualExpiryDays, 
           features: featuresList,
-          machineCode
+          machineCode: { code: machineCode, version }
         });
       } else {
-        license = await invoke<string>("generate_license_key", { 
+        license = await invoke<LicenseIssuance>("generate_license_key", { 
           customerName, 
           customerEmail, 
           expiryDays: actualExpiryDays, 
@@ -111,15 +142,29 @@ function App() {
         });
       }
       
-      setGeneratedLicense(license);
+      setGeneratedLicense(license.license_key);
+      // 宽松模式下不在功能目录中的功能等警告
+      if (license.warnings.length > 0) {
+        Modal.warning({
+          title: '许可证已生成，但有以下警告',
+          content: license.warnings.join('\n')
+        });
+      }
       // 如果成功生成许可证，清空表单
       setCustomerName("");
       setCustomerEmail("");
       setExpiryDays("365");
       setFeatures("");
-    } catch (error) {
+    } catch (error: any) {
       console.error("生成许可证失败:", error);
-      setGeneratedLicense(`错误: ${error}`);
+      // 密钥目录不可写时给出单独的提示
+      if (error?.code === 'permission_error') {
+        Modal.error({
+          title: '没有密钥目录的访问权限',
+          content: error.message
+        });
+      }
+      setGeneratedLicense(`错误: ${error?.message ?? error}`);
     }
   }
   
@@ -128,7 +173,12 @@ function App() {
     try {
       let result;
       
-      if (validationMachineCode) {
+      if (validationMachineCode && validateOnCurrentMachine) {
+        const verification = await invoke<MachineVerificationResult>("verify_current_machine", {
+          licenseKey
+        });
+        result = verification.result;
+      } else if (validationMachineCode) {
         result = await invoke<LicenseValidationResult>("validate_license_key_with_machine_code", { 
           licenseKey,
           machineCode: validationMachineCode
@@ -140,11 +190,11 @@ function App() {
       }
       
       setValidationResult(result);
-    } catch (error) {
+    } catch (error: any) {
       console.error("验证许可证失败:", error);
       setValidationResult({
         is_valid: false,
-        message: `错误: ${error}`
+        message: `错误: ${error?.message ?? error}`
       });
     }
   }
@@ -158,11 +208,19 @@ function App() {
       Modal.success({
         content: '许可证已删除'
       });
-    } catch (error) {
+    } catch (error: any) {
+      // 许可证已不存在（如已在其他窗口删除），刷新列表即可，无需报错
+      if (error?.code === 'not_found') {
+        loadLicenseHistory();
+        Modal.info({
+          content: '许可证已不存在'
+        });
+        return;
+      }
       console.error("删除许可证失败:", error);
       Modal.error({
         title: '删除失败',
-        content: `错误: ${error}`
+        content: `错误: ${error?.message ?? error}`
       });
     }
   }
@@ -220,11 +278,11 @@ function App() {
       const key = await invoke<string>("export_license_public_key");
       setPublicKey(key);
       setIsPublicKeyModalOpen(true);
-    } catch (error) {
+    } catch (error: any) {
       console.error("导出公钥失败:", error);
       Modal.error({
-        title: '导出公钥失败',
-        content: `${error}`
+        title: error?.code === 'permission_error' ? '没有密钥目录的访问权限' : '导出公钥失败',
+        content: `${error?.message ?? error}`
       });
     }
   }
@@ -329,6 +387,14 @@ function App() {
                 onChange={(e) => setMachineCode(e.target.value)}
                 placeholder="输入机器码..."
               />
+              <Input
+                id="machine-code-version"
+                type="number"
+                value={machineCodeVersion}
+                onChange={(e) => setMachineCodeVersion(e.target.value)}
+                placeholder="机器码版本"
+                style={{ width: 120 }}
+              />
               <Button onClick={getCurrentMachineId} type="primary" className="get-machine-code-btn">
                 获取当前机器码
               </Button>
@@ -406,7 +472,10 @@ function App() {
               <Input
                 id="validation-machine-code"
                 value={validationMachineCode}
-                onChange={(e) => setValidationMachineCode(e.target.value)}
+                onChange={(e) => {
+                  setValidationMachineCode(e.target.value);
+                  setValidateOnCurrentMachine(false);
+                }}
                 placeholder="输入机器码..."
               />
               <Button onClick={getCurrentMachineId} type="primary" className="get-machine-code-btn">
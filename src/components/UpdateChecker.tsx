@@ -10,6 +10,12 @@ interface UpdatePayload {
   body?: string;
 }
 
+interface UpdateInstallResult {
+  installed_version: string;
+  restart_required: boolean;
+  note: string;
+}
+
 export function UpdateChecker() {
   const [checking, setChecking] = useState(false);
   const [updateAvailable, setUpdateAvailable] = useState(false);
@@ -83,11 +89,13 @@ export function UpdateChecker() {
         }
       });
       
-      // 监听更新就绪
-      const unlistenReady = await listen('tauri://update-ready', async () => {
-        message.success('更新已下载，即将重启应用');
-        // 重启应用以安装更新
-        await relaunch();
+      // 监听安装完成
+      const unlistenReady = await listen<UpdateInstallResult>('update://installed', async (event) => {
+        message.success(`已安装版本 ${event.payload.installed_version}，${event.payload.note}`);
+        // 需要重启时重启应用以运行新版本
+        if (event.payload.restart_required) {
+          await relaunch();
+        }
       });
       
       // 监听更新错误